@@ -2,10 +2,22 @@
 extern crate criterion;
 
 use automata::ca::*;
+use automata::memo::nth_layer_memo;
+use automata::packed::PackedLayer;
+use automata::store::{next_layer_generic_store, LayerStore, Packed64};
 use criterion::{black_box, Criterion};
 
 fn nth_layer(n: usize) -> Vec<bool> {
-    iter_layers(30).skip(n).next().unwrap()
+    iter_layers(30).nth(n).unwrap()
+}
+
+// Steps `seed` forward `n` generations, holding the layers in `S`.
+fn nth_layer_in<S: LayerStore>(seed: &S, n: usize) -> S {
+    let mut layer = seed.clone();
+    for _ in 0..n {
+        layer = next_layer_generic_store(30, &layer);
+    }
+    layer
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -14,7 +26,52 @@ fn criterion_benchmark(c: &mut Criterion) {
         |b, n| b.iter(|| nth_layer(black_box(*n))),
         vec![10, 50, 200],
     );
+
+    // Stepping the same wide layer, allocating a new one every time versus
+    // reusing a buffer the way `Simulation` does with its pool.
+    let layer = nth_layer(1000);
+    c.bench_function("step_allocating", move |b| {
+        b.iter(|| next_layer(30, black_box(&layer)))
+    });
+    let layer = nth_layer(1000);
+    c.bench_function("step_reusing", move |b| {
+        let mut out = Vec::new();
+        b.iter(|| next_layer_into(30, black_box(&layer), false, &mut out))
+    });
+
+    // The same stepping through each layer store.
+    let layer = nth_layer(1000);
+    let bools = Vec::<bool>::from_bools(&layer);
+    c.bench_function("store_vec_bool", move |b| {
+        b.iter(|| nth_layer_in(black_box(&bools), 10))
+    });
+    let bytes = PackedLayer::from_bools(&layer);
+    c.bench_function("store_packed_u8", move |b| {
+        b.iter(|| nth_layer_in(black_box(&bytes), 10))
+    });
+    let words = Packed64::from_bools(&layer);
+    c.bench_function("store_packed_u64", move |b| {
+        b.iter(|| nth_layer_in(black_box(&words), 10))
+    });
+}
+
+// Jumping far ahead by memoizing blocks, against stepping the whole way. Each
+// naive run takes seconds, so these take fewer samples.
+fn memo_benchmark(c: &mut Criterion) {
+    for &rule in &[30, 90] {
+        c.bench_function(&format!("nth_layer_memo_{}", rule), move |b| {
+            b.iter(|| nth_layer_memo(rule, black_box(50_000)))
+        });
+        c.bench_function(&format!("nth_layer_naive_{}", rule), move |b| {
+            b.iter(|| iter_layers(rule).nth(black_box(50_000)))
+        });
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
+criterion_group! {
+    name = memo;
+    config = Criterion::default().sample_size(10);
+    targets = memo_benchmark
+}
+criterion_main!(benches, memo);
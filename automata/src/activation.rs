@@ -0,0 +1,199 @@
+use crate::ca::take_layers;
+use std::io::{self, Write};
+
+/// The first generation at which each column of a run was alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationMap {
+    first: Vec<Option<usize>>,
+    // The global column of `first[0]`.
+    left: isize,
+}
+
+impl ActivationMap {
+    /// The first generation at which `column` was alive, if it ever was.
+    pub fn get(&self, column: isize) -> Option<usize> {
+        let index = column - self.left;
+        if index < 0 {
+            return None;
+        }
+        self.first.get(index as usize).copied().flatten()
+    }
+
+    /// The first activation of every column the run reached, starting from
+    /// column `left()`.
+    pub fn first(&self) -> &[Option<usize>] {
+        &self.first
+    }
+
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    /// The leftmost and rightmost columns which were ever alive.
+    pub fn extent(&self) -> Option<(isize, isize)> {
+        let leftmost = self.first.iter().position(Option::is_some)?;
+        let rightmost = self.first.iter().rposition(Option::is_some)?;
+        Some((
+            self.left + leftmost as isize,
+            self.left + rightmost as isize,
+        ))
+    }
+}
+
+/// Works out when each column first comes alive over the first `generations`
+/// layers of `rule`, in a single pass over them. Columns run from `-(n - 1)` to
+/// `n - 1`, for `n` generations.
+pub fn first_activation(rule: u8, generations: usize) -> ActivationMap {
+    let width = (2 * generations).saturating_sub(1);
+    let left = -(generations as isize - 1);
+    let mut first = vec![None; width];
+
+    for (generation, layer) in take_layers(rule, generations).enumerate() {
+        // Layer `n` spans columns `-n..=n`.
+        let start = (-(generation as isize) - left) as usize;
+        for (i, &cell) in layer.iter().enumerate() {
+            let slot = &mut first[start + i];
+            if cell && slot.is_none() {
+                *slot = Some(generation);
+            }
+        }
+    }
+
+    ActivationMap { first, left }
+}
+
+// Shades of grey from the 256-color palette, from black to nearly white.
+const GRAYSCALE: std::ops::RangeInclusive<u8> = 232..=255;
+
+/// Writes `map` as a line of text, with each activated column shaded from
+/// black (activated first) to white (activated last, at `generations - 1`),
+/// and a `.` for each column which never came alive. Columns activated any
+/// later than that, which a map of a longer run can have, are white too.
+pub fn write_activation_ansi<W: Write>(
+    mut w: W,
+    map: &ActivationMap,
+    generations: usize,
+) -> io::Result<()> {
+    let shades = (GRAYSCALE.end() - GRAYSCALE.start()) as usize;
+    for first in map.first() {
+        match first {
+            None => write!(w, "\x1b[0m.")?,
+            Some(generation) => {
+                let shade = (generation.saturating_mul(shades)
+                    / generations.saturating_sub(1).max(1))
+                .min(shades);
+                write!(w, "\x1b[38;5;{}m#", GRAYSCALE.start() + shade as u8)?
+            }
+        }
+    }
+    writeln!(w, "\x1b[0m")
+}
+
+#[cfg(feature = "image")]
+pub use self::png::write_activation_png;
+
+#[cfg(feature = "image")]
+mod png {
+    use super::ActivationMap;
+    use crate::raster::{lerp, write_png, Rgb};
+    use std::io::{self, Write};
+
+    const NEVER: Rgb = [255, 255, 255];
+    const EARLY: Rgb = [0, 0, 0];
+    const LATE: Rgb = [200, 200, 200];
+
+    /// Writes `map` as a PNG strip `height` pixels tall, with each activated
+    /// column shaded from black to light grey by when it first came alive, and
+    /// the rest left white.
+    pub fn write_activation_png<W: Write>(
+        w: W,
+        map: &ActivationMap,
+        generations: usize,
+        height: usize,
+    ) -> io::Result<()> {
+        let row: Vec<Rgb> = map
+            .first()
+            .iter()
+            .map(|first| match first {
+                None => NEVER,
+                Some(generation) => lerp(
+                    EARLY,
+                    LATE,
+                    *generation as f64 / generations.saturating_sub(1).max(1) as f64,
+                ),
+            })
+            .collect();
+        let pixels: Vec<Rgb> = (0..height).flat_map(|_| row.iter().copied()).collect();
+        write_png(w, row.len(), height, &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_254_light_cone() {
+        let map = first_activation(254, 20);
+        assert_eq!(map.left(), -19);
+        for k in 0..20 {
+            assert_eq!(map.get(k), Some(k as usize));
+            assert_eq!(map.get(-k), Some(k as usize));
+        }
+        assert_eq!(map.get(20), None);
+        assert_eq!(map.extent(), Some((-19, 19)));
+    }
+
+    #[test]
+    fn sparse_rules() {
+        // Rule 90's edges are always alive, so every column lights up as soon
+        // as the edge reaches it, even though many go dead again.
+        let map = first_activation(90, 8);
+        assert_eq!(map.get(-5), Some(5));
+        assert_eq!(map.get(2), Some(2));
+
+        // Rule 4 only ever keeps the single cell alive.
+        let map = first_activation(4, 8);
+        assert_eq!(map.extent(), Some((0, 0)));
+        assert_eq!(map.get(1), None);
+
+        // Rule 2 moves the cell left, leaving the right untouched.
+        let map = first_activation(2, 8);
+        assert_eq!(map.extent(), Some((-7, 0)));
+        assert_eq!(map.get(-3), Some(3));
+    }
+
+    #[test]
+    fn extent_matches_growth() {
+        // Rule 30 grows by one cell either side every generation.
+        let map = first_activation(30, 50);
+        assert_eq!(map.extent(), Some((-49, 49)));
+
+        // Rule 0 dies straight away, but the seed was alive.
+        assert_eq!(first_activation(0, 10).extent(), Some((0, 0)));
+        assert_eq!(first_activation(30, 0).extent(), None);
+    }
+
+    #[test]
+    fn ansi() {
+        let map = first_activation(2, 3);
+        let mut out = Vec::new();
+        write_activation_ansi(&mut out, &map, 3).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[38;5;255m#\x1b[38;5;243m#\x1b[38;5;232m#\x1b[0m.\x1b[0m.\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn ansi_longer_map() {
+        // Shading a 30 generation map as if it were 2 generations long would
+        // take the later columns past the end of the grayscale ramp.
+        let map = first_activation(254, 30);
+        let mut out = Vec::new();
+        write_activation_ansi(&mut out, &map, 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.matches("\x1b[38;5;255m#").count(), 2 * 29);
+        assert_eq!(text.matches("\x1b[38;5;232m#").count(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, Write};
+
+/// Tracks how many generations each cell has been continuously alive for.
+///
+/// Cells are identified by their global column, so the tracker works the same
+/// for fixed-width runs (where every layer starts at column 0) and growing runs
+/// (where layer `n` starts at column `-n`). Columns which a layer doesn't cover
+/// are treated as dead.
+#[derive(Debug, Clone, Default)]
+pub struct AgeTracker {
+    ages: Vec<u32>,
+
+    // The global column of `ages[0]`.
+    left: isize,
+}
+
+impl AgeTracker {
+    pub fn new() -> AgeTracker {
+        AgeTracker::default()
+    }
+
+    /// Records the next layer of a run, whose first cell is at global column `left`.
+    pub fn observe(&mut self, layer: &[bool], left: isize) {
+        if self.ages.is_empty() {
+            self.left = left;
+        }
+
+        // Widen the tracked region to cover the layer, if it doesn't already.
+        let new_left = left.min(self.left);
+        let new_right = (left + layer.len() as isize).max(self.right());
+        if new_left < self.left || new_right > self.right() {
+            let mut ages = vec![0; (self.left - new_left) as usize];
+            ages.append(&mut self.ages);
+            ages.resize((new_right - new_left) as usize, 0);
+            self.ages = ages;
+            self.left = new_left;
+        }
+
+        for (i, age) in self.ages.iter_mut().enumerate() {
+            let index = self.left + i as isize - left;
+            let alive = index >= 0 && *layer.get(index as usize).unwrap_or(&false);
+            // A cell which outlives what a u32 can count stays at the oldest age.
+            *age = if alive { age.saturating_add(1) } else { 0 };
+        }
+    }
+
+    /// Records the next layer of a growing run, where layer `n` is `2n + 1`
+    /// cells wide and centered on column 0.
+    pub fn observe_centered(&mut self, layer: &[bool]) {
+        self.observe(layer, -(layer.len() as isize / 2));
+    }
+
+    /// The age of each tracked cell. Dead cells have age 0, and a cell which
+    /// has been alive in just the latest layer has age 1.
+    pub fn ages(&self) -> &[u32] {
+        &self.ages
+    }
+
+    /// The global column of the first entry in `ages()`.
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    fn right(&self) -> isize {
+        self.left + self.ages.len() as isize
+    }
+}
+
+// 256-color palette entries for live cells, from young (white) to old (red).
+// Ages are bucketed by powers of two: 1, 2-3, 4-7, 8-15, 16-31, and 32+.
+const AGE_COLORS: [u8; 6] = [231, 226, 220, 214, 208, 196];
+
+/// Picks the 256-color palette entry for a live cell of the given age.
+pub fn age_color(age: u32) -> u8 {
+    assert!(age > 0, "dead cells don't have a color");
+    let bucket = (31 - age.leading_zeros()) as usize;
+    AGE_COLORS[bucket.min(AGE_COLORS.len() - 1)]
+}
+
+/// Writes a line of text for `ages`, with a `#` colored by age for each live
+/// cell and a `.` for each dead one.
+pub fn write_ages_ansi<W: Write>(mut w: W, ages: &[u32]) -> io::Result<()> {
+    for &age in ages {
+        if age == 0 {
+            write!(w, ".")?;
+        } else {
+            write!(w, "\x1b[38;5;{}m#", age_color(age))?;
+        }
+    }
+    writeln!(w, "\x1b[0m")
+}
+
+#[cfg(feature = "image")]
+pub use self::png::write_ages_png;
+
+#[cfg(feature = "image")]
+mod png {
+    use crate::raster::{lerp, write_png, Rgb};
+    use std::io::{self, Write};
+
+    const DEAD: Rgb = [255, 255, 255];
+    const YOUNG: Rgb = [255, 220, 0];
+    const OLD: Rgb = [120, 0, 0];
+
+    /// Writes a PNG with a row of pixels for each entry in `rows`, where every
+    /// row is the same width. Dead cells are white, and live ones shade from
+    /// yellow to dark red as they approach `max_age`.
+    pub fn write_ages_png<W: Write>(w: W, rows: &[Vec<u32>], max_age: u32) -> io::Result<()> {
+        let width = rows.first().map_or(0, Vec::len);
+        let pixels: Vec<Rgb> = rows
+            .iter()
+            .flatten()
+            .map(|&age| match age {
+                0 => DEAD,
+                age => lerp(YOUNG, OLD, (age - 1) as f64 / (max_age.max(2) - 1) as f64),
+            })
+            .collect();
+        write_png(w, width, rows.len(), &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::{iter_layers, next_layer_wrapping};
+
+    #[test]
+    fn blinking_ages() {
+        // Under rule 108 on a ring, the lone cell holds steady, while the middle
+        // of the triple blinks on and off.
+        let mut layer = vec![
+            false, true, false, false, false, true, true, true, false, false,
+        ];
+        let mut tracker = AgeTracker::new();
+
+        let mut history = Vec::new();
+        for _ in 0..4 {
+            tracker.observe(&layer, 0);
+            history.push(tracker.ages().to_vec());
+            layer = next_layer_wrapping(108, &layer);
+        }
+
+        assert_eq!(history[0], &[0, 1, 0, 0, 0, 1, 1, 1, 0, 0]);
+        assert_eq!(history[1], &[0, 2, 0, 0, 0, 2, 0, 2, 0, 0]);
+        assert_eq!(history[2], &[0, 3, 0, 0, 0, 3, 1, 3, 0, 0]);
+        assert_eq!(history[3], &[0, 4, 0, 0, 0, 4, 0, 4, 0, 0]);
+    }
+
+    #[test]
+    fn reset_on_death() {
+        let mut tracker = AgeTracker::new();
+        tracker.observe(&[true, true], 0);
+        tracker.observe(&[true, true], 0);
+        tracker.observe(&[true, false], 0);
+        assert_eq!(tracker.ages(), &[3, 0]);
+        tracker.observe(&[true, true], 0);
+        assert_eq!(tracker.ages(), &[4, 1]);
+    }
+
+    #[test]
+    fn oldest_age() {
+        let mut tracker = AgeTracker::new();
+        tracker.observe(&[true, true], 0);
+        tracker.ages[0] = u32::MAX - 1;
+        tracker.observe(&[true, true], 0);
+        tracker.observe(&[true, true], 0);
+        assert_eq!(tracker.ages(), &[u32::MAX, 3]);
+        tracker.observe(&[false, true], 0);
+        assert_eq!(tracker.ages(), &[0, 4]);
+    }
+
+    #[test]
+    fn growing_layers() {
+        let mut tracker = AgeTracker::new();
+        for layer in iter_layers(254).take(4) {
+            tracker.observe_centered(&layer);
+        }
+
+        // Rule 254 fills in the light cone, so column k came alive at generation |k|.
+        assert_eq!(tracker.left(), -3);
+        assert_eq!(tracker.ages(), &[1, 2, 3, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn narrower_layers() {
+        let mut tracker = AgeTracker::new();
+        tracker.observe(&[true, true, true], -1);
+        tracker.observe(&[true], 0);
+        assert_eq!(tracker.left(), -1);
+        assert_eq!(tracker.ages(), &[0, 2, 0]);
+    }
+
+    #[test]
+    fn ansi_codes() {
+        let mut out = Vec::new();
+        write_ages_ansi(&mut out, &[0, 1, 3, 40]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ".\x1b[38;5;231m#\x1b[38;5;226m#\x1b[38;5;196m#\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn color_buckets() {
+        assert_eq!(age_color(1), 231);
+        assert_eq!(age_color(2), 226);
+        assert_eq!(age_color(3), 226);
+        assert_eq!(age_color(4), 220);
+        assert_eq!(age_color(31), 208);
+        assert_eq!(age_color(32), 196);
+        assert_eq!(age_color(u32::MAX), 196);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn png_ages() {
+        let mut out = Vec::new();
+        write_ages_png(&mut out, &[vec![0, 1], vec![8, 0]], 8).unwrap();
+
+        let mut reader = ::png::Decoder::new(&out[..]).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(
+            &buf[..12],
+            &[255, 255, 255, 255, 220, 0, 120, 0, 0, 255, 255, 255]
+        );
+    }
+}
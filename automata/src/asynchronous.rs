@@ -0,0 +1,167 @@
+//! Asynchronous updating, where cells are updated one at a time in a random
+//! order rather than all at once. Each update sees any changes made by the
+//! ones before it, which can change a rule's behavior completely.
+
+use crate::ca::test_rule;
+use crate::rng::Rng;
+use std::mem;
+
+/// Updates `updates` cells of `layer`, one at a time, each chosen uniformly at
+/// random. Each cell is recomputed from its neighbors as they are at that
+/// moment, wrapping round at the edges.
+pub fn step_async(rule: u8, layer: &mut [bool], updates: usize, rng: &mut Rng) {
+    let width = layer.len();
+    if width == 0 {
+        return;
+    }
+    for _ in 0..updates {
+        let i = rng.below(width);
+        let left = layer[(i + width - 1) % width];
+        let right = layer[(i + 1) % width];
+        layer[i] = test_rule(rule, (left, layer[i], right));
+    }
+}
+
+/// Iterates through the layers of an asynchronous run of `rule` from `seed`,
+/// making `updates_per_sweep` random updates between each layer. The seed
+/// is the first layer, and the same `rng_seed` always gives the same run.
+pub fn iter_layers_async(
+    rule: u8,
+    seed: Vec<bool>,
+    updates_per_sweep: usize,
+    rng_seed: u64,
+) -> AsyncLayers {
+    AsyncLayers {
+        rule,
+        current: seed,
+        updates_per_sweep,
+        rng: Rng::new(rng_seed),
+    }
+}
+
+/// The layers of an asynchronous run, one sweep after another. Made by
+/// `iter_layers_async`.
+#[derive(Debug, Clone)]
+pub struct AsyncLayers {
+    rule: u8,
+    /// The layer which will be yielded next.
+    current: Vec<bool>,
+    updates_per_sweep: usize,
+    rng: Rng,
+}
+
+impl Iterator for AsyncLayers {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        let mut next = self.current.clone();
+        step_async(self.rule, &mut next, self.updates_per_sweep, &mut self.rng);
+        Some(mem::replace(&mut self.current, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::next_layer_wrapping;
+    use crate::seeds;
+
+    fn density(layer: &[bool]) -> f64 {
+        layer.iter().filter(|&&cell| cell).count() as f64 / layer.len() as f64
+    }
+
+    // The fraction of cells which are alive along with their right neighbor.
+    fn pair_density(layer: &[bool]) -> f64 {
+        let pairs = (0..layer.len())
+            .filter(|&i| layer[i] && layer[(i + 1) % layer.len()])
+            .count();
+        pairs as f64 / layer.len() as f64
+    }
+
+    // Averages `measure` over the second half of `layers`.
+    fn late_mean(layers: &[Vec<bool>], measure: fn(&[bool]) -> f64) -> f64 {
+        let late = &layers[layers.len() / 2..];
+        late.iter().map(|layer| measure(layer)).sum::<f64>() / late.len() as f64
+    }
+
+    fn sync_run(rule: u8, seed: Vec<bool>, generations: usize) -> Vec<Vec<bool>> {
+        std::iter::successors(Some(seed), |layer| Some(next_layer_wrapping(rule, layer)))
+            .take(generations)
+            .collect()
+    }
+
+    #[test]
+    fn deterministic() {
+        let seed = seeds::random(64, 0.5, 1);
+        let a: Vec<_> = iter_layers_async(30, seed.clone(), 64, 7)
+            .take(20)
+            .collect();
+        let b: Vec<_> = iter_layers_async(30, seed.clone(), 64, 7)
+            .take(20)
+            .collect();
+        let c: Vec<_> = iter_layers_async(30, seed.clone(), 64, 8)
+            .take(20)
+            .collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a[0], seed);
+    }
+
+    #[test]
+    fn identity_rule() {
+        let seed = seeds::random(50, 0.5, 3);
+        let mut layer = seed.clone();
+        step_async(204, &mut layer, 500, &mut Rng::new(1));
+        assert_eq!(layer, seed);
+    }
+
+    #[test]
+    fn rule_0_dies() {
+        let mut layer = seeds::random(40, 0.5, 4);
+        // Enough updates that every cell is all but certainly hit.
+        step_async(0, &mut layer, 40 * 20, &mut Rng::new(2));
+        assert!(layer.iter().all(|&cell| !cell));
+        step_async(0, &mut [], 10, &mut Rng::new(2));
+    }
+
+    #[test]
+    fn reads_current_state() {
+        // Under rule 254 a live cell spreads to anything next to it. Updating
+        // in place, it can spread more than one cell in a single sweep, which
+        // it never could synchronously.
+        let mut seed = vec![false; 30];
+        seed[0] = true;
+        let spread = (0..20)
+            .map(|rng_seed| {
+                let layer = iter_layers_async(254, seed.clone(), 30, rng_seed)
+                    .nth(1)
+                    .unwrap();
+                layer.iter().filter(|&&cell| cell).count()
+            })
+            .max()
+            .unwrap();
+        assert!(spread > 3);
+    }
+
+    #[test]
+    fn differs_from_sync() {
+        let width = 400;
+        let seed = seeds::random(width, 0.5, 1);
+        let sync_30 = sync_run(30, seed.clone(), 100);
+        let async_30: Vec<_> = iter_layers_async(30, seed.clone(), width, 2)
+            .take(100)
+            .collect();
+
+        // Rule 30 stays about half alive either way, but updating one cell at
+        // a time all but wipes out neighboring live cells.
+        assert!((late_mean(&sync_30, density) - 0.5).abs() < 0.05);
+        assert!((late_mean(&async_30, density) - 0.5).abs() < 0.05);
+        assert!(late_mean(&sync_30, pair_density) > 0.2);
+        assert!(late_mean(&async_30, pair_density) < 0.08);
+
+        // Rule 110 fills up noticeably more.
+        let sync_110 = sync_run(110, seed.clone(), 100);
+        let async_110: Vec<_> = iter_layers_async(110, seed, width, 2).take(100).collect();
+        assert!(late_mean(&async_110, density) > late_mean(&sync_110, density) + 0.1);
+    }
+}
@@ -0,0 +1,440 @@
+use crate::ca::{next_background, next_layer_fixed, next_layer_on, test_rule, Boundary};
+use crate::render::render_layer;
+use std::error::Error;
+use std::fmt;
+
+/// A layer on an infinite, uniform background, which might be alive. Only the
+/// cells between the first and last which differ from the background are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    cells: Vec<bool>,
+    left: isize,
+    background: bool,
+}
+
+/// How many cells of a layer are alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Population {
+    Finite(usize),
+    /// The background is alive, so there's no counting the live cells. This is
+    /// how many cells differ from the background instead.
+    Infinite {
+        differing: usize,
+    },
+}
+
+impl Layer {
+    /// Places `seed` at columns `0..seed.len()`, surrounded by `background`.
+    pub fn new(seed: &[bool], background: bool) -> Layer {
+        Layer::trimmed(seed.to_vec(), 0, background)
+    }
+
+    fn trimmed(mut cells: Vec<bool>, mut left: isize, background: bool) -> Layer {
+        let start = cells
+            .iter()
+            .position(|&cell| cell != background)
+            .unwrap_or(cells.len());
+        let end = cells
+            .iter()
+            .rposition(|&cell| cell != background)
+            .map_or(start, |i| i + 1);
+
+        cells.truncate(end);
+        cells.drain(..start);
+        left += start as isize;
+
+        Layer {
+            cells,
+            left,
+            background,
+        }
+    }
+
+    /// The stored cells, which start at column `left()`. Every cell outside
+    /// them is the background.
+    pub fn cells(&self) -> &[bool] {
+        &self.cells
+    }
+
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    pub fn background(&self) -> bool {
+        self.background
+    }
+
+    /// Gets the cell at `column`.
+    pub fn get(&self, column: isize) -> bool {
+        let index = column - self.left;
+        if index < 0 {
+            return self.background;
+        }
+        *self.cells.get(index as usize).unwrap_or(&self.background)
+    }
+
+    pub fn population(&self) -> Population {
+        let differing = self
+            .cells
+            .iter()
+            .filter(|&&cell| cell != self.background)
+            .count();
+        if self.background {
+            Population::Infinite { differing }
+        } else {
+            Population::Finite(differing)
+        }
+    }
+
+    /// Generates the next layer under `rule`, background and all.
+    pub fn step(&self, rule: u8) -> Layer {
+        Layer::trimmed(
+            next_layer_on(rule, &self.cells, self.background),
+            self.left - 1,
+            next_background(rule, self.background),
+        )
+    }
+
+    /// Renders columns `from..to` as text, like `render_layer`.
+    pub fn render(&self, from: isize, to: isize) -> String {
+        let cells: Vec<bool> = (from..to).map(|column| self.get(column)).collect();
+        render_layer(&cells)
+    }
+}
+
+/// The rule which does to inverted cells what `rule` does to ordinary ones.
+pub fn complement_rule(rule: u8) -> u8 {
+    // The output for the inverted neighbourhood, itself inverted, for each
+    // neighbourhood in turn.
+    (0..8).fold(0, |out, input| out | ((!rule >> (7 - input)) & 1) << input)
+}
+
+/// The longest period a periodic background can have before we give up on it.
+pub const MAX_BACKGROUND_PERIOD: usize = 1 << 12;
+
+/// Why a tile can't be used as a background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackgroundError {
+    EmptyTile,
+    /// The tile never comes back to itself under `rule`, at least not within
+    /// `MAX_BACKGROUND_PERIOD` generations.
+    NotPeriodic {
+        rule: u8,
+    },
+}
+
+impl fmt::Display for BackgroundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackgroundError::EmptyTile => write!(f, "background tile is empty"),
+            BackgroundError::NotPeriodic { rule } => write!(
+                f,
+                "background tile doesn't repeat under rule {} within {} generations",
+                rule, MAX_BACKGROUND_PERIOD
+            ),
+        }
+    }
+}
+
+impl Error for BackgroundError {}
+
+/// An infinite background made of a tile repeated over and over, such as the
+/// "ether" of rule 110. Every generation of it is worked out up front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodicBackground {
+    rule: u8,
+    /// The tile at each generation, until it comes back around to the first.
+    phases: Vec<Vec<bool>>,
+    /// Which cell of the tile lands on column 0.
+    phase: usize,
+}
+
+impl PeriodicBackground {
+    /// Repeats `tile` in both directions, with `tile[phase]` on column 0. The
+    /// tile has to be periodic under `rule`.
+    pub fn new(
+        rule: u8,
+        tile: &[bool],
+        phase: usize,
+    ) -> Result<PeriodicBackground, BackgroundError> {
+        if tile.is_empty() {
+            return Err(BackgroundError::EmptyTile);
+        }
+
+        let mut phases = vec![tile.to_vec()];
+        loop {
+            let next = next_layer_fixed(rule, phases.last().unwrap(), Boundary::Wrapping);
+            if next == tile {
+                break;
+            }
+            if phases.len() == MAX_BACKGROUND_PERIOD {
+                return Err(BackgroundError::NotPeriodic { rule });
+            }
+            phases.push(next);
+        }
+
+        Ok(PeriodicBackground {
+            rule,
+            phases,
+            phase: phase % tile.len(),
+        })
+    }
+
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    /// The number of generations before the background repeats exactly.
+    pub fn period(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Gets the background cell at `column` in `generation`.
+    pub fn get(&self, generation: usize, column: isize) -> bool {
+        let tile = &self.phases[generation % self.period()];
+        tile[(column + self.phase as isize).rem_euclid(tile.len() as isize) as usize]
+    }
+}
+
+/// A layer on a periodic background, keeping only the cells between the first
+/// and last which differ from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodicLayer<'a> {
+    background: &'a PeriodicBackground,
+    generation: usize,
+    cells: Vec<bool>,
+    left: isize,
+}
+
+impl<'a> PeriodicLayer<'a> {
+    /// Places `seed` at columns starting from `left`, on generation 0 of
+    /// `background`.
+    pub fn new(
+        background: &'a PeriodicBackground,
+        seed: &[bool],
+        left: isize,
+    ) -> PeriodicLayer<'a> {
+        PeriodicLayer::trimmed(background, 0, seed.to_vec(), left)
+    }
+
+    fn trimmed(
+        background: &'a PeriodicBackground,
+        generation: usize,
+        mut cells: Vec<bool>,
+        mut left: isize,
+    ) -> PeriodicLayer<'a> {
+        let differs = |i: usize, cell: bool| cell != background.get(generation, left + i as isize);
+        let start = (0..cells.len())
+            .position(|i| differs(i, cells[i]))
+            .unwrap_or(cells.len());
+        let end = (0..cells.len())
+            .rposition(|i| differs(i, cells[i]))
+            .map_or(start, |i| i + 1);
+
+        cells.truncate(end);
+        cells.drain(..start);
+        left += start as isize;
+
+        PeriodicLayer {
+            background,
+            generation,
+            cells,
+            left,
+        }
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The stored cells, which start at column `left()`. Every cell outside
+    /// them matches the background.
+    pub fn cells(&self) -> &[bool] {
+        &self.cells
+    }
+
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    /// Gets the cell at `column`.
+    pub fn get(&self, column: isize) -> bool {
+        let index = column - self.left;
+        if index < 0 || index as usize >= self.cells.len() {
+            return self.background.get(self.generation, column);
+        }
+        self.cells[index as usize]
+    }
+
+    /// The number of cells which differ from the background.
+    pub fn differing(&self) -> usize {
+        (0..self.cells.len())
+            .filter(|&i| {
+                self.cells[i] != self.background.get(self.generation, self.left + i as isize)
+            })
+            .count()
+    }
+
+    /// Generates the next layer, moving the background on a generation too.
+    pub fn step(&self) -> PeriodicLayer<'a> {
+        let left = self.left - 1;
+        let cells = (left..left + self.cells.len() as isize + 2)
+            .map(|column| {
+                let input = (self.get(column - 1), self.get(column), self.get(column + 1));
+                test_rule(self.background.rule(), input)
+            })
+            .collect();
+        PeriodicLayer::trimmed(self.background, self.generation + 1, cells, left)
+    }
+
+    /// Renders columns `from..to` as text, like `render_layer`.
+    pub fn render(&self, from: isize, to: isize) -> String {
+        let cells: Vec<bool> = (from..to).map(|column| self.get(column)).collect();
+        render_layer(&cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::next_layer;
+    use crate::seeds;
+
+    // The background of rule 110, which repeats every 7 generations.
+    fn ether() -> PeriodicBackground {
+        let tile = seeds::from_str("#####...#..##.").unwrap();
+        PeriodicBackground::new(110, &tile, 0).unwrap()
+    }
+
+    #[test]
+    fn matches_next_layer_on_dead_background() {
+        let mut layer = Layer::new(&[true], false);
+        let mut plain = vec![true];
+        for generation in 1..20 {
+            layer = layer.step(30);
+            plain = next_layer(30, &plain);
+
+            let from = -(generation as isize);
+            assert_eq!(layer.render(from, -from + 1), render_layer(&plain));
+        }
+    }
+
+    #[test]
+    fn rule_255_fixed_point() {
+        let mut layer = Layer::new(&[false], true);
+        assert_eq!(layer.population(), Population::Infinite { differing: 1 });
+
+        for _ in 0..100 {
+            layer = layer.step(255);
+            assert!(layer.cells().is_empty());
+            assert!(layer.background());
+        }
+        assert_eq!(layer.population(), Population::Infinite { differing: 0 });
+        assert_eq!(layer.render(-2, 3), "#####");
+    }
+
+    #[test]
+    fn flipping_background() {
+        // Rule 1 turns an empty background alive, and then back again.
+        let layer = Layer::new(&[true], false).step(1);
+        assert!(layer.background());
+        assert_eq!(layer.render(-3, 4), "##...##");
+        assert_eq!(layer.population(), Population::Infinite { differing: 3 });
+
+        let layer = layer.step(1);
+        assert_eq!(layer.render(-3, 4), "...#...");
+        assert!(!layer.background());
+        assert_eq!(layer.population(), Population::Finite(1));
+    }
+
+    #[test]
+    fn complement_rules() {
+        assert_eq!(complement_rule(0), 255);
+        assert_eq!(complement_rule(30), 135);
+        assert_eq!(complement_rule(110), 137);
+        for rule in 0..=255 {
+            assert_eq!(complement_rule(complement_rule(rule)), rule);
+        }
+    }
+
+    #[test]
+    fn complement_equivalence() {
+        for rule in 0..=255 {
+            let seed = seeds::random(12, 0.5, rule as u64);
+            let inverted: Vec<bool> = seed.iter().map(|&cell| !cell).collect();
+
+            let mut layer = Layer::new(&seed, false);
+            let mut complement = Layer::new(&inverted, true);
+            for _ in 0..10 {
+                layer = layer.step(rule);
+                complement = complement.step(complement_rule(rule));
+
+                assert_eq!(layer.background(), !complement.background());
+                for column in -15..30 {
+                    assert_eq!(layer.get(column), !complement.get(column));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ether_is_invariant() {
+        let ether = ether();
+        assert_eq!(ether.period(), 7);
+
+        let mut layer = PeriodicLayer::new(&ether, &[], 0);
+        for generation in 0..28 {
+            assert!(layer.cells().is_empty());
+            assert_eq!(layer.generation(), generation);
+            assert_eq!(layer.differing(), 0);
+            layer = layer.step();
+        }
+        // After four periods, the tile is back where it started.
+        assert_eq!(layer.render(0, 14), "#####...#..##.");
+    }
+
+    #[test]
+    fn defect_on_ether() {
+        let ether = ether();
+        let mut layer = PeriodicLayer::new(&ether, &[!ether.get(0, 0)], 0);
+
+        // Worked out separately, on a wide ring of ether.
+        let expected = [
+            ("#...#..##..####...#..##.", 1),
+            ("#..##.###.##..#..##.####", 2),
+            ("#.#####.####.##.#####...", 3),
+            ("###...###..######...#..#", 5),
+            ("..#..##.#.##....#..##.##", 2),
+        ];
+        for &(row, differing) in &expected {
+            assert_eq!(layer.render(-10, 14), row);
+            assert_eq!(layer.differing(), differing);
+            layer = layer.step();
+        }
+    }
+
+    #[test]
+    fn rejects_bad_tiles() {
+        // Rule 0 kills everything, so a live cell never comes back.
+        let error = PeriodicBackground::new(0, &[true, false], 0).unwrap_err();
+        assert_eq!(error, BackgroundError::NotPeriodic { rule: 0 });
+        assert_eq!(
+            error.to_string(),
+            "background tile doesn't repeat under rule 0 within 4096 generations"
+        );
+
+        assert_eq!(
+            PeriodicBackground::new(110, &[], 0),
+            Err(BackgroundError::EmptyTile)
+        );
+    }
+
+    #[test]
+    fn phase_shifts_tile() {
+        let tile = [true, false, false];
+        let background = PeriodicBackground::new(204, &tile, 1).unwrap();
+        assert_eq!(background.period(), 1);
+        let cells: Vec<bool> = (-3..3).map(|column| background.get(5, column)).collect();
+        assert_eq!(render_layer(&cells), "..#..#");
+    }
+}
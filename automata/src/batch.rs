@@ -0,0 +1,140 @@
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Runs `f` for each of `rules` across `threads` worker threads, returning each
+/// rule alongside its result, in the same order as `rules`. See `run_parallel`.
+pub fn run_rules_parallel<T, F>(rules: &[u8], f: F, threads: usize) -> Vec<(u8, T)>
+where
+    T: Send,
+    F: Fn(u8) -> T + Sync,
+{
+    let results = run_parallel(rules, |&rule| f(rule), threads);
+    rules.iter().copied().zip(results).collect()
+}
+
+/// Runs `f` for each of `items` across `threads` worker threads, returning the
+/// results in the same order as `items`.
+///
+/// Workers pull items off a shared queue, so slow items don't hold up the rest.
+/// If `f` panics for any item, the panic is re-raised here once all the workers
+/// have stopped. With a single thread, everything runs on the calling thread.
+pub fn run_parallel<I, T, F>(items: &[I], f: F, threads: usize) -> Vec<T>
+where
+    I: Sync,
+    T: Send,
+    F: Fn(&I) -> T + Sync,
+{
+    assert!(threads > 0, "need at least one thread to run on");
+
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    // The index into `items` of the next item to be picked up by a worker.
+    let next = AtomicUsize::new(0);
+
+    let worker = || {
+        let mut done = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            match items.get(i) {
+                Some(item) => done.push((i, f(item))),
+                None => return done,
+            }
+        }
+    };
+
+    let mut results: Vec<Option<T>> = items.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(items.len()))
+            .map(|_| scope.spawn(worker))
+            .collect();
+
+        for worker in workers {
+            match worker.join() {
+                Ok(done) => {
+                    for (i, result) in done {
+                        results[i] = Some(result);
+                    }
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+    });
+
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+    use std::time::{Duration, Instant};
+
+    // Counts the live cells in the 100th layer of `rule`.
+    fn population_100(rule: u8) -> usize {
+        let layer = iter_layers(rule).nth(100).unwrap();
+        layer.iter().filter(|&&cell| cell).count()
+    }
+
+    #[test]
+    fn matches_sequential() {
+        let rules: Vec<u8> = (0..=255).collect();
+        let sequential: Vec<_> = rules.iter().map(|&r| (r, population_100(r))).collect();
+
+        assert_eq!(run_rules_parallel(&rules, population_100, 4), sequential);
+        assert_eq!(run_rules_parallel(&rules, population_100, 1), sequential);
+    }
+
+    #[test]
+    fn preserves_order() {
+        let rules = [200, 3, 90, 30, 110, 0, 255, 17];
+        let results = run_rules_parallel(&rules, |rule| rule as u32 * 2, 3);
+        let expected: Vec<_> = rules.iter().map(|&r| (r, r as u32 * 2)).collect();
+        assert_eq!(results, expected);
+
+        assert!(run_rules_parallel(&[], |rule| rule, 4).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "rule 90 is broken")]
+    fn propagates_panics() {
+        run_rules_parallel(
+            &[30, 90, 110],
+            |rule| {
+                if rule == 90 {
+                    panic!("rule 90 is broken");
+                }
+                rule
+            },
+            2,
+        );
+    }
+
+    #[test]
+    fn runs_concurrently() {
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        run_rules_parallel(
+            &[1, 2, 3, 4],
+            |_| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+
+                // Hang around until another worker shows up, giving up eventually
+                // so that a sequential run fails rather than hangs.
+                let start = Instant::now();
+                while peak.load(Ordering::SeqCst) < 2 && start.elapsed() < Duration::from_secs(5) {
+                    thread::yield_now();
+                }
+
+                active.fetch_sub(1, Ordering::SeqCst);
+            },
+            2,
+        );
+
+        assert!(peak.load(Ordering::SeqCst) > 1);
+    }
+}
@@ -0,0 +1,213 @@
+use automata::ca::take_layers_from;
+use automata::cli::{render_life, run_rules_from_reader, terminal_width, RenderOptions};
+use automata::renderer::{Format, RenderCommon};
+use automata::rule::Rule;
+use automata::seed_input::{load_seed, load_seed_as, SeedFormat, SeedSource};
+use automata::seeds::{self, NamedSeed};
+use std::env;
+use std::io;
+use std::process;
+
+const USAGE: &str = "usage: automata [--generations N] [--width N] [--seed-name NAME] \
+                     [--seed-file PATH | --seed TEXT|-] [--seed-format text|rle] \
+                     [--format text|npy|png] (--rule N | --rgb N,N,N | --from-stdin | \
+                     --seed-name glider|blinker|r_pentomino|gosper_gun)";
+
+fn main() {
+    let mut opts = RenderOptions::default();
+    let mut rule: Option<u8> = None;
+    let mut from_stdin = false;
+    let mut format = Format::Text;
+    let mut rgb: Option<[u8; 3]> = None;
+    // Whether PNG output was asked for, which is handled separately when
+    // there's no image support, so that it can say so.
+    let mut png = false;
+    let mut seed_source: Option<SeedSource> = None;
+    let mut seed_format: Option<SeedFormat> = None;
+    // A 2D pattern to run Life from, given by --seed-name.
+    let mut life: Option<Vec<Vec<bool>>> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from-stdin" => from_stdin = true,
+            "--generations" => opts.generations = parse_value(&arg, args.next()),
+            "--rule" => {
+                // Either a rule number, or its 8-digit truth table.
+                let Rule(number) = parse_value(&arg, args.next());
+                rule = Some(number);
+            }
+            "--rgb" => {
+                // Three rules, for the red, green and blue channels.
+                let list: String = parse_value(&arg, args.next());
+                let rules: Vec<u8> = list
+                    .split(',')
+                    .map(|rule| {
+                        let Rule(number) = parse_value(&arg, Some(rule.trim().to_string()));
+                        number
+                    })
+                    .collect();
+                match rules[..] {
+                    [r, g, b] => rgb = Some([r, g, b]),
+                    _ => fail("--rgb needs exactly three rules"),
+                }
+                png = true;
+            }
+            "--width" => opts.width = Some(parse_value(&arg, args.next())),
+            "--format" => {
+                let name: String = parse_value(&arg, args.next());
+                png = name == "png";
+                match Format::from_name(&name) {
+                    Some(named) => format = named,
+                    None if png => (),
+                    None => fail(&format!("unknown format '{}'", name)),
+                }
+            }
+            "--seed-file" => {
+                let path: String = parse_value(&arg, args.next());
+                seed_source = Some(SeedSource::Path(path.into()));
+            }
+            "--seed" => {
+                let text: String = parse_value(&arg, args.next());
+                seed_source = Some(if text == "-" {
+                    SeedSource::Stdin
+                } else {
+                    SeedSource::Literal(text)
+                });
+            }
+            "--seed-format" => match parse_value::<String>(&arg, args.next()).as_str() {
+                "text" => seed_format = Some(SeedFormat::Text),
+                "rle" => seed_format = Some(SeedFormat::Rle),
+                other => fail(&format!("unknown seed format '{}'", other)),
+            },
+            "--seed-name" => {
+                let name: String = parse_value(&arg, args.next());
+                match seeds::by_name(&name) {
+                    Some(NamedSeed::Layer(seed)) => opts.seed = seed,
+                    Some(NamedSeed::Pattern(pattern)) => life = Some(pattern),
+                    None => fail(&format!("unknown seed '{}'", name)),
+                }
+            }
+            _ => fail(&format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    if let Some(source) = seed_source {
+        if source == SeedSource::Stdin && from_stdin {
+            fail("--seed - and --from-stdin can't both read stdin");
+        }
+        if life.is_some() {
+            fail("2D seeds can't be used with --seed or --seed-file");
+        }
+        let loaded = match seed_format {
+            Some(format) => load_seed_as(source, format),
+            None => load_seed(source),
+        };
+        match loaded {
+            Ok(seed) => opts.seed = seed,
+            Err(err) => fail(&err.to_string()),
+        }
+    }
+
+    if rgb.is_some() && !png {
+        fail("--rgb only makes PNGs");
+    }
+
+    // An explicit width wins over whatever the terminal says.
+    opts.width = opts.width.or_else(|| Some(terminal_width()));
+
+    let stdout = io::stdout();
+    if let Some(pattern) = life {
+        if rule.is_some() || rgb.is_some() || from_stdin {
+            fail("2D seeds run Life, so can't be used with --rule, --rgb or --from-stdin");
+        }
+        if format != Format::Text || png {
+            fail("2D seeds only render text");
+        }
+        if let Err(err) = render_life(stdout.lock(), &pattern, &opts) {
+            fail(&err.to_string());
+        }
+    } else if let Some(rules) = rgb {
+        if rule.is_some() || from_stdin {
+            fail("--rgb can't be used with --rule or --from-stdin");
+        }
+        if let Err(err) = write_rgb_png(stdout.lock(), rules, &opts) {
+            fail(&err.to_string());
+        }
+    } else if from_stdin {
+        if format != Format::Text || png {
+            // Arrays and images can't be concatenated like text.
+            fail("--from-stdin only renders text");
+        }
+        let stdin = io::stdin();
+        match run_rules_from_reader(stdin.lock(), stdout.lock(), &opts) {
+            Ok(summary) if summary.is_success() => (),
+            Ok(summary) => {
+                for token in &summary.invalid {
+                    eprintln!(
+                        "warning: skipped '{}', which isn't a rule from 0 to 255",
+                        token
+                    );
+                }
+                eprintln!(
+                    "rendered {} rules, skipped {} invalid tokens",
+                    summary.rendered.len(),
+                    summary.invalid.len()
+                );
+                process::exit(1);
+            }
+            Err(err) => fail(&err.to_string()),
+        }
+    } else if let Some(rule) = rule {
+        if png && !cfg!(feature = "image") {
+            fail("--format png needs the image feature");
+        }
+        let mut layers = take_layers_from(rule, opts.seed.clone(), opts.generations);
+        let common = RenderCommon {
+            max_width: opts.width,
+            layer_width: Some(layers.last_width()),
+            ..RenderCommon::default()
+        };
+        if let Err(err) = format
+            .renderer(common)
+            .render(&mut layers, &mut stdout.lock())
+        {
+            fail(&err.to_string());
+        }
+    } else {
+        fail("either --rule or --from-stdin is required");
+    }
+}
+
+#[cfg(feature = "image")]
+fn write_rgb_png<W: io::Write>(w: W, rules: [u8; 3], opts: &RenderOptions) -> io::Result<()> {
+    use automata::compose::{compose_rgb, ComposeOptions};
+    let compose = ComposeOptions {
+        seed: opts.seed.clone(),
+        ..ComposeOptions::default()
+    };
+    compose_rgb(rules, opts.generations, &compose).write_png(w)
+}
+
+#[cfg(not(feature = "image"))]
+fn write_rgb_png<W: io::Write>(_w: W, _rules: [u8; 3], _opts: &RenderOptions) -> io::Result<()> {
+    fail("--rgb needs the image feature")
+}
+
+// Parses the value following the flag `flag`, exiting if it's missing or malformed.
+fn parse_value<T>(flag: &str, value: Option<String>) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        Some(Err(err)) => fail(&format!("invalid value for {}: {}", flag, err)),
+        None => fail(&format!("missing value for {}", flag)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}\n{}", message, USAGE);
+    process::exit(2);
+}
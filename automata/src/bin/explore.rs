@@ -0,0 +1,93 @@
+use automata::explore::{apply_key, viewport, ExploreState, Key, LayerCache};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+use std::env;
+use std::io::{self, Write};
+
+fn main() -> io::Result<()> {
+    let rule = match env::args().nth(1).map(|arg| arg.parse()) {
+        None => 30,
+        Some(Ok(rule)) => rule,
+        Some(Err(_)) => {
+            eprintln!("usage: explore [RULE]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = run(&mut stdout, rule);
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run<W: Write>(out: &mut W, rule: u8) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    // Leave the bottom row for the status line.
+    let mut state = ExploreState::new(rule, rows.saturating_sub(1) as usize, cols as usize);
+    let mut cache = LayerCache::new(rule);
+
+    while !state.quit {
+        draw(out, &state, &mut cache)?;
+
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                if let Some(key) = translate_key(key) {
+                    state = apply_key(state, key);
+                }
+            }
+            Event::Resize(cols, rows) => {
+                state.rows = rows.saturating_sub(1) as usize;
+                state.cols = cols as usize;
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn draw<W: Write>(out: &mut W, state: &ExploreState, cache: &mut LayerCache) -> io::Result<()> {
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+    for (row, line) in viewport(state, cache).iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, row as u16), style::Print(line))?;
+    }
+
+    let status = match (&state.jump, &state.message) {
+        (Some(jump), _) => format!("jump to generation: {}", jump),
+        (None, Some(message)) => message.clone(),
+        (None, None) => format!(
+            "rule {} | generations {}-{} | column {} | arrows scroll, r/R rule, g jump, q quit",
+            state.rule,
+            state.top,
+            state.top + state.rows,
+            state.left
+        ),
+    };
+    queue!(
+        out,
+        cursor::MoveTo(0, state.rows as u16),
+        style::Print(status)
+    )?;
+    out.flush()
+}
+
+fn translate_key(key: KeyEvent) -> Option<Key> {
+    match key.code {
+        KeyCode::Up => Some(Key::Up),
+        KeyCode::Down => Some(Key::Down),
+        KeyCode::Left => Some(Key::Left),
+        KeyCode::Right => Some(Key::Right),
+        KeyCode::PageUp => Some(Key::PageUp),
+        KeyCode::PageDown => Some(Key::PageDown),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Backspace => Some(Key::Backspace),
+        KeyCode::Esc => Some(Key::Esc),
+        KeyCode::Char(c) => Some(Key::Char(c)),
+        _ => None,
+    }
+}
@@ -1,11 +1,56 @@
-use automata::ca;
-use std::time::Instant;
+use automata::checkpoint::{continue_with_checkpoints, resume_latest};
+use automata::observer::ProgressPrinter;
+use automata::simulation::Simulation;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+const GENERATIONS: usize = 50000;
+const CHECKPOINT_EVERY: usize = 5000;
 
 fn main() {
+    let mut checkpoint_dir: Option<PathBuf> = None;
+    let mut resume = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--checkpoint-dir" => checkpoint_dir = args.next().map(PathBuf::from),
+            "--resume" => resume = true,
+            _ => {
+                eprintln!("usage: first_10k [--checkpoint-dir DIR [--resume]]");
+                process::exit(2);
+            }
+        }
+    }
+
+    let mut sim = Simulation::new(30, vec![true]);
+    if let (Some(dir), true) = (&checkpoint_dir, resume) {
+        match resume_latest(dir) {
+            Ok(resumed) => {
+                for (path, err) in &resumed.skipped {
+                    eprintln!("warning: skipping checkpoint {}: {}", path.display(), err);
+                }
+                println!("Resuming from generation {}", resumed.state.generation);
+                sim = resumed.state.into_simulation();
+            }
+            Err(err) => eprintln!("Starting from scratch: {}", err),
+        }
+    }
+
     println!("Generating layers... ");
-    let start = Instant::now();
-    ca::iter_layers(30).skip(50000).next().unwrap();
-    let done = Instant::now();
-    let duration = done.duration_since(start);
-    println!("Done in {:?}", duration);
+    sim.observe(Box::new(ProgressPrinter::stdout(CHECKPOINT_EVERY)));
+    match &checkpoint_dir {
+        Some(dir) => {
+            if let Err(err) =
+                continue_with_checkpoints(&mut sim, GENERATIONS, CHECKPOINT_EVERY, dir)
+            {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        None => {
+            sim.run_until(GENERATIONS);
+        }
+    }
 }
@@ -0,0 +1,109 @@
+use automata::interest::{rank_rules, ScoreOptions};
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "usage: search [--rules N,N,...] [--seed N] [--top N] \
+                     [--render-top N --out-dir DIR]";
+
+fn main() {
+    let mut opts = ScoreOptions::default();
+    let mut rules: Vec<u8> = (0..=255).collect();
+    let mut top: Option<usize> = None;
+    let mut render_top = 0;
+    let mut out_dir: Option<PathBuf> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => {
+                let list: String = parse_value(&arg, args.next());
+                rules = list
+                    .split(',')
+                    .map(|rule| parse_value(&arg, Some(rule.trim().to_string())))
+                    .collect();
+            }
+            "--seed" => opts.seed = parse_value(&arg, args.next()),
+            "--top" => top = Some(parse_value(&arg, args.next())),
+            "--render-top" => render_top = parse_value(&arg, args.next()),
+            "--out-dir" => out_dir = Some(parse_value::<String>(&arg, args.next()).into()),
+            _ => fail(&format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    let ranked = rank_rules(&rules, &opts);
+    let shown = top.unwrap_or(ranked.len()).min(ranked.len());
+
+    println!(
+        "{:>4}  {:>4}  {:>6}  {:>7}  {:>8}  {:>8}  {:>9}",
+        "rank", "rule", "total", "entropy", "activity", "compress", "transient"
+    );
+    for (rank, score) in ranked[..shown].iter().enumerate() {
+        println!(
+            "{:>4}  {:>4}  {:>6.3}  {:>7.3}  {:>8.3}  {:>8.3}  {:>9.1}",
+            rank + 1,
+            score.rule,
+            score.total,
+            score.block_entropy,
+            score.activity,
+            score.compression_score,
+            score.transient
+        );
+    }
+
+    if render_top > 0 {
+        let dir = match out_dir {
+            Some(dir) => dir,
+            None => fail("--render-top needs --out-dir"),
+        };
+        let rules: Vec<u8> = ranked.iter().take(render_top).map(|s| s.rule).collect();
+        render(&dir, &rules);
+    }
+}
+
+#[cfg(feature = "image")]
+fn render(dir: &std::path::Path, rules: &[u8]) {
+    use automata::ca::take_layers;
+    use automata::raster::write_layers_png;
+    use std::fs::{self, File};
+    use std::io::BufWriter;
+
+    // How many layers of each rule to draw.
+    const GENERATIONS: usize = 256;
+
+    if let Err(err) = fs::create_dir_all(dir) {
+        fail(&format!("can't create {}: {}", dir.display(), err));
+    }
+    for &rule in rules {
+        let path = dir.join(format!("rule_{:03}.png", rule));
+        let layers: Vec<_> = take_layers(rule, GENERATIONS).collect();
+        let written =
+            File::create(&path).and_then(|file| write_layers_png(BufWriter::new(file), &layers));
+        if let Err(err) = written {
+            fail(&format!("can't write {}: {}", path.display(), err));
+        }
+    }
+}
+
+#[cfg(not(feature = "image"))]
+fn render(_dir: &std::path::Path, _rules: &[u8]) {
+    fail("rendering PNGs needs the image feature");
+}
+
+// Parses the value following the flag `flag`, exiting if it's missing or malformed.
+fn parse_value<T>(flag: &str, value: Option<String>) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        Some(Err(err)) => fail(&format!("invalid value for {}: {}", flag, err)),
+        None => fail(&format!("missing value for {}", flag)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}\n{}", message, USAGE);
+    process::exit(2);
+}
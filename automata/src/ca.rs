@@ -1,4 +1,6 @@
-use std::iter;
+use crate::render::{render_layer_with, CellStyle};
+use std::iter::{self, FusedIterator};
+use std::mem;
 
 fn get_bit(byte: u8, index: u8) -> bool {
     assert!(index < 8); // make sure index makes sense
@@ -31,29 +33,263 @@ pub fn test_rule(rule: u8, input: (bool, bool, bool)) -> bool {
 
 /// Generates the next layer in the CA with the given `rule` and `input` layer above.
 pub fn next_layer(rule: u8, input: &[bool]) -> Vec<bool> {
-    let mut out: Vec<bool> = Vec::new();
-    out.reserve(input.len() + 2); // Reserve the 2 new cells either side.
+    next_layer_on(rule, input, false)
+}
+
+/// Like `next_layer`, but every cell beyond `input` has the value `background`
+/// rather than being empty. The background itself moves on according to
+/// `next_background`.
+pub fn next_layer_on(rule: u8, input: &[bool], background: bool) -> Vec<bool> {
+    let mut out: Vec<bool> = Vec::with_capacity(input.len() + 2); // Reserve the 2 new cells either side.
+    next_layer_into(rule, input, background, &mut out);
+    out
+}
+
+/// Like `next_layer_on`, but writes the new layer into `out` rather than a new
+/// `Vec`. Anything already in `out` is cleared first.
+pub fn next_layer_into(rule: u8, input: &[bool], background: bool, out: &mut Vec<bool>) {
+    step_into(|a, b, c| test_rule(rule, (a, b, c)), input, background, out)
+}
+
+/// Generates the next layer using `f` in place of a rule number: `f` is given
+/// each cell's left neighbour, the cell, and its right neighbour, and returns
+/// the cell below.
+pub fn next_layer_with<F: Fn(bool, bool, bool) -> bool>(f: F, input: &[bool]) -> Vec<bool> {
+    let mut out: Vec<bool> = Vec::with_capacity(input.len() + 2);
+    step_into(f, input, false, &mut out);
+    out
+}
+
+// Generates the next layer into `out`, growing it by a cell either side.
+fn step_into<F: Fn(bool, bool, bool) -> bool>(
+    f: F,
+    input: &[bool],
+    background: bool,
+    out: &mut Vec<bool>,
+) {
+    out.clear();
 
     // Function to get the input bit at a given location. If the location isn't
-    // included in `input`, return false---the empty cell.
-    let input_bit = |loc: isize| input.get(loc as usize).unwrap_or(&false).clone();
+    // included in `input`, return the background.
+    let input_bit = |loc: isize| *input.get(loc as usize).unwrap_or(&background);
 
     // TODO: perhaps use slice.windows()?
-    for i in (-1 as isize)..(input.len() + 1) as isize {
-        let input_triple = (input_bit(i - 1), input_bit(i), input_bit(i + 1));
-        let cell = test_rule(rule, input_triple);
+    for i in -1..(input.len() + 1) as isize {
+        let cell = f(input_bit(i - 1), input_bit(i), input_bit(i + 1));
         out.push(cell)
     }
+}
 
-    out
+/// Works out the number of the rule which `f` follows, by trying it on every
+/// neighbourhood.
+pub fn rule_number_of<F: Fn(bool, bool, bool) -> bool>(f: F) -> u8 {
+    (0..8).fold(0, |rule, input: u8| {
+        let output = f(get_bit(input, 2), get_bit(input, 1), get_bit(input, 0));
+        set_bit(rule, input, output)
+    })
+}
+
+/// The value a uniform `background` takes on after one step of `rule`.
+pub fn next_background(rule: u8, background: bool) -> bool {
+    test_rule(rule, (background, background, background))
+}
+
+/// How a fixed-width layer treats the cells just beyond its edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// The layer is a ring: the cell left of the first is the last, and vice versa.
+    Wrapping,
+    /// Cells beyond either edge are always empty.
+    Dead,
+}
+
+/// Generates the next layer of a fixed-width CA, which has the same width as
+/// `input`. Cells beyond the edges are filled in according to `boundary`.
+pub fn next_layer_fixed(rule: u8, input: &[bool], boundary: Boundary) -> Vec<bool> {
+    let len = input.len() as isize;
+    let input_bit = |loc: isize| match boundary {
+        Boundary::Wrapping => input[loc.rem_euclid(len) as usize],
+        Boundary::Dead => *input.get(loc as usize).unwrap_or(&false),
+    };
+
+    (0..len)
+        .map(|i| test_rule(rule, (input_bit(i - 1), input_bit(i), input_bit(i + 1))))
+        .collect()
+}
+
+/// Generates the next layer of a fixed-width ring of cells.
+pub fn next_layer_wrapping(rule: u8, input: &[bool]) -> Vec<bool> {
+    next_layer_fixed(rule, input, Boundary::Wrapping)
 }
 
 /// Iterates through the layers of the given rule
-pub fn iter_layers(rule: u8) -> impl Iterator<Item = Vec<bool>> {
-    iter::successors(Some(vec![true]), move |last| Some(next_layer(rule, last)))
+pub fn iter_layers(rule: u8) -> Layers {
+    iter_layers_from(rule, vec![true])
+}
+
+/// Iterates through the layers of the given rule, starting from `seed`.
+pub fn iter_layers_from(rule: u8, seed: Vec<bool>) -> Layers {
+    Layers {
+        rule,
+        current: seed,
+        generation: 0,
+    }
+}
+
+/// The layers of a rule, one generation after another. Made by `iter_layers`.
+#[derive(Debug, Clone)]
+pub struct Layers {
+    rule: u8,
+    /// The layer which will be yielded next.
+    current: Vec<bool>,
+    generation: usize,
+}
+
+impl Layers {
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    /// The generation of the next layer to be yielded. Without any skipping,
+    /// that's the number of layers yielded so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Skips ahead so that the next layer yielded is generation `n`, without
+    /// handing over the layers in between. Does nothing if that's already past.
+    pub fn skip_to(&mut self, n: usize) {
+        while self.generation < n {
+            self.current = next_layer(self.rule, &self.current);
+            self.generation += 1;
+        }
+    }
+
+    /// Renders each layer as text, drawing cells with `style`.
+    pub fn rendered(self, style: CellStyle) -> impl Iterator<Item = String> {
+        self.map(move |layer| render_layer_with(&layer, style))
+    }
+}
+
+impl Iterator for Layers {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        let next = next_layer(self.rule, &self.current);
+        self.generation += 1;
+        Some(mem::replace(&mut self.current, next))
+    }
+}
+
+/// The first `n` layers of `rule`, as an iterator which knows how many are
+/// left.
+pub fn take_layers(rule: u8, n: usize) -> TakeLayers {
+    take_layers_from(rule, vec![true], n)
+}
+
+/// The first `n` layers of `rule` starting from `seed`. See `take_layers`.
+pub fn take_layers_from(rule: u8, seed: Vec<bool>, n: usize) -> TakeLayers {
+    TakeLayers {
+        layers: iter_layers_from(rule, seed),
+        remaining: n,
+    }
+}
+
+/// A fixed number of layers of a rule. Made by `take_layers`.
+#[derive(Debug, Clone)]
+pub struct TakeLayers {
+    layers: Layers,
+    remaining: usize,
+}
+
+impl TakeLayers {
+    /// The width of the last layer still to come, which is the widest, as
+    /// each layer is a cell wider either side than the one before. 0 if there
+    /// are none left.
+    pub fn last_width(&self) -> usize {
+        match self.remaining {
+            0 => 0,
+            n => self.layers.current.len() + 2 * (n - 1),
+        }
+    }
+}
+
+impl Iterator for TakeLayers {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        match self.remaining {
+            0 => None,
+            1 => {
+                // The last one, so there's no need to work out what follows it.
+                self.remaining = 0;
+                Some(mem::take(&mut self.layers.current))
+            }
+            _ => {
+                self.remaining -= 1;
+                self.layers.next()
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn nth(&mut self, k: usize) -> Option<Vec<bool>> {
+        if k >= self.remaining {
+            self.remaining = 0;
+            return None;
+        }
+        // Step past the skipped layers without handing any of them out.
+        self.layers.skip_to(self.layers.generation + k);
+        self.remaining -= k;
+        self.next()
+    }
+}
+
+impl ExactSizeIterator for TakeLayers {}
+
+impl FusedIterator for TakeLayers {}
+
+/// Iterates through the layers made by `f`, starting from `seed`. See
+/// `next_layer_with`.
+pub fn iter_layers_with<F>(f: F, seed: Vec<bool>) -> impl Iterator<Item = Vec<bool>>
+where
+    F: Fn(bool, bool, bool) -> bool,
+{
+    iter::successors(Some(seed), move |last| Some(next_layer_with(&f, last)))
+}
+
+/// Iterates through the layers produced by cycling through `schedule`, using
+/// one rule per generation: the first rule makes generation 1 from `seed`, the
+/// second makes generation 2, and so on, wrapping around at the end.
+pub fn iter_layers_schedule(schedule: &[u8], seed: Vec<bool>) -> impl Iterator<Item = Vec<bool>> {
+    assert!(
+        !schedule.is_empty(),
+        "schedule must contain at least one rule"
+    );
+
+    let schedule = schedule.to_vec();
+    let mut generation = 0;
+    iter::successors(Some(seed), move |last| {
+        let rule = schedule[generation % schedule.len()];
+        generation += 1;
+        Some(next_layer(rule, last))
+    })
+}
+
+/// Applies each of `rules` in turn to `input`, as one combined step. Each rule
+/// grows the layer by one cell either side, so the output is `2 * rules.len()`
+/// cells wider than the input---even when some of the rules are the identity.
+pub fn compose_step(rules: &[u8], input: &[bool]) -> Vec<bool> {
+    rules
+        .iter()
+        .fold(input.to_vec(), |layer, &rule| next_layer(rule, &layer))
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::iter_skip_next)]
 mod tests {
     use super::*;
 
@@ -87,6 +323,50 @@ mod tests {
         assert_eq!(next_layer(30, &input), correct_output);
     }
 
+    #[test]
+    pub fn rule_90_wrapping() {
+        let input = vec![true, false, false, false, false];
+        assert_eq!(
+            next_layer_wrapping(90, &input),
+            &[false, true, false, false, true]
+        );
+        assert_eq!(
+            next_layer_fixed(90, &input, Boundary::Dead),
+            &[false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    pub fn single_rule_schedule() {
+        let seed = vec![true, false, true, true];
+        let scheduled = iter_layers_schedule(&[110], seed.clone()).take(20);
+        assert!(scheduled.eq(iter_layers_from(110, seed).take(20)));
+    }
+
+    #[test]
+    pub fn alternating_schedule() {
+        let layers: Vec<_> = iter_layers_schedule(&[90, 30], vec![true])
+            .take(4)
+            .collect();
+        assert_eq!(layers[1], &[true, false, true]);
+        assert_eq!(layers[2], &[true, true, false, true, true]);
+        assert_eq!(layers[3], &[true, true, true, false, true, true, true]);
+    }
+
+    #[test]
+    pub fn compose_with_identity() {
+        // Rule 204 only copies its input, but still pads it with an empty cell either side.
+        let input = vec![true, true, false, false, true];
+        let stepped = next_layer(30, &input);
+        let mut padded = vec![false];
+        padded.extend(&stepped);
+        padded.push(false);
+
+        assert_eq!(compose_step(&[30, 204], &input), padded);
+        assert_eq!(compose_step(&[204, 30], &input), padded);
+        assert_eq!(compose_step(&[], &input), input);
+    }
+
     #[test]
     pub fn rule_30_iter() {
         let layers = iter_layers(30);
@@ -95,4 +375,140 @@ mod tests {
             &[true, true, false, true, true, true, true, false, true, true, true]
         )
     }
+
+    #[test]
+    pub fn closure_rules() {
+        let rule_30 = |a: bool, b: bool, c: bool| a ^ (b || c);
+        let rule_90 = |a: bool, _: bool, c: bool| a ^ c;
+        let rule_110 = |a: bool, b: bool, c: bool| (b ^ c) || (!a && b);
+
+        assert_eq!(rule_number_of(rule_30), 30);
+        assert_eq!(rule_number_of(rule_90), 90);
+        assert_eq!(rule_number_of(rule_110), 110);
+
+        let input = vec![true, true, false, false, true, false, false, false, true];
+        assert_eq!(next_layer_with(rule_30, &input), next_layer(30, &input));
+        assert!(iter_layers_with(rule_110, vec![true])
+            .zip(iter_layers(110))
+            .take(50)
+            .all(|(with, number)| with == number));
+    }
+
+    #[test]
+    pub fn rule_numbers_round_trip() {
+        for rule in 0..=255 {
+            assert_eq!(rule_number_of(|a, b, c| test_rule(rule, (a, b, c))), rule);
+        }
+    }
+
+    #[test]
+    pub fn steps_sub_slices() {
+        let layer: Vec<bool> = iter_layers(30).nth(20).unwrap();
+        let window = &layer[7..30];
+        let copy = window.to_vec();
+
+        assert_eq!(next_layer(30, window), next_layer(30, &copy));
+        assert_eq!(
+            next_layer_wrapping(90, window),
+            next_layer_wrapping(90, &copy)
+        );
+        assert_eq!(
+            next_layer_fixed(110, window, Boundary::Dead),
+            next_layer_fixed(110, &copy, Boundary::Dead)
+        );
+
+        let mut out = Vec::new();
+        next_layer_into(30, window, true, &mut out);
+        assert_eq!(out, next_layer_on(30, &copy, true));
+    }
+
+    #[test]
+    pub fn named_layers() {
+        let mut layers = iter_layers(90);
+        assert_eq!(layers.rule(), 90);
+        assert_eq!(layers.generation(), 0);
+
+        layers.next();
+        layers.next();
+        assert_eq!(layers.generation(), 2);
+
+        layers.skip_to(10);
+        assert_eq!(layers.generation(), 10);
+        assert_eq!(layers.next(), iter_layers(90).nth(10));
+
+        // Skipping backwards doesn't do anything.
+        layers.skip_to(3);
+        assert_eq!(layers.generation(), 11);
+        assert_eq!(layers.next(), iter_layers(90).nth(11));
+    }
+
+    #[test]
+    pub fn rendered_layers() {
+        let rendered: Vec<String> = iter_layers(90)
+            .rendered(CellStyle::default())
+            .take(3)
+            .collect();
+        assert_eq!(rendered, &["#", "#.#", "#...#"]);
+
+        let style = CellStyle {
+            live: 'o',
+            dead: ' ',
+        };
+        let rendered: Vec<String> = iter_layers(90).rendered(style).take(3).collect();
+        assert_eq!(rendered, &["o", "o o", "o   o"]);
+    }
+
+    #[test]
+    fn take_layers_counts_down() {
+        let mut layers = take_layers(30, 5);
+        assert_eq!(layers.len(), 5);
+        assert_eq!(layers.size_hint(), (5, Some(5)));
+        layers.next();
+        layers.next();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers.by_ref().count(), 3);
+        assert_eq!(layers.size_hint(), (0, Some(0)));
+        assert_eq!(layers.next(), None);
+        assert_eq!(layers.next(), None);
+    }
+
+    #[test]
+    fn take_layers_matches_iter_layers() {
+        let taken: Vec<_> = take_layers(110, 30).collect();
+        let expected: Vec<_> = iter_layers(110).take(30).collect();
+        assert_eq!(taken, expected);
+
+        let seed = vec![true, false, true, true];
+        assert!(take_layers_from(30, seed.clone(), 12).eq(iter_layers_from(30, seed).take(12)));
+        assert_eq!(take_layers(30, 0).len(), 0);
+        assert_eq!(take_layers(30, 0).next(), None);
+    }
+
+    #[test]
+    fn take_layers_last_width() {
+        let seed = vec![true, false, true];
+        let mut layers = take_layers_from(90, seed, 6);
+        assert_eq!(layers.last_width(), 13);
+        layers.next();
+        assert_eq!(layers.last_width(), 13);
+        assert_eq!(layers.last().map(|layer| layer.len()), Some(13));
+        assert_eq!(take_layers(30, 0).last_width(), 0);
+    }
+
+    #[test]
+    fn take_layers_nth() {
+        for k in 0..12 {
+            let mut fast = take_layers(30, 10);
+            let mut naive = iter_layers(30).take(10);
+            assert_eq!(fast.nth(k), naive.nth(k));
+            assert_eq!(fast.len(), 10usize.saturating_sub(k + 1));
+            assert!(fast.eq(naive));
+        }
+
+        let mut layers = take_layers(90, 10);
+        layers.nth(2);
+        assert_eq!(layers.len(), 7);
+        assert_eq!(layers.nth(1), iter_layers(90).nth(4));
+        assert_eq!(layers.len(), 5);
+    }
 }
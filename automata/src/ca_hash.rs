@@ -0,0 +1,161 @@
+//! A toy hash function built on rule 30.
+//!
+//! This is for fun, not security: it hasn't been analysed by anyone, and it's
+//! slow. Don't use it for anything that matters.
+//!
+//! The state is a ring of `WIDTH` cells, starting with a single live cell in
+//! the middle. Input is padded with a `0x80` byte and then zeros up to a whole
+//! number of `BLOCK_BYTES`-byte blocks, and followed by one more block holding
+//! its length in bytes, little-endian. Each block is XORed into the first
+//! `8 * BLOCK_BYTES` cells, first byte first and lowest bit first, and then
+//! the ring is stepped `BLANK_ROUNDS` times. Output is squeezed out one bit at
+//! a time by stepping once and reading the middle cell, lowest bit first.
+//!
+//! The keyed variant absorbs the key, padded the same way, before the data.
+
+use crate::ca::next_layer_wrapping;
+
+/// The number of cells in the state.
+pub const WIDTH: usize = 257;
+
+/// The number of bytes absorbed at a time.
+pub const BLOCK_BYTES: usize = 8;
+
+/// The number of steps after absorbing each block. It takes this long for a
+/// change to spread the whole way around the ring.
+pub const BLANK_ROUNDS: usize = WIDTH;
+
+struct Sponge {
+    cells: Vec<bool>,
+}
+
+impl Sponge {
+    fn new() -> Sponge {
+        let mut cells = vec![false; WIDTH];
+        cells[WIDTH / 2] = true;
+        Sponge { cells }
+    }
+
+    fn step(&mut self) {
+        self.cells = next_layer_wrapping(30, &self.cells);
+    }
+
+    fn absorb_block(&mut self, block: &[u8]) {
+        for (i, byte) in block.iter().enumerate() {
+            for bit in 0..8 {
+                self.cells[i * 8 + bit] ^= byte >> bit & 1 == 1;
+            }
+        }
+        for _ in 0..BLANK_ROUNDS {
+            self.step();
+        }
+    }
+
+    // Absorbs all of `data`, with its padding and length.
+    fn absorb(&mut self, data: &[u8]) {
+        let mut padded = data.to_vec();
+        padded.push(0x80);
+        while !padded.len().is_multiple_of(BLOCK_BYTES) {
+            padded.push(0);
+        }
+        padded.extend_from_slice(&(data.len() as u64).to_le_bytes());
+
+        for block in padded.chunks(BLOCK_BYTES) {
+            self.absorb_block(block);
+        }
+    }
+
+    fn squeeze(&mut self, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                (0..8).fold(0, |byte, bit| {
+                    self.step();
+                    byte | (self.cells[WIDTH / 2] as u8) << bit
+                })
+            })
+            .collect()
+    }
+}
+
+/// Hashes `data` to `output_len` bytes.
+pub fn hash(data: &[u8], output_len: usize) -> Vec<u8> {
+    let mut sponge = Sponge::new();
+    sponge.absorb(data);
+    sponge.squeeze(output_len)
+}
+
+/// Hashes `data` to `output_len` bytes, under `key`.
+pub fn hash_keyed(key: &[u8], data: &[u8], output_len: usize) -> Vec<u8> {
+    let mut sponge = Sponge::new();
+    sponge.absorb(key);
+    sponge.absorb(data);
+    sponge.squeeze(output_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // The fraction of bits which differ between `a` and `b`.
+    fn difference(a: &[u8], b: &[u8]) -> f64 {
+        let differing: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        differing as f64 / (a.len() * 8) as f64
+    }
+
+    #[test]
+    fn known_answers() {
+        // Worked out separately, from the description above.
+        assert_eq!(hex(&hash(b"", 16)), "a503b67901722c2b45358c3ec3902807");
+        assert_eq!(hex(&hash(b"abc", 16)), "4a2e127f58c4c1c8b36e8833befaaa5e");
+        assert_eq!(
+            hex(&hash_keyed(b"key", b"abc", 16)),
+            "5998fa5b69a66123cd15e1a188bdf51f"
+        );
+    }
+
+    #[test]
+    fn deterministic_and_length_robust() {
+        assert_eq!(hash(b"rule 30", 32), hash(b"rule 30", 32));
+        assert_eq!(hash(b"rule 30", 32)[..16], hash(b"rule 30", 16)[..]);
+
+        // Trailing zeros and padding-like bytes still change the hash.
+        assert_ne!(hash(b"", 16), hash(&[0], 16));
+        assert_ne!(hash(&[0], 16), hash(&[0, 0], 16));
+        assert_ne!(hash(b"abc", 16), hash(b"abc\x80", 16));
+        assert_ne!(hash(&[0; 8], 16), hash(&[0; 16], 16));
+    }
+
+    #[test]
+    fn avalanche() {
+        let mut rng = Rng::new(7);
+        let trials = 50;
+        let mut total = 0.0;
+        for _ in 0..trials {
+            let data: Vec<u8> = (0..12).map(|_| rng.below(256) as u8).collect();
+            let mut flipped = data.clone();
+            let bit = rng.below(data.len() * 8);
+            flipped[bit / 8] ^= 1 << (bit % 8);
+
+            total += difference(&hash(&data, 32), &hash(&flipped, 32));
+        }
+
+        let average = total / trials as f64;
+        assert!((0.45..0.55).contains(&average), "{}", average);
+    }
+
+    #[test]
+    fn keys() {
+        let a = hash_keyed(b"one key", b"data", 32);
+        let b = hash_keyed(b"another key", b"data", 32);
+        assert!((0.35..0.65).contains(&difference(&a, &b)));
+        assert_ne!(a, hash(b"data", 32));
+
+        // The key and data are kept apart by their padding.
+        assert_ne!(hash_keyed(b"ab", b"c", 16), hash_keyed(b"a", b"bc", 16));
+    }
+}
@@ -0,0 +1,460 @@
+use crate::error::CaError;
+use crate::formats::parse_layer;
+use crate::packed::{layer_from_packed, layer_to_packed, PackedLayer};
+use crate::simulation::Simulation;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Everything needed to pick a run back up where it left off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimState {
+    pub rule: u8,
+    pub generation: usize,
+    pub layer: Vec<bool>,
+}
+
+// The first line of every state file. Version 1 files stored the cells as `#`
+// and `.`, and can still be read.
+const STATE_HEADER: &str = "castate 2";
+const STATE_HEADER_V1: &str = "castate 1";
+
+impl SimState {
+    pub fn of(sim: &Simulation) -> SimState {
+        SimState {
+            rule: sim.rule(),
+            generation: sim.generation(),
+            layer: sim.layer().to_vec(),
+        }
+    }
+
+    pub fn into_simulation(self) -> Simulation {
+        Simulation::resume(self.rule, self.layer, self.generation)
+    }
+
+    /// Writes the state as text: a header line, then a `key value` line each
+    /// for the rule, generation, width, and cells. The cells are packed as
+    /// described in `packed`, and written in hex.
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{}", STATE_HEADER)?;
+        writeln!(w, "rule {}", self.rule)?;
+        writeln!(w, "generation {}", self.generation)?;
+        writeln!(w, "width {}", self.layer.len())?;
+        write!(w, "packed ")?;
+        for byte in layer_to_packed(&self.layer).bytes {
+            write!(w, "{:02x}", byte)?;
+        }
+        writeln!(w)
+    }
+
+    /// Reads a state written by `write_to`. Anything malformed or truncated is
+    /// a `Parse` error, and a file which doesn't start with a known header is
+    /// an `UnsupportedVersion` error.
+    pub fn read_from<R: BufRead>(r: R) -> Result<SimState, CaError> {
+        let mut lines = r.lines();
+        let mut number = 0;
+        let mut next_line = || {
+            number += 1;
+            match lines.next() {
+                Some(line) => Ok((number, line?)),
+                None => Err(malformed(number, 1, "file is truncated")),
+            }
+        };
+
+        let (_, header) = next_line()?;
+        if header != STATE_HEADER && header != STATE_HEADER_V1 {
+            return Err(CaError::UnsupportedVersion {
+                format: "state",
+                found: header,
+            });
+        }
+        let rule = parse_field(next_line()?, "rule")?;
+        let generation = parse_field(next_line()?, "generation")?;
+        let width: usize = parse_field(next_line()?, "width")?;
+
+        let layer = if header == STATE_HEADER_V1 {
+            let line = next_line()?;
+            let number = line.0;
+            let cells: String = parse_field(line, "cells")?;
+            parse_layer(&cells).map_err(|err| match err {
+                // Count the column from the start of the line.
+                CaError::Parse {
+                    column, message, ..
+                } => malformed(number, "cells ".len() + column, &message),
+                other => other,
+            })?
+        } else {
+            let line = next_line()?;
+            let number = line.0;
+            let hex: String = parse_field(line, "packed")?;
+            let packed = PackedLayer {
+                bytes: parse_hex(&hex, number)?,
+                bit_len: width,
+            };
+            layer_from_packed(&packed)?
+        };
+        if layer.len() != width {
+            return Err(CaError::InvalidDimensions(format!(
+                "the layer is {} cells wide, not {}",
+                layer.len(),
+                width
+            )));
+        }
+
+        Ok(SimState {
+            rule,
+            generation,
+            layer,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<SimState, CaError> {
+        SimState::read_from(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+fn malformed(line: usize, column: usize, message: &str) -> CaError {
+    CaError::Parse {
+        format: "state",
+        line,
+        column,
+        message: message.to_string(),
+    }
+}
+
+// Parses the hex after `packed ` on line `line`.
+fn parse_hex(hex: &str, line: usize) -> Result<Vec<u8>, CaError> {
+    let column = |i: usize| "packed ".len() + i + 1;
+    if !hex.is_ascii() {
+        return Err(malformed(line, column(0), "invalid packed cells"));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(malformed(
+            line,
+            column(hex.len()),
+            "odd number of hex digits",
+        ));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| malformed(line, column(i), "invalid packed cells"))
+        })
+        .collect()
+}
+
+// Parses a numbered `key value` line, checking that the key is `key`.
+fn parse_field<T: std::str::FromStr>(
+    (number, line): (usize, String),
+    key: &str,
+) -> Result<T, CaError> {
+    match line.split_once(' ') {
+        Some((k, value)) if k == key => value
+            .parse()
+            .map_err(|_| malformed(number, key.len() + 2, &format!("invalid {}", key))),
+        _ => Err(malformed(number, 1, &format!("expected {}", key))),
+    }
+}
+
+/// The number of checkpoints kept around by `run_with_checkpoints`.
+pub const KEEP_CHECKPOINTS: usize = 3;
+
+/// What happened during a checkpointed run.
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    /// The generation the run finished at.
+    pub generation: usize,
+    /// The layer the run finished on.
+    pub final_layer: Vec<bool>,
+    /// Every checkpoint written during the run, in order, including any
+    /// which were later pruned.
+    pub checkpoints: Vec<PathBuf>,
+    /// The first generation at which the layer was clipped to the
+    /// simulation's maximum width, if it was.
+    pub clipped_at: Option<usize>,
+}
+
+fn checkpoint_path(dir: &Path, generation: usize) -> PathBuf {
+    dir.join(format!("gen_{:06}.castate", generation))
+}
+
+// Finds the checkpoints in `dir`, oldest first.
+fn list_checkpoints(dir: &Path) -> io::Result<Vec<(usize, PathBuf)>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        let generation = name
+            .strip_prefix("gen_")
+            .and_then(|rest| rest.strip_suffix(".castate"))
+            .and_then(|digits| digits.parse().ok());
+        if let Some(generation) = generation {
+            found.push((generation, path));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+// Saves a checkpoint atomically, by writing it to a temporary file first.
+fn write_checkpoint(dir: &Path, sim: &Simulation) -> io::Result<PathBuf> {
+    let path = checkpoint_path(dir, sim.generation());
+    let temp = path.with_extension("castate.tmp");
+    SimState::of(sim).save(&temp)?;
+    fs::rename(&temp, &path)?;
+    Ok(path)
+}
+
+/// Runs `rule` from `seed` for `generations` steps, checkpointing to `dir`.
+/// See `continue_with_checkpoints`.
+pub fn run_with_checkpoints(
+    rule: u8,
+    seed: Vec<bool>,
+    generations: usize,
+    every: usize,
+    dir: &Path,
+) -> io::Result<RunReport> {
+    let mut sim = Simulation::new(rule, seed);
+    continue_with_checkpoints(&mut sim, generations, every, dir)
+}
+
+/// Steps `sim` until it reaches generation `until`, writing a checkpoint to
+/// `dir` at every multiple of `every` and at the end. Only the newest
+/// `KEEP_CHECKPOINTS` checkpoints are kept.
+pub fn continue_with_checkpoints(
+    sim: &mut Simulation,
+    until: usize,
+    every: usize,
+    dir: &Path,
+) -> io::Result<RunReport> {
+    assert!(every > 0, "checkpoints must be at least a generation apart");
+    fs::create_dir_all(dir)?;
+    let start = Instant::now();
+    let first_generation = sim.generation();
+
+    let mut checkpoints = Vec::new();
+    while sim.generation() < until {
+        sim.step();
+        if sim.generation().is_multiple_of(every) || sim.generation() == until {
+            checkpoints.push(write_checkpoint(dir, sim)?);
+
+            let existing = list_checkpoints(dir)?;
+            let stale = existing.len().saturating_sub(KEEP_CHECKPOINTS);
+            for (_, path) in &existing[..stale] {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    sim.finish(first_generation, start);
+
+    Ok(RunReport {
+        generation: sim.generation(),
+        final_layer: sim.layer().to_vec(),
+        checkpoints,
+        clipped_at: sim.clipped_at(),
+    })
+}
+
+/// The checkpoint a run can carry on from. See `resume_latest`.
+#[derive(Debug)]
+pub struct Resumed {
+    /// The state saved in the newest checkpoint which could be read.
+    pub state: SimState,
+    /// Every newer checkpoint which couldn't be read, newest first, with why.
+    pub skipped: Vec<(PathBuf, CaError)>,
+}
+
+/// Loads the newest checkpoint in `dir` which can be read, along with any
+/// newer ones which couldn't be, for the caller to warn about.
+pub fn resume_latest(dir: &Path) -> io::Result<Resumed> {
+    let mut skipped = Vec::new();
+    for (_, path) in list_checkpoints(dir)?.into_iter().rev() {
+        match SimState::load(&path) {
+            Ok(state) => return Ok(Resumed { state, skipped }),
+            Err(err) => skipped.push((path, err)),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no usable checkpoints in {}", dir.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+
+    fn names(dir: &Path) -> Vec<String> {
+        list_checkpoints(dir)
+            .unwrap()
+            .into_iter()
+            .map(|(_, path)| path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let state = SimState {
+            rule: 110,
+            generation: 3,
+            layer: vec![true, false, true, true, false, false, true],
+        };
+        let mut out = Vec::new();
+        state.write_to(&mut out).unwrap();
+        assert_eq!(SimState::read_from(&out[..]).unwrap(), state);
+    }
+
+    #[test]
+    fn state_formats() {
+        let state = SimState {
+            rule: 30,
+            generation: 2,
+            layer: vec![true, false, true],
+        };
+        let mut out = Vec::new();
+        state.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 05\n"
+        );
+
+        // Older files are still readable.
+        let v1 = "castate 1\nrule 30\ngeneration 2\nwidth 3\ncells #.#\n";
+        assert_eq!(SimState::read_from(v1.as_bytes()).unwrap(), state);
+    }
+
+    #[test]
+    fn state_rejects_garbage() {
+        let good = "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 05\n";
+        assert!(SimState::read_from(good.as_bytes()).is_ok());
+
+        for bad in &[
+            "",
+            "castate 2\nrule 30\ngeneration 2\nwidth 3\ncells #.#\n",
+            "castate 1\nrule 300\ngeneration 2\nwidth 3\ncells #.#\n",
+            "castate 1\nrule 30\ngeneration 2\nwidth 4\ncells #.#\n",
+            "castate 1\nrule 30\ngeneration 2\nwidth 3\n",
+            "castate 1\nrule 30\nwidth 3\ngeneration 2\ncells #.#\n",
+            "castate 1\nrule 30\ngeneration 2\nwidth 3\ncells #x#\n",
+            "castate 3\nrule 30\ngeneration 2\nwidth 3\npacked 05\n",
+            "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 0500\n",
+            "castate 2\nrule 30\ngeneration 2\nwidth 9\npacked 05\n",
+            "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 5\n",
+            "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked zz\n",
+        ] {
+            let err = SimState::read_from(bad.as_bytes()).unwrap_err();
+            assert!(!matches!(err, CaError::Io(_)), "{:?}", bad);
+        }
+
+        let read = |text: &str| SimState::read_from(text.as_bytes()).unwrap_err();
+        assert!(matches!(
+            read("castate 3\nrule 30\n"),
+            CaError::UnsupportedVersion {
+                format: "state",
+                ..
+            }
+        ));
+        assert!(matches!(
+            read("castate 2\nrule 30\ngeneration 2\nwidth 9\npacked 05\n"),
+            CaError::InvalidDimensions(_)
+        ));
+        let err = read("castate 1\nrule 30\ngeneration 2\nwidth 3\ncells #x#\n");
+        assert_eq!(
+            err.to_string(),
+            "unexpected 'x' at line 5, column 8 of state"
+        );
+        let err = read("castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 0z\n");
+        assert!(matches!(
+            err,
+            CaError::Parse {
+                line: 5,
+                column: 8,
+                ..
+            }
+        ));
+        let err = read("castate 1\nrule 30\n");
+        assert_eq!(
+            err.to_string(),
+            "file is truncated at line 3, column 1 of state"
+        );
+    }
+
+    #[test]
+    fn checkpoints_written_and_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = run_with_checkpoints(30, vec![true], 450, 100, dir.path()).unwrap();
+
+        assert_eq!(report.generation, 450);
+        assert_eq!(report.final_layer, iter_layers(30).nth(450).unwrap());
+        assert_eq!(report.checkpoints.len(), 5);
+        assert_eq!(
+            names(dir.path()),
+            &[
+                "gen_000300.castate",
+                "gen_000400.castate",
+                "gen_000450.castate"
+            ]
+        );
+
+        let latest = resume_latest(dir.path()).unwrap().state;
+        assert_eq!(latest.generation, 450);
+        assert_eq!(latest.layer, report.final_layer);
+    }
+
+    #[test]
+    fn resume_after_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        run_with_checkpoints(90, vec![true, true, false, true], 250, 100, dir.path()).unwrap();
+
+        // Pretend we crashed just before the final checkpoint.
+        fs::remove_file(checkpoint_path(dir.path(), 250)).unwrap();
+
+        let resumed = resume_latest(dir.path()).unwrap();
+        assert!(resumed.skipped.is_empty());
+        let state = resumed.state;
+        assert_eq!(state.generation, 200);
+
+        let mut sim = state.into_simulation();
+        let report = continue_with_checkpoints(&mut sim, 500, 100, dir.path()).unwrap();
+        let mut uninterrupted = Simulation::new(90, vec![true, true, false, true]);
+        for _ in 0..500 {
+            uninterrupted.step();
+        }
+        assert_eq!(report.final_layer, uninterrupted.layer());
+    }
+
+    #[test]
+    fn corrupt_checkpoint_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        run_with_checkpoints(30, vec![true], 200, 100, dir.path()).unwrap();
+        fs::write(checkpoint_path(dir.path(), 200), "castate 1\nrule 30\ngen").unwrap();
+
+        let resumed = resume_latest(dir.path()).unwrap();
+        assert_eq!(resumed.state.generation, 100);
+        assert_eq!(resumed.state.layer, iter_layers(30).nth(100).unwrap());
+        assert_eq!(resumed.skipped.len(), 1);
+        let (path, err) = &resumed.skipped[0];
+        assert_eq!(path, &checkpoint_path(dir.path(), 200));
+        assert!(matches!(err, CaError::Parse { .. }));
+    }
+
+    #[test]
+    fn nothing_to_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = resume_latest(dir.path()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
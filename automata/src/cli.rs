@@ -0,0 +1,212 @@
+use crate::ca::Boundary;
+use crate::life::Grid;
+use crate::render::{render_seed_fitted, TextRenderer};
+use crate::renderer::{RenderCommon, Renderer};
+use crate::seeds;
+use std::env;
+use std::io::{self, BufRead, Write};
+
+/// Options for rendering rules from the command line.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// The number of layers to render for each rule.
+    pub generations: usize,
+
+    /// The number of columns to fit the output in, if it's limited.
+    pub width: Option<usize>,
+
+    /// The first layer of each rendering.
+    pub seed: Vec<bool>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            generations: 16,
+            width: None,
+            seed: seeds::single(),
+        }
+    }
+}
+
+/// The number of columns used when the terminal's width can't be worked out.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Works out how many columns of text fit on the terminal. The `AUTOMATA_WIDTH`
+/// environment variable takes priority; otherwise, if stdout is a terminal and
+/// the `terminal_size` feature is enabled, that terminal's width is used.
+pub fn terminal_width() -> usize {
+    if let Some(width) = env::var("AUTOMATA_WIDTH").ok().and_then(|w| w.parse().ok()) {
+        return width;
+    }
+
+    #[cfg(feature = "terminal_size")]
+    {
+        if let Some((terminal_size::Width(width), _)) = terminal_size::terminal_size() {
+            return width as usize;
+        }
+    }
+
+    DEFAULT_WIDTH
+}
+
+/// What happened while rendering a list of rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// The rules which were rendered, in order.
+    pub rendered: Vec<u8>,
+    /// The tokens which couldn't be parsed as a rule number, in order.
+    pub invalid: Vec<String>,
+}
+
+impl Summary {
+    /// Whether every token was rendered successfully.
+    pub fn is_success(&self) -> bool {
+        self.invalid.is_empty()
+    }
+}
+
+/// Reads whitespace-separated rule numbers from `r`, rendering each to `w` under
+/// a `== rule N ==` header. Tokens which aren't rule numbers are skipped, and
+/// handed back in the returned `Summary` for the caller to warn about.
+pub fn run_rules_from_reader<R: BufRead, W: Write>(
+    r: R,
+    mut w: W,
+    opts: &RenderOptions,
+) -> io::Result<Summary> {
+    let mut summary = Summary::default();
+
+    for line in r.lines() {
+        for token in line?.split_whitespace() {
+            match token.parse::<u8>() {
+                Ok(rule) => {
+                    writeln!(w, "== rule {} ==", rule)?;
+                    let width = opts.width.unwrap_or(usize::MAX);
+                    render_seed_fitted(&mut w, rule, &opts.seed, opts.generations, width)?;
+                    summary.rendered.push(rule);
+                }
+                Err(_) => summary.invalid.push(token.to_string()),
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Renders `opts.generations` generations of Life from `pattern`, each under a
+/// `== generation N ==` header, clipping lines to `opts.width` like a rule's.
+/// The grid leaves room for the pattern to grow a cell a generation in every
+/// direction, which is as fast as anything in Life can, so nothing ever
+/// reaches its edge.
+pub fn render_life<W: Write>(
+    mut w: W,
+    pattern: &[Vec<bool>],
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    let margin = opts.generations.saturating_sub(1);
+    let width = pattern.first().map_or(0, Vec::len);
+    let mut grid = Grid::new(
+        width + 2 * margin,
+        pattern.len() + 2 * margin,
+        Boundary::Dead,
+    );
+    grid.place(pattern, margin, margin);
+
+    let renderer = TextRenderer {
+        common: RenderCommon {
+            max_width: opts.width,
+            ..RenderCommon::default()
+        },
+    };
+    for generation in 0..opts.generations {
+        if generation > 0 {
+            grid = grid.step();
+        }
+        writeln!(w, "== generation {} ==", generation)?;
+        renderer.render(&mut grid.rows().into_iter(), &mut w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> (String, Summary) {
+        let mut out = Vec::new();
+        let opts = RenderOptions {
+            generations: 2,
+            ..RenderOptions::default()
+        };
+        let summary = run_rules_from_reader(input.as_bytes(), &mut out, &opts).unwrap();
+        (String::from_utf8(out).unwrap(), summary)
+    }
+
+    #[test]
+    fn renders_each_rule() {
+        let (out, summary) = run("30 90\n110\n");
+        assert_eq!(
+            out,
+            "== rule 30 ==\n.#.\n###\n== rule 90 ==\n.#.\n#.#\n== rule 110 ==\n.#.\n##.\n"
+        );
+        assert_eq!(summary.rendered, &[30, 90, 110]);
+        assert!(summary.is_success());
+    }
+
+    #[test]
+    fn skips_invalid_tokens() {
+        let (out, summary) = run("  30 foo\n\n256 -1\t90  ");
+        assert_eq!(out, "== rule 30 ==\n.#.\n###\n== rule 90 ==\n.#.\n#.#\n");
+        assert_eq!(summary.rendered, &[30, 90]);
+        assert_eq!(summary.invalid, &["foo", "256", "-1"]);
+        assert!(!summary.is_success());
+    }
+
+    #[test]
+    fn fits_width() {
+        let mut out = Vec::new();
+        let opts = RenderOptions {
+            generations: 3,
+            width: Some(3),
+            ..RenderOptions::default()
+        };
+        run_rules_from_reader("90".as_bytes(), &mut out, &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "== rule 90 ==\n…#…\n….…\n….…\n"
+        );
+    }
+
+    #[test]
+    fn life() {
+        let mut out = Vec::new();
+        let opts = RenderOptions {
+            generations: 2,
+            ..RenderOptions::default()
+        };
+        render_life(&mut out, &seeds::blinker(), &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "== generation 0 ==\n.....\n.###.\n.....\n\
+             == generation 1 ==\n..#..\n..#..\n..#..\n"
+        );
+
+        // A glider never reaches the edge of the grid.
+        let mut out = Vec::new();
+        let opts = RenderOptions {
+            generations: 40,
+            ..RenderOptions::default()
+        };
+        render_life(&mut out, &seeds::glider(), &opts).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let last = text.split("== generation 39 ==\n").nth(1).unwrap();
+        assert_eq!(last.matches('#').count(), 5);
+    }
+
+    #[test]
+    fn empty_input() {
+        let (out, summary) = run("");
+        assert!(out.is_empty());
+        assert_eq!(summary, Summary::default());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::ca::take_layers_from;
+use crate::raster::{write_png, Rgb};
+use crate::seeds;
+use std::io::{self, Write};
+
+/// One of the color channels of an RGB pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Red => 0,
+            Channel::Green => 1,
+            Channel::Blue => 2,
+        }
+    }
+}
+
+/// How `compose_rgb` turns three runs into colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposeOptions {
+    /// The channel each rule's live cells light up, in the same order as the
+    /// rules.
+    pub channels: [Channel; 3],
+    /// How brightly each rule's live cells light up their channel.
+    pub intensity: [u8; 3],
+    /// The color where no rule is alive. Live cells replace the background in
+    /// their own channel and leave the others alone.
+    pub background: Rgb,
+    /// The first layer of every run.
+    pub seed: Vec<bool>,
+}
+
+impl Default for ComposeOptions {
+    fn default() -> ComposeOptions {
+        ComposeOptions {
+            channels: [Channel::Red, Channel::Green, Channel::Blue],
+            intensity: [255; 3],
+            background: [0, 0, 0],
+            seed: seeds::single(),
+        }
+    }
+}
+
+/// An image held in memory, row by row from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl RgbImage {
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        assert!(x < self.width && y < self.height, "pixel out of range");
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        write_png(w, self.width, self.height, &self.pixels)
+    }
+}
+
+/// Runs three rules from the same seed and overlays them in one image, each in
+/// its own color channel. With the default options, structure all three share
+/// comes out white, and structure only one has comes out red, green or blue.
+///
+/// Every layer is centered in the width of the widest, so the image is as wide
+/// as the widest layer of any of the runs.
+pub fn compose_rgb(rules: [u8; 3], generations: usize, opts: &ComposeOptions) -> RgbImage {
+    let runs: Vec<Vec<Vec<bool>>> = rules
+        .iter()
+        .map(|&rule| take_layers_from(rule, opts.seed.clone(), generations).collect())
+        .collect();
+    let width = runs.iter().flatten().map(Vec::len).max().unwrap_or(0);
+
+    let mut pixels = vec![opts.background; width * generations];
+    for (i, run) in runs.iter().enumerate() {
+        let channel = opts.channels[i].index();
+        for (y, layer) in run.iter().enumerate() {
+            let pad = (width - layer.len()) / 2;
+            for (x, &cell) in layer.iter().enumerate() {
+                if cell {
+                    pixels[y * width + pad + x][channel] = opts.intensity[i];
+                }
+            }
+        }
+    }
+
+    RgbImage {
+        width,
+        height: generations,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb = [255, 0, 0];
+    const GREEN: Rgb = [0, 255, 0];
+    const BLUE: Rgb = [0, 0, 255];
+    const WHITE: Rgb = [255, 255, 255];
+    const BLACK: Rgb = [0, 0, 0];
+
+    #[test]
+    fn channels() {
+        // Rule 204 keeps only the seed, rule 254 fills the whole cone, and
+        // rule 0 is dead after the seed.
+        let image = compose_rgb([204, 254, 0], 4, &ComposeOptions::default());
+        assert_eq!((image.width, image.height), (7, 4));
+
+        // All three start from the same single cell.
+        assert_eq!(image.get(3, 0), WHITE);
+        assert_eq!(image.get(0, 0), BLACK);
+        // Then the center stays alive under 204 and 254, but not 0.
+        assert_eq!(image.get(3, 1), [255, 255, 0]);
+        // And only 254 reaches the sides.
+        assert_eq!(image.get(2, 1), GREEN);
+        assert_eq!(image.get(0, 3), GREEN);
+
+        let red = compose_rgb([254, 0, 0], 2, &ComposeOptions::default());
+        assert_eq!(red.get(0, 1), RED);
+        let blue = compose_rgb([0, 0, 254], 2, &ComposeOptions::default());
+        assert_eq!(blue.get(0, 1), BLUE);
+    }
+
+    #[test]
+    fn reassigned_channels() {
+        let opts = ComposeOptions {
+            channels: [Channel::Blue, Channel::Red, Channel::Green],
+            intensity: [255, 100, 255],
+            background: [10, 20, 30],
+            ..ComposeOptions::default()
+        };
+        let image = compose_rgb([204, 254, 0], 3, &opts);
+        assert_eq!(image.get(2, 0), [100, 255, 255]);
+        assert_eq!(image.get(2, 1), [100, 20, 255]);
+        assert_eq!(image.get(1, 1), [100, 20, 30]);
+        assert_eq!(image.get(0, 0), [10, 20, 30]);
+    }
+
+    #[test]
+    fn widest_diagram() {
+        let opts = ComposeOptions {
+            seed: seeds::block(3),
+            ..ComposeOptions::default()
+        };
+        let image = compose_rgb([30, 90, 110], 10, &opts);
+        assert_eq!((image.width, image.height), (3 + 2 * 9, 10));
+        assert_eq!(image.pixels.len(), image.width * image.height);
+
+        let empty = compose_rgb([30, 90, 110], 0, &opts);
+        assert_eq!((empty.width, empty.height), (0, 0));
+    }
+}
@@ -0,0 +1,206 @@
+//! Automata whose cells hold a value from 0 to 1 rather than being alive or
+//! dead. Each cell becomes the fractional part of `avg * a + b`, where `avg` is
+//! the average of it and its two neighbors. Layers wrap round, and keep the
+//! same width.
+//!
+//! Cells which aren't finite (NaN or infinite) are read as 0, and finite cells
+//! outside 0 to 1 are clamped into it, so the output is always in range.
+
+use std::mem;
+
+/// The rule taking a neighborhood average `avg` to `frac(avg * a + b)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContinuousRule {
+    pub a: f32,
+    pub b: f32,
+}
+
+impl ContinuousRule {
+    fn apply(&self, average: f32) -> f32 {
+        let x = average * self.a + self.b;
+        clean(x - x.floor())
+    }
+}
+
+// Brings a cell into range, as described in the module docs.
+fn clean(cell: f32) -> f32 {
+    if cell.is_finite() {
+        cell.clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+pub fn next_layer_continuous(rule: &ContinuousRule, input: &[f32]) -> Vec<f32> {
+    let width = input.len();
+    (0..width)
+        .map(|i| {
+            let left = clean(input[(i + width - 1) % width]);
+            let right = clean(input[(i + 1) % width]);
+            rule.apply((left + clean(input[i]) + right) / 3.0)
+        })
+        .collect()
+}
+
+/// Iterates through the layers of `rule`, starting from `seed`.
+pub fn iter_continuous(rule: ContinuousRule, seed: Vec<f32>) -> ContinuousLayers {
+    ContinuousLayers {
+        rule,
+        current: seed,
+    }
+}
+
+/// The layers of a continuous rule. Made by `iter_continuous`.
+#[derive(Debug, Clone)]
+pub struct ContinuousLayers {
+    rule: ContinuousRule,
+    /// The layer which will be yielded next.
+    current: Vec<f32>,
+}
+
+impl Iterator for ContinuousLayers {
+    type Item = Vec<f32>;
+
+    fn next(&mut self) -> Option<Vec<f32>> {
+        let next = next_layer_continuous(&self.rule, &self.current);
+        Some(mem::replace(&mut self.current, next))
+    }
+}
+
+/// The mean and variance of the cells in a layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueStats {
+    pub mean: f32,
+    pub variance: f32,
+}
+
+pub fn value_stats(layer: &[f32]) -> ValueStats {
+    let n = layer.len().max(1) as f32;
+    let mean = layer.iter().map(|&cell| clean(cell)).sum::<f32>() / n;
+    let variance = layer
+        .iter()
+        .map(|&cell| (clean(cell) - mean).powi(2))
+        .sum::<f32>()
+        / n;
+    ValueStats { mean, variance }
+}
+
+/// Turns each cell into a live one if it's at least `threshold`, so the layer
+/// can go through the usual renderers.
+pub fn threshold(layer: &[f32], threshold: f32) -> Vec<bool> {
+    layer.iter().map(|&cell| clean(cell) >= threshold).collect()
+}
+
+#[cfg(feature = "image")]
+pub use self::png::{gray, write_continuous_png};
+
+#[cfg(feature = "image")]
+mod png {
+    use super::clean;
+    use crate::raster::{write_png, Rgb};
+    use std::io::{self, Write};
+
+    /// The shade of grey for a cell, from black at 0 to white at 1.
+    pub fn gray(cell: f32) -> Rgb {
+        let level = (clean(cell) * 255.0).round() as u8;
+        [level; 3]
+    }
+
+    /// Writes a greyscale PNG with a row for each of `layers`, which all have
+    /// to be the same width.
+    pub fn write_continuous_png<W: Write>(w: W, layers: &[Vec<f32>]) -> io::Result<()> {
+        let width = layers.first().map_or(0, Vec::len);
+        let pixels: Vec<Rgb> = layers.iter().flatten().map(|&cell| gray(cell)).collect();
+        write_png(w, width, layers.len(), &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn constant_layers() {
+        let rule = ContinuousRule { a: 1.0, b: 0.0 };
+        for &value in &[0.0, 0.25, 0.5, 0.75] {
+            for layer in iter_continuous(rule, vec![value; 10]).take(20) {
+                assert_close(&layer, &[value; 10]);
+            }
+        }
+    }
+
+    #[test]
+    fn known_rows() {
+        let rule = ContinuousRule { a: 1.5, b: 0.2 };
+        let layers: Vec<_> = iter_continuous(rule, vec![0.0, 0.3, 0.9, 0.6])
+            .take(3)
+            .collect();
+        // Worked out separately: the first row's averages are 0.3, 0.4, 0.6
+        // and 0.5, which go to 0.65, 0.8, 1.1 and 0.95.
+        assert_close(&layers[1], &[0.65, 0.8, 0.1, 0.95]);
+        assert_close(&layers[2], &[0.4, 0.975, 0.125, 0.05]);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        let mut rng = Rng::new(5);
+        for _ in 0..20 {
+            let rule = ContinuousRule {
+                a: rng.next_f64() as f32 * 20.0 - 10.0,
+                b: rng.next_f64() as f32 * 4.0 - 2.0,
+            };
+            let seed: Vec<f32> = (0..30).map(|_| rng.next_f64() as f32).collect();
+            for layer in iter_continuous(rule, seed).take(50) {
+                assert!(layer.iter().all(|cell| (0.0..=1.0).contains(cell)));
+            }
+        }
+    }
+
+    #[test]
+    fn bad_inputs() {
+        let rule = ContinuousRule { a: 1.0, b: 0.0 };
+        let layer = next_layer_continuous(&rule, &[f32::NAN, f32::INFINITY, 3.0, -1.0]);
+        // Read as 0, 0, 1 and 0.
+        assert_close(&layer, &[0.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+
+        let rule = ContinuousRule {
+            a: f32::NAN,
+            b: 0.0,
+        };
+        assert_eq!(next_layer_continuous(&rule, &[0.5; 3]), &[0.0; 3]);
+    }
+
+    #[test]
+    fn stats_and_thresholds() {
+        let layer = [0.0, 0.5, 1.0, 0.5];
+        let stats = value_stats(&layer);
+        assert_eq!(stats.mean, 0.5);
+        assert_eq!(stats.variance, 0.125);
+        assert_eq!(threshold(&layer, 0.5), &[false, true, true, true]);
+        assert_eq!(value_stats(&[]).mean, 0.0);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn grayscale_png() {
+        let layers = vec![vec![0.0, 0.5, 1.0], vec![1.0, 0.2, 0.0]];
+        let mut out = Vec::new();
+        write_continuous_png(&mut out, &layers).unwrap();
+
+        let decoder = ::png::Decoder::new(&out[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(&buf[..9], &[0, 0, 0, 128, 128, 128, 255, 255, 255]);
+        assert_eq!(&buf[12..15], &[51, 51, 51]);
+    }
+}
@@ -0,0 +1,111 @@
+/// The cells which differ between two layers of a growing run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerDiff {
+    /// Whether each cell changed, starting from column `left`.
+    changed: Vec<bool>,
+    left: isize,
+    count: usize,
+}
+
+impl LayerDiff {
+    /// The XOR of the two layers, starting from column `left()`.
+    pub fn xor(&self) -> &[bool] {
+        &self.changed
+    }
+
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    /// The number of cells which changed.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The global columns of the cells which changed, from left to right.
+    pub fn columns(&self) -> impl Iterator<Item = isize> + '_ {
+        self.changed
+            .iter()
+            .enumerate()
+            .filter(|&(_, &changed)| changed)
+            .map(move |(i, _)| self.left + i as isize)
+    }
+}
+
+/// Compares two layers, lining them up by their middle cells, as in a growing
+/// run where each layer is centered on column 0. Where one layer is wider,
+/// the cells beyond the other's edges count as dead.
+pub fn diff_layers(a: &[bool], b: &[bool]) -> LayerDiff {
+    let width = a.len().max(b.len());
+    let left = -(width as isize / 2);
+    let cell = |layer: &[bool], column: isize| {
+        let index = column + layer.len() as isize / 2;
+        index >= 0 && *layer.get(index as usize).unwrap_or(&false)
+    };
+
+    let changed: Vec<bool> = (left..left + width as isize)
+        .map(|column| cell(a, column) != cell(b, column))
+        .collect();
+    let count = changed.iter().filter(|&&changed| changed).count();
+
+    LayerDiff {
+        changed,
+        left,
+        count,
+    }
+}
+
+/// Diffs each layer of a run against the one before it.
+pub fn changes<I: Iterator<Item = Vec<bool>>>(layers: I) -> impl Iterator<Item = LayerDiff> {
+    let mut layers = layers.peekable();
+    std::iter::from_fn(move || {
+        let before = layers.next()?;
+        let after = layers.peek()?;
+        Some(diff_layers(&before, after))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+
+    #[test]
+    fn rule_204_changes_nothing() {
+        // Rule 204 copies every cell, and the border cells it grows by are
+        // dead, just like the empty space they replace.
+        for diff in changes(iter_layers(204)).take(10) {
+            assert_eq!(diff.count(), 0);
+        }
+    }
+
+    #[test]
+    fn rule_51_flips_everything() {
+        for (generation, diff) in changes(iter_layers(51)).take(10).enumerate() {
+            assert_eq!(diff.count(), 2 * generation + 3);
+            assert!(diff.xor().iter().all(|&changed| changed));
+        }
+    }
+
+    #[test]
+    fn rule_30_counts() {
+        // #  ->  ###  ->  ##..#  ->  ##.####
+        let counts: Vec<usize> = changes(iter_layers(30))
+            .take(3)
+            .map(|d| d.count())
+            .collect();
+        assert_eq!(counts, &[2, 4, 5]);
+
+        let diff = changes(iter_layers(30)).nth(1).unwrap();
+        assert_eq!(diff.left(), -2);
+        assert_eq!(diff.columns().collect::<Vec<_>>(), &[-2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn lines_up_centers() {
+        let diff = diff_layers(&[true], &[false, true, false, true, false]);
+        assert_eq!(diff.columns().collect::<Vec<_>>(), &[-1, 0, 1]);
+        assert_eq!(diff_layers(&[], &[]).count(), 0);
+        assert_eq!(changes(iter_layers(30).take(1)).count(), 0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::batch::run_parallel;
+use crate::ca::next_layer_wrapping;
+use crate::rng::Rng;
+use crate::seeds;
+use std::collections::VecDeque;
+use std::thread;
+
+/// The longest cycle a sample can fall into and still count as settled.
+pub const MAX_SETTLED_PERIOD: usize = 8;
+
+/// The mean and standard deviation of something measured over every sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeanStd {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl MeanStd {
+    fn of(values: impl Iterator<Item = f64> + Clone) -> MeanStd {
+        let n = values.clone().count().max(1) as f64;
+        let mean = values.clone().sum::<f64>() / n;
+        let variance = values.map(|v| (v - mean) * (v - mean)).sum::<f64>() / n;
+        MeanStd {
+            mean,
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+/// How an ensemble of runs behaved, generation by generation. Each list has an
+/// entry for every generation from 0 (the random starting layers) to the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnsembleReport {
+    pub rule: u8,
+    pub samples: usize,
+    /// The fraction of cells alive.
+    pub density: Vec<MeanStd>,
+    /// The fraction of cells which changed since the generation before, which
+    /// is always zero for generation 0.
+    pub activity: Vec<MeanStd>,
+    /// The fraction of samples which had reached a fixed point or a cycle of
+    /// at most `MAX_SETTLED_PERIOD` generations.
+    pub settled: Vec<f64>,
+}
+
+// What happened in a single sample, one entry per generation.
+struct Sample {
+    density: Vec<f64>,
+    activity: Vec<f64>,
+    settled: Vec<bool>,
+}
+
+fn run_sample(rule: u8, seed: Vec<bool>, generations: usize) -> Sample {
+    let width = seed.len().max(1) as f64;
+    let fraction = |cells: usize| cells as f64 / width;
+
+    let mut sample = Sample {
+        density: Vec::with_capacity(generations + 1),
+        activity: Vec::with_capacity(generations + 1),
+        settled: Vec::with_capacity(generations + 1),
+    };
+    let mut recent: VecDeque<Vec<bool>> = VecDeque::with_capacity(MAX_SETTLED_PERIOD);
+    let mut layer = seed;
+    let mut settled = false;
+
+    for generation in 0..=generations {
+        if generation > 0 {
+            let next = next_layer_wrapping(rule, &layer);
+            let changed = layer.iter().zip(&next).filter(|(a, b)| a != b).count();
+            sample.activity.push(fraction(changed));
+            if recent.len() == MAX_SETTLED_PERIOD {
+                recent.pop_front();
+            }
+            recent.push_back(layer);
+            settled = settled || recent.contains(&next);
+            layer = next;
+        } else {
+            sample.activity.push(0.0);
+        }
+        sample
+            .density
+            .push(fraction(layer.iter().filter(|&&cell| cell).count()));
+        sample.settled.push(settled);
+    }
+    sample
+}
+
+/// Runs `rule` on `samples` rings of `width` cells, each starting from its own
+/// random layer with cells alive with probability `density`, for
+/// `generations` steps, and sums up how they behaved.
+///
+/// Each sample's layer comes from a seed derived from `seed`, so the same
+/// arguments always give the same report. Samples are run in parallel.
+pub fn run_ensemble(
+    rule: u8,
+    width: usize,
+    density: f64,
+    samples: usize,
+    generations: usize,
+    seed: u64,
+) -> EnsembleReport {
+    let mut rng = Rng::new(seed);
+    let sample_seeds: Vec<u64> = (0..samples).map(|_| rng.next_u64()).collect();
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let runs = run_parallel(
+        &sample_seeds,
+        |&sample_seed| {
+            run_sample(
+                rule,
+                seeds::random(width, density, sample_seed),
+                generations,
+            )
+        },
+        threads,
+    );
+
+    let per_generation = |stat: fn(&Sample) -> &[f64]| -> Vec<MeanStd> {
+        (0..=generations)
+            .map(|g| MeanStd::of(runs.iter().map(|run| stat(run)[g])))
+            .collect()
+    };
+
+    EnsembleReport {
+        rule,
+        samples,
+        density: per_generation(|run| &run.density),
+        activity: per_generation(|run| &run.activity),
+        settled: (0..=generations)
+            .map(|g| {
+                let count = runs.iter().filter(|run| run.settled[g]).count();
+                count as f64 / samples.max(1) as f64
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_0_dies() {
+        let report = run_ensemble(0, 64, 0.5, 20, 5, 1);
+        assert!(report.density[0].mean > 0.3);
+        for stats in &report.density[1..] {
+            assert_eq!(
+                *stats,
+                MeanStd {
+                    mean: 0.0,
+                    std_dev: 0.0
+                }
+            );
+        }
+        // Dead on step 1, and seen to stay dead on step 2.
+        assert_eq!(report.settled, &[0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn rule_204_keeps_density() {
+        let report = run_ensemble(204, 100, 0.3, 16, 10, 7);
+        for stats in &report.density {
+            assert_eq!(stats, &report.density[0]);
+        }
+        assert!((report.density[0].mean - 0.3).abs() < 0.05);
+        assert!(report.activity.iter().all(|a| a.mean == 0.0));
+        assert_eq!(report.settled[0], 0.0);
+        assert!(report.settled[1..].iter().all(|&s| s == 1.0));
+    }
+
+    #[test]
+    fn rule_184_relaxes_to_free_flow() {
+        let report = run_ensemble(184, 200, 0.5, 32, 400, 3);
+        // Rule 184 moves cars along without making or destroying any.
+        for stats in &report.density {
+            assert_eq!(stats, &report.density[0]);
+        }
+
+        // Random traffic starts out with a lot of jams, so only about half
+        // the cells change. At density 0.5 the jams slowly clear, leaving
+        // cars and gaps alternating, where every cell flips every step.
+        let early = report.activity[1].mean;
+        let late = report.activity[400].mean;
+        assert!((0.4..0.6).contains(&early));
+        assert!(late > 0.8);
+        assert!(report.activity[100].mean > early);
+        assert!(report.activity[400].mean >= report.activity[100].mean);
+    }
+
+    #[test]
+    fn reproducible() {
+        assert_eq!(
+            run_ensemble(30, 50, 0.5, 8, 20, 99),
+            run_ensemble(30, 50, 0.5, 8, 20, 99)
+        );
+        assert_ne!(
+            run_ensemble(30, 50, 0.5, 8, 20, 99),
+            run_ensemble(30, 50, 0.5, 8, 20, 100)
+        );
+    }
+}
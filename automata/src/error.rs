@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong reading or writing the crate's formats.
+#[derive(Debug)]
+pub enum CaError {
+    /// Something in the input couldn't be parsed. Lines and columns count
+    /// from 1, and `format` names what was being parsed, like `"RLE"`.
+    Parse {
+        format: &'static str,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io(io::Error),
+    /// A rule which isn't one, saying what's wrong with it.
+    InvalidRule(String),
+    /// Sizes which don't fit together, saying how.
+    InvalidDimensions(String),
+    /// A file in a version of its format which can't be read, or which isn't
+    /// that format at all. `found` is what was there instead of the header.
+    UnsupportedVersion {
+        format: &'static str,
+        found: String,
+    },
+    /// There was nothing to read.
+    Empty,
+    /// An error in the input read from `source`, like a file.
+    Input {
+        source: String,
+        error: Box<CaError>,
+    },
+}
+
+impl CaError {
+    /// Says that the error happened in the input read from `source`.
+    pub fn in_source(self, source: &str) -> CaError {
+        CaError::Input {
+            source: source.to_string(),
+            error: Box::new(self),
+        }
+    }
+
+    /// The error with any `Input` wrappers taken off.
+    pub fn innermost(&self) -> &CaError {
+        match self {
+            CaError::Input { error, .. } => error.innermost(),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for CaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaError::Parse {
+                format,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{} at line {}, column {} of {}",
+                message, line, column, format
+            ),
+            CaError::Io(err) => write!(f, "{}", err),
+            CaError::InvalidRule(message) => write!(f, "invalid rule: {}", message),
+            CaError::InvalidDimensions(message) => write!(f, "wrong size: {}", message),
+            CaError::UnsupportedVersion { format, found } => {
+                write!(f, "'{}' isn't a {} header this can read", found, format)
+            }
+            CaError::Empty => write!(f, "nothing to read"),
+            CaError::Input { source, error } => write!(f, "{}: {}", source, error),
+        }
+    }
+}
+
+impl Error for CaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CaError::Io(err) => Some(err),
+            CaError::Input { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CaError {
+    fn from(err: io::Error) -> CaError {
+        CaError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::SimState;
+    use crate::formats::{parse_cells, parse_layer, parse_rle_row};
+    use crate::rng::Rng;
+    use crate::rule::Rule;
+    use crate::seed_input::{read_seed, SeedFormat};
+
+    // Valid inputs for the parsers, which get cut up and mixed together.
+    const CORPUS: &[&str] = &[
+        "..#.#..",
+        "!Name: Glider\n.O\n..O\nOOO\n",
+        "#C comment\nx = 4, y = 1\n2bob$3o!\n",
+        "0001_1110",
+        "110",
+        "castate 2\nrule 30\ngeneration 2\nwidth 3\npacked 05\n",
+        "castate 1\nrule 30\ngeneration 2\nwidth 3\ncells #.#\n",
+        "99999999999999999999999o",
+        "o18446744073709551615o!",
+        "é\u{0}\r\n\t ",
+    ];
+
+    // Makes up an input by splicing together random pieces of the corpus.
+    fn mangled(rng: &mut Rng) -> String {
+        let mut text = String::new();
+        for _ in 0..rng.below(6) {
+            let chars: Vec<char> = CORPUS[rng.below(CORPUS.len())].chars().collect();
+            let start = rng.below(chars.len());
+            let end = start + rng.below(chars.len() - start + 1);
+            text.extend(&chars[start..end]);
+        }
+        text
+    }
+
+    #[test]
+    fn parsers_never_panic() {
+        let mut rng = Rng::new(1);
+        let whole = CORPUS.iter().map(|text| text.to_string());
+        for text in whole.chain((0..20_000).map(|_| mangled(&mut rng))) {
+            let _ = parse_layer(&text);
+            let _ = parse_cells(&text);
+            let _ = parse_rle_row(&text);
+            let _ = text.parse::<Rule>();
+            let _ = Rule::from_binary_str(&text);
+            let _ = SimState::read_from(text.as_bytes());
+            let _ = read_seed(text.as_bytes(), SeedFormat::Text, "fuzz");
+            let _ = read_seed(text.as_bytes(), SeedFormat::Rle, "fuzz");
+        }
+    }
+
+    #[test]
+    fn messages() {
+        let err = CaError::Parse {
+            format: "RLE",
+            line: 2,
+            column: 5,
+            message: "unexpected 'q'".to_string(),
+        };
+        let err = err.in_source("glider.rle");
+        assert_eq!(
+            err.to_string(),
+            "glider.rle: unexpected 'q' at line 2, column 5 of RLE"
+        );
+        assert!(matches!(err.innermost(), CaError::Parse { line: 2, .. }));
+        assert!(err.source().is_some());
+
+        let err: CaError = io::Error::new(io::ErrorKind::NotFound, "gone").into();
+        assert_eq!(err.to_string(), "gone");
+        let err = CaError::UnsupportedVersion {
+            format: "state",
+            found: "castate 9".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "'castate 9' isn't a state header this can read"
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use crate::ca::next_layer;
+use crate::render::render_layer;
+use crate::rle::StoredLayer;
+
+/// Lazily computes and keeps the layers of a rule, extending them as far as
+/// they're asked for.
+pub struct LayerCache {
+    rule: u8,
+    layers: Vec<StoredLayer>,
+    /// The latest layer, which the next one is computed from.
+    last: Vec<bool>,
+    /// Whether to store layers run-length encoded, when that's smaller.
+    compact: bool,
+    /// The most recently decoded run-length encoded layer.
+    decoded: Vec<bool>,
+}
+
+impl LayerCache {
+    pub fn new(rule: u8) -> LayerCache {
+        LayerCache {
+            rule,
+            layers: vec![StoredLayer::Bits(vec![true])],
+            last: vec![true],
+            compact: false,
+            decoded: Vec::new(),
+        }
+    }
+
+    /// Like `new`, but stores each layer run-length encoded if that takes less
+    /// memory, at the cost of decoding layers when they're asked for.
+    pub fn compact(rule: u8) -> LayerCache {
+        LayerCache {
+            compact: true,
+            ..LayerCache::new(rule)
+        }
+    }
+
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    /// Whether layers are stored run-length encoded, as with `compact`.
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Throws away every layer and starts again from a single cell under
+    /// `rule`, storing layers the same way as before.
+    pub fn reset(&mut self, rule: u8) {
+        *self = LayerCache {
+            compact: self.compact,
+            ..LayerCache::new(rule)
+        };
+    }
+
+    /// The number of layers which have been computed so far.
+    pub fn computed(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Gets the layer at `generation`, computing it and any before it if needed.
+    pub fn layer(&mut self, generation: usize) -> &[bool] {
+        while self.layers.len() <= generation {
+            self.last = next_layer(self.rule, &self.last);
+            let layer = self.last.clone();
+            self.layers.push(if self.compact {
+                StoredLayer::compact(layer)
+            } else {
+                StoredLayer::Bits(layer)
+            });
+        }
+
+        match &self.layers[generation] {
+            StoredLayer::Bits(layer) => layer,
+            StoredLayer::Rle(rle) => {
+                self.decoded = rle.to_bits();
+                &self.decoded
+            }
+        }
+    }
+
+    /// Roughly how much memory the stored layers take up, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        self.layers.iter().map(StoredLayer::memory_bytes).sum()
+    }
+}
+
+/// The furthest generation `g` will jump to. The cache keeps every layer up to
+/// the ones on screen, and layer `n` is `2n + 1` cells, so getting to
+/// generation `n` takes around `n²` bytes; this keeps a jump to about 100MB.
+pub const MAX_JUMP: usize = 10_000;
+
+/// A key press, as far as the explorer is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Enter,
+    Backspace,
+    Esc,
+    Char(char),
+}
+
+/// Everything the explorer shows, apart from the layers themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExploreState {
+    pub rule: u8,
+
+    /// The generation shown in the top row of the viewport.
+    pub top: usize,
+
+    /// The column shown at the left of the viewport. Column 0 is the seed cell,
+    /// and layer `n` spans columns `-n..=n`.
+    pub left: isize,
+
+    /// The size of the viewport, in cells.
+    pub rows: usize,
+    pub cols: usize,
+
+    /// The digits typed so far after pressing `g`, if we're waiting for a
+    /// generation to jump to.
+    pub jump: Option<String>,
+
+    /// Something to tell the user, such as why a jump didn't happen, until the
+    /// next key is pressed.
+    pub message: Option<String>,
+
+    pub quit: bool,
+}
+
+impl ExploreState {
+    /// Starts exploring `rule` from the first generation, centered on the seed.
+    pub fn new(rule: u8, rows: usize, cols: usize) -> ExploreState {
+        ExploreState {
+            rule,
+            top: 0,
+            left: -(cols as isize / 2),
+            rows,
+            cols,
+            jump: None,
+            message: None,
+            quit: false,
+        }
+    }
+}
+
+/// Works out the state after pressing `key`.
+///
+/// Arrows scroll by a cell and page up/down by a screenful, `r` and `R` step
+/// the rule number down and up, `g` starts typing a generation to jump to
+/// (finished with enter, or abandoned with escape), and `q` quits. Jumps
+/// past `MAX_JUMP` are refused with a message instead.
+pub fn apply_key(state: ExploreState, key: Key) -> ExploreState {
+    let state = ExploreState {
+        message: None,
+        ..state
+    };
+    if let Some(jump) = state.jump {
+        return apply_jump_key(
+            ExploreState {
+                jump: None,
+                ..state
+            },
+            jump,
+            key,
+        );
+    }
+
+    match key {
+        Key::Up => ExploreState {
+            top: state.top.saturating_sub(1),
+            ..state
+        },
+        Key::Down => ExploreState {
+            top: state.top + 1,
+            ..state
+        },
+        Key::PageUp => ExploreState {
+            top: state.top.saturating_sub(state.rows),
+            ..state
+        },
+        Key::PageDown => ExploreState {
+            top: state.top + state.rows,
+            ..state
+        },
+        Key::Left => ExploreState {
+            left: state.left - 1,
+            ..state
+        },
+        Key::Right => ExploreState {
+            left: state.left + 1,
+            ..state
+        },
+        Key::Char('r') => ExploreState {
+            rule: state.rule.wrapping_sub(1),
+            ..state
+        },
+        Key::Char('R') => ExploreState {
+            rule: state.rule.wrapping_add(1),
+            ..state
+        },
+        Key::Char('g') => ExploreState {
+            jump: Some(String::new()),
+            ..state
+        },
+        Key::Char('q') => ExploreState {
+            quit: true,
+            ..state
+        },
+        _ => state,
+    }
+}
+
+// Handles a key pressed while typing a generation to jump to. `state.jump` has
+// been taken out into `jump`, and is put back if we're still typing.
+fn apply_jump_key(state: ExploreState, mut jump: String, key: Key) -> ExploreState {
+    match key {
+        Key::Char(c) if c.is_ascii_digit() => {
+            jump.push(c);
+            ExploreState {
+                jump: Some(jump),
+                ..state
+            }
+        }
+        Key::Backspace => {
+            jump.pop();
+            ExploreState {
+                jump: Some(jump),
+                ..state
+            }
+        }
+        Key::Enter if jump.is_empty() => state,
+        Key::Enter => match jump.parse() {
+            Ok(top) if top <= MAX_JUMP => ExploreState { top, ..state },
+            _ => ExploreState {
+                message: Some(format!("can't jump past generation {}", MAX_JUMP)),
+                ..state
+            },
+        },
+        Key::Esc => state,
+        _ => ExploreState {
+            jump: Some(jump),
+            ..state
+        },
+    }
+}
+
+/// Renders the part of the layer at `generation` which falls between columns
+/// `left` and `left + cols`. Columns outside the layer are dead.
+pub fn viewport_row(layer: &[bool], generation: usize, left: isize, cols: usize) -> String {
+    let cells: Vec<bool> = (left..left + cols as isize)
+        .map(|col| {
+            let index = col + generation as isize;
+            index >= 0 && *layer.get(index as usize).unwrap_or(&false)
+        })
+        .collect();
+    render_layer(&cells)
+}
+
+/// Renders every row of the viewport described by `state`, extending `cache` as
+/// needed. The cache is reset if the rule has changed.
+pub fn viewport(state: &ExploreState, cache: &mut LayerCache) -> Vec<String> {
+    if cache.rule() != state.rule {
+        cache.reset(state.rule);
+    }
+
+    (state.top..state.top + state.rows)
+        .map(|generation| viewport_row(cache.layer(generation), generation, state.left, state.cols))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: ExploreState, keys: &[Key]) -> ExploreState {
+        keys.iter().fold(state, |state, &key| apply_key(state, key))
+    }
+
+    #[test]
+    fn starts_centered() {
+        let state = ExploreState::new(30, 10, 9);
+        assert_eq!(state.top, 0);
+        assert_eq!(state.left, -4);
+    }
+
+    #[test]
+    fn scrolling() {
+        let state = ExploreState::new(30, 10, 9);
+        let moved = press(state.clone(), &[Key::Down, Key::Down, Key::Right, Key::Up]);
+        assert_eq!(moved.top, 1);
+        assert_eq!(moved.left, -3);
+
+        // Can't scroll above the first generation.
+        assert_eq!(press(state.clone(), &[Key::Up, Key::PageUp]).top, 0);
+
+        let paged = press(
+            state,
+            &[Key::PageDown, Key::PageDown, Key::PageUp, Key::Left],
+        );
+        assert_eq!(paged.top, 10);
+        assert_eq!(paged.left, -5);
+    }
+
+    #[test]
+    fn changing_rules() {
+        let state = ExploreState::new(30, 10, 9);
+        assert_eq!(press(state.clone(), &[Key::Char('R')]).rule, 31);
+        assert_eq!(
+            press(state.clone(), &[Key::Char('r'), Key::Char('r')]).rule,
+            28
+        );
+
+        // Rule numbers wrap around.
+        assert_eq!(
+            press(ExploreState::new(0, 1, 1), &[Key::Char('r')]).rule,
+            255
+        );
+        assert_eq!(
+            press(ExploreState::new(255, 1, 1), &[Key::Char('R')]).rule,
+            0
+        );
+    }
+
+    #[test]
+    fn jumping() {
+        let state = ExploreState::new(30, 10, 9);
+        let typing = press(
+            state.clone(),
+            &[Key::Char('g'), Key::Char('1'), Key::Char('2')],
+        );
+        assert_eq!(typing.jump.as_deref(), Some("12"));
+
+        // Other keys are ignored while typing.
+        let typing = press(typing, &[Key::Char('q'), Key::Down, Key::Char('5')]);
+        assert_eq!(typing.jump.as_deref(), Some("125"));
+        assert!(!typing.quit);
+        assert_eq!(typing.top, 0);
+
+        let typing = press(typing, &[Key::Backspace]);
+        assert_eq!(typing.jump.as_deref(), Some("12"));
+
+        let jumped = press(typing.clone(), &[Key::Enter]);
+        assert_eq!(jumped.top, 12);
+        assert_eq!(jumped.jump, None);
+
+        let cancelled = press(typing, &[Key::Esc]);
+        assert_eq!(cancelled.top, 0);
+        assert_eq!(cancelled.jump, None);
+
+        // Jumping nowhere doesn't move.
+        let nowhere = press(state.clone(), &[Key::Char('g'), Key::Enter]);
+        assert_eq!(nowhere.top, 0);
+        assert_eq!(nowhere.jump, None);
+        assert_eq!(nowhere.message, None);
+    }
+
+    #[test]
+    fn jumping_too_far() {
+        let state = ExploreState::new(30, 10, 9);
+        let type_jump = |state, digits: &str| {
+            let keys: Vec<Key> = std::iter::once('g')
+                .chain(digits.chars())
+                .map(Key::Char)
+                .chain(std::iter::once(Key::Enter))
+                .collect();
+            press(state, &keys)
+        };
+
+        assert_eq!(type_jump(state.clone(), "10000").top, MAX_JUMP);
+        for digits in ["10001", "99999999999999999999999"] {
+            let refused = type_jump(state.clone(), digits);
+            assert_eq!(refused.top, 0);
+            assert_eq!(refused.jump, None);
+            assert_eq!(
+                refused.message.as_deref(),
+                Some("can't jump past generation 10000")
+            );
+
+            // The message goes with the next key.
+            let moved = press(refused, &[Key::Down]);
+            assert_eq!(moved.top, 1);
+            assert_eq!(moved.message, None);
+        }
+    }
+
+    #[test]
+    fn quitting() {
+        let state = ExploreState::new(30, 10, 9);
+        assert!(!state.quit);
+        assert!(press(state, &[Key::Char('q')]).quit);
+    }
+
+    #[test]
+    fn rows_are_clipped_and_padded() {
+        let layer = [true, false, true, true, false];
+
+        // Generation 2 spans columns -2..=2.
+        assert_eq!(viewport_row(&layer, 2, -4, 9), "..#.##...");
+        assert_eq!(viewport_row(&layer, 2, -1, 3), ".##");
+        assert_eq!(viewport_row(&layer, 2, 5, 3), "...");
+    }
+
+    #[test]
+    fn cache_extends_lazily() {
+        let mut cache = LayerCache::new(90);
+        assert_eq!(cache.computed(), 1);
+        assert_eq!(cache.layer(2), &[true, false, false, false, true]);
+        assert_eq!(cache.computed(), 3);
+    }
+
+    #[test]
+    fn compact_cache() {
+        let mut plain = LayerCache::new(4);
+        let mut compact = LayerCache::compact(4);
+        for generation in (0..300).rev() {
+            assert_eq!(compact.layer(generation), plain.layer(generation));
+        }
+
+        // Rule 4 keeps just the one cell, so nearly every layer is two long runs.
+        assert!(compact.memory_bytes() * 5 < plain.memory_bytes());
+    }
+
+    #[test]
+    fn viewport_follows_rule() {
+        let mut cache = LayerCache::new(90);
+        let state = ExploreState::new(90, 3, 5);
+        assert_eq!(viewport(&state, &mut cache), &["..#..", ".#.#.", "#...#"]);
+
+        let state = apply_key(state, Key::Char('r'));
+        assert_eq!(viewport(&state, &mut cache), &["..#..", "...#.", "##..#"]);
+        assert_eq!(cache.rule(), 89);
+        assert!(!cache.is_compact());
+
+        // A compact cache stays compact under the new rule.
+        let mut cache = LayerCache::compact(90);
+        let state = ExploreState::new(90, 3, 5);
+        viewport(&state, &mut cache);
+        let state = apply_key(state, Key::Char('r'));
+        assert_eq!(viewport(&state, &mut cache), &["..#..", "...#.", "##..#"]);
+        assert_eq!(cache.rule(), 89);
+        assert!(cache.is_compact());
+    }
+}
@@ -0,0 +1,127 @@
+//! Stable hashes of layers, for spotting repeats without keeping every layer.
+//!
+//! A fingerprint is the 64-bit FNV-1a hash of the layer's cells packed eight to
+//! a byte, first cell in the lowest bit, with any unused bits of the last byte
+//! left clear, followed by the layer's length as eight little-endian bytes. It
+//! doesn't depend on the platform, or on how the layer was built, so it's safe
+//! to store and compare across runs.
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, byte: u8) -> u64 {
+    (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+}
+
+// Fingerprints the cells produced by `cells`, of which there are `len`.
+fn fingerprint_cells<I: Iterator<Item = bool>>(cells: I, len: usize) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut byte = 0;
+    for (i, cell) in cells.enumerate() {
+        byte |= (cell as u8) << (i % 8);
+        if i % 8 == 7 {
+            hash = fnv1a(hash, byte);
+            byte = 0;
+        }
+    }
+    if !len.is_multiple_of(8) {
+        hash = fnv1a(hash, byte);
+    }
+
+    (len as u64)
+        .to_le_bytes()
+        .iter()
+        .fold(hash, |hash, &b| fnv1a(hash, b))
+}
+
+/// Fingerprints `layer`. Equal layers always have equal fingerprints.
+pub fn layer_fingerprint(layer: &[bool]) -> u64 {
+    fingerprint_cells(layer.iter().copied(), layer.len())
+}
+
+/// The rotation of a wrapping layer which comes first in lexicographic order,
+/// with dead before live, given as the index of its first cell.
+pub fn min_rotation(layer: &[bool]) -> usize {
+    let rotation = |start: usize| layer[start..].iter().chain(&layer[..start]);
+    (0..layer.len())
+        .min_by(|&a, &b| rotation(a).cmp(rotation(b)))
+        .unwrap_or(0)
+}
+
+/// Fingerprints a wrapping layer, so that every rotation of it gets the same
+/// fingerprint. This is the fingerprint of its `min_rotation`.
+pub fn fingerprint_canonical(layer: &[bool]) -> u64 {
+    let start = min_rotation(layer);
+    let rotated = layer[start..].iter().chain(&layer[..start]).copied();
+    fingerprint_cells(rotated, layer.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds;
+    use std::collections::HashSet;
+
+    #[test]
+    fn equal_layers_match() {
+        let layer = seeds::random(77, 0.5, 1);
+
+        let mut roomy = Vec::with_capacity(1000);
+        roomy.extend_from_slice(&layer);
+        let mut longer = layer.clone();
+        longer.extend_from_slice(&[true; 9]);
+
+        assert_eq!(layer_fingerprint(&layer), layer_fingerprint(&roomy));
+        assert_eq!(layer_fingerprint(&layer), layer_fingerprint(&longer[..77]));
+        assert_ne!(layer_fingerprint(&layer), layer_fingerprint(&longer));
+    }
+
+    #[test]
+    fn length_counts() {
+        // These pack to the same bytes, so only the length tells them apart.
+        assert_ne!(layer_fingerprint(&[]), layer_fingerprint(&[false]));
+        assert_ne!(
+            layer_fingerprint(&[true]),
+            layer_fingerprint(&[true, false])
+        );
+    }
+
+    #[test]
+    fn stable_values() {
+        assert_eq!(layer_fingerprint(&[]), 0xa8c7_f832_281a_39c5);
+        assert_eq!(layer_fingerprint(&[true]), 0x7194_f3e5_9ae4_7dcd);
+        assert_eq!(
+            layer_fingerprint(&seeds::from_str("##..#.#..###").unwrap()),
+            0x7424_ebb4_a71c_8de8
+        );
+    }
+
+    #[test]
+    fn rotations() {
+        let layer = seeds::from_str("#..##.#...").unwrap();
+        assert_eq!(min_rotation(&layer), 7);
+
+        let canonical = fingerprint_canonical(&layer);
+        for start in 0..layer.len() {
+            let mut rotated = layer.clone();
+            rotated.rotate_left(start);
+            assert_eq!(fingerprint_canonical(&rotated), canonical);
+        }
+
+        let mirrored: Vec<bool> = layer.iter().rev().copied().collect();
+        assert_ne!(fingerprint_canonical(&mirrored), canonical);
+        assert_eq!(fingerprint_canonical(&[]), layer_fingerprint(&[]));
+    }
+
+    #[test]
+    fn few_collisions() {
+        let mut layers = HashSet::new();
+        let mut fingerprints = HashSet::new();
+        for seed in 0..100_000 {
+            let layer = seeds::random(64, 0.5, seed);
+            fingerprints.insert(layer_fingerprint(&layer));
+            layers.insert(layer);
+        }
+        assert_eq!(fingerprints.len(), layers.len());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::error::CaError;
+
+// An error for the character `found` at `line` and `column`.
+fn unexpected(format: &'static str, line: usize, column: usize, found: char) -> CaError {
+    CaError::Parse {
+        format,
+        line,
+        column,
+        message: format!("unexpected '{}'", found),
+    }
+}
+
+/// Parses a layer written the way `render_layer` writes them, with `#` for
+/// live cells and `.` for dead ones. Surrounding whitespace is ignored.
+pub fn parse_layer(text: &str) -> Result<Vec<bool>, CaError> {
+    parse_row("layer", text.trim(), 1, '#', '.')
+}
+
+// Parses a row of cells on line `line`.
+fn parse_row(
+    format: &'static str,
+    row: &str,
+    line: usize,
+    live: char,
+    dead: char,
+) -> Result<Vec<bool>, CaError> {
+    row.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            c if c == live => Ok(true),
+            c if c == dead => Ok(false),
+            found => Err(unexpected(format, line, i + 1, found)),
+        })
+        .collect()
+}
+
+/// Parses a 2D pattern in the plaintext (`.cells`) format: `O` for live cells,
+/// `.` for dead ones, and comment lines starting with `!`. Short rows are
+/// padded out with dead cells so that every row is the same width.
+pub fn parse_cells(text: &str) -> Result<Vec<Vec<bool>>, CaError> {
+    let mut rows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.starts_with('!') {
+            continue;
+        }
+        rows.push(parse_row("plaintext", line.trim_end(), i + 1, 'O', '.')?);
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut rows {
+        row.resize(width, false);
+    }
+    Ok(rows)
+}
+
+/// The widest row `parse_rle_row` will read, so that a huge count can't use
+/// up all the memory there is.
+pub const MAX_RLE_WIDTH: usize = 1 << 24;
+
+/// Parses the first row of a pattern in run-length encoded (RLE) format, as a
+/// layer: `b` for dead cells and `o` for live ones, each optionally preceded by
+/// a count. Comment lines starting with `#` and the `x = ...` header are
+/// skipped, and the row ends at a `$` or `!`.
+pub fn parse_rle_row(text: &str) -> Result<Vec<bool>, CaError> {
+    let mut layer = Vec::new();
+    let mut count = String::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') || trimmed.starts_with('x') {
+            continue;
+        }
+
+        for (j, c) in line.chars().enumerate() {
+            let cell = match c {
+                '0'..='9' => {
+                    count.push(c);
+                    continue;
+                }
+                'b' => false,
+                'o' => true,
+                '$' | '!' => return Ok(layer),
+                c if c.is_whitespace() => continue,
+                found => return Err(unexpected("RLE", i + 1, j + 1, found)),
+            };
+
+            // Counts are only digits, so this only fails if they overflow.
+            let run = if count.is_empty() {
+                Some(1)
+            } else {
+                count.parse().ok()
+            };
+            match run {
+                Some(run) if run <= MAX_RLE_WIDTH - layer.len() => {
+                    layer.extend(std::iter::repeat_n(cell, run))
+                }
+                _ => {
+                    return Err(CaError::Parse {
+                        format: "RLE",
+                        line: i + 1,
+                        column: j + 1,
+                        message: format!("row is more than {} cells wide", MAX_RLE_WIDTH),
+                    })
+                }
+            }
+            count.clear();
+        }
+    }
+    Ok(layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The position of a parse error, and what it's about.
+    fn position(err: CaError) -> (&'static str, usize, usize, String) {
+        match err {
+            CaError::Parse {
+                format,
+                line,
+                column,
+                message,
+            } => (format, line, column, message),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn layer() {
+        assert_eq!(parse_layer("#..#").unwrap(), &[true, false, false, true]);
+        assert_eq!(parse_layer("  .#.\n").unwrap(), &[false, true, false]);
+        assert_eq!(parse_layer("").unwrap(), &[]);
+    }
+
+    #[test]
+    fn layer_error() {
+        let err = parse_layer("#.x#").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected 'x' at line 1, column 3 of layer"
+        );
+        assert_eq!(position(err), ("layer", 1, 3, "unexpected 'x'".to_string()));
+    }
+
+    #[test]
+    fn cells() {
+        let text = "!Name: Glider\n!\n.O\n..O\nOOO\n";
+        assert_eq!(
+            parse_cells(text).unwrap(),
+            vec![
+                vec![false, true, false],
+                vec![false, false, true],
+                vec![true, true, true],
+            ]
+        );
+    }
+
+    #[test]
+    fn cells_error() {
+        let err = parse_cells("!comment\n.O.\n.#.\n").unwrap_err();
+        assert_eq!(
+            position(err),
+            ("plaintext", 3, 2, "unexpected '#'".to_string())
+        );
+    }
+
+    #[test]
+    fn rle_row() {
+        assert_eq!(
+            parse_rle_row("2bo3b!").unwrap(),
+            parse_layer("..#...").unwrap()
+        );
+        assert_eq!(
+            parse_rle_row("#N Something\nx = 5, y = 2\n2o\nbo$5o!").unwrap(),
+            parse_layer("##.#").unwrap()
+        );
+        assert_eq!(parse_rle_row("").unwrap(), &[]);
+
+        let err = parse_rle_row("x = 1\n3bq!").unwrap_err();
+        assert_eq!(position(err), ("RLE", 2, 3, "unexpected 'q'".to_string()));
+
+        // Counts too big to hold are an error, rather than overflowing.
+        let err = parse_rle_row("99999999999999999999999o!").unwrap_err();
+        let (_, line, column, message) = position(err);
+        assert_eq!((line, column), (1, 24));
+        assert_eq!(message, "row is more than 16777216 cells wide");
+        let err = parse_rle_row("16777216o2o!").unwrap_err();
+        assert!(matches!(err, CaError::Parse { column: 11, .. }));
+        let err = parse_rle_row("o18446744073709551615o!").unwrap_err();
+        assert!(matches!(err, CaError::Parse { column: 22, .. }));
+    }
+}
@@ -0,0 +1,253 @@
+//! Scoring rules by how interesting they look, so that a search over all 256
+//! can put the ones worth a closer look at the top.
+//!
+//! Nothing here is definitive: each part of the score picks up one way a rule
+//! can be boring, and the total is only high when a rule avoids all of them.
+
+use crate::ca::{next_layer_wrapping, take_layers};
+use crate::rle::RleLayer;
+use crate::rng::Rng;
+use crate::seeds;
+use std::collections::HashMap;
+
+/// Settings for `interestingness`. The same options always give the same
+/// scores.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreOptions {
+    /// The width of the ring the entropy and activity are measured on.
+    pub width: usize,
+    /// How long to run the ring for.
+    pub generations: usize,
+    /// How many of the ring's last layers to measure.
+    pub late_layers: usize,
+    /// The length of the blocks counted for the block entropy.
+    pub block_size: usize,
+    /// How many layers of the triangle grown from a single cell to compress.
+    pub triangle_generations: usize,
+    /// The width of the small rings run until they repeat.
+    pub ring_width: usize,
+    /// How many small rings to average the transient length over.
+    pub rings: usize,
+    /// Where all the random starting layers come from.
+    pub seed: u64,
+}
+
+impl Default for ScoreOptions {
+    fn default() -> ScoreOptions {
+        ScoreOptions {
+            width: 128,
+            generations: 256,
+            late_layers: 32,
+            block_size: 4,
+            triangle_generations: 64,
+            ring_width: 12,
+            rings: 8,
+            seed: 1,
+        }
+    }
+}
+
+/// The parts of a rule's score. Every `_score` field and the total run from 0
+/// (boring) to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreBreakdown {
+    pub rule: u8,
+    /// The Shannon entropy of the blocks in the late layers, in bits per cell.
+    /// Rules which die out or freeze into stripes have little.
+    pub block_entropy: f64,
+    /// The fraction of cells which change each generation, late in the run.
+    pub activity: f64,
+    /// Highest when about half the cells change, and zero when none or all of
+    /// them do.
+    pub activity_score: f64,
+    /// Runs per cell of the triangle grown from a single cell, doubled so that
+    /// noise scores about 1. Simple patterns are made of long runs.
+    pub compression_score: f64,
+    /// How many generations a small ring takes on average to fall into a
+    /// cycle.
+    pub transient: f64,
+    /// Approaches 1 as the transient gets long compared to the ring.
+    pub transient_score: f64,
+    /// The geometric mean of the scores above.
+    pub total: f64,
+}
+
+/// Scores how interesting `rule` looks. See `ScoreBreakdown` for the parts.
+pub fn interestingness(rule: u8, opts: &ScoreOptions) -> ScoreBreakdown {
+    let late = late_layers(rule, opts);
+    let block_entropy = block_entropy(&late, opts.block_size);
+    let activity = activity(&late);
+    let activity_score = 4.0 * activity * (1.0 - activity);
+    let compression_score = compression_score(rule, opts.triangle_generations);
+    let transient = mean_transient(rule, opts);
+    let transient_score = 1.0 - (-transient / opts.ring_width.max(1) as f64).exp();
+
+    let total = (block_entropy * activity_score * compression_score * transient_score).powf(0.25);
+    ScoreBreakdown {
+        rule,
+        block_entropy,
+        activity,
+        activity_score,
+        compression_score,
+        transient,
+        transient_score,
+        total,
+    }
+}
+
+/// Scores every one of `rules`, most interesting first.
+pub fn rank_rules(rules: &[u8], opts: &ScoreOptions) -> Vec<ScoreBreakdown> {
+    let mut scores: Vec<_> = rules
+        .iter()
+        .map(|&rule| interestingness(rule, opts))
+        .collect();
+    scores.sort_by(|a, b| b.total.total_cmp(&a.total));
+    scores
+}
+
+// The last `late_layers` layers of a ring started from random cells.
+fn late_layers(rule: u8, opts: &ScoreOptions) -> Vec<Vec<bool>> {
+    let mut layer = seeds::random(opts.width, 0.5, opts.seed);
+    let mut late = Vec::with_capacity(opts.late_layers);
+    for generation in 0..opts.generations {
+        let next = next_layer_wrapping(rule, &layer);
+        if generation >= opts.generations.saturating_sub(opts.late_layers) {
+            late.push(layer);
+        }
+        layer = next;
+    }
+    late
+}
+
+fn block_entropy(layers: &[Vec<bool>], block_size: usize) -> f64 {
+    let mut counts: HashMap<Vec<bool>, usize> = HashMap::new();
+    for layer in layers {
+        for i in 0..layer.len() {
+            let block = (0..block_size)
+                .map(|j| layer[(i + j) % layer.len()])
+                .collect();
+            *counts.entry(block).or_insert(0) += 1;
+        }
+    }
+
+    let total: usize = counts.values().sum();
+    let bits: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum();
+    bits / block_size.max(1) as f64
+}
+
+fn activity(layers: &[Vec<bool>]) -> f64 {
+    let changes: Vec<f64> = layers
+        .windows(2)
+        .map(|pair| {
+            let changed = pair[0].iter().zip(&pair[1]).filter(|(a, b)| a != b).count();
+            changed as f64 / pair[0].len().max(1) as f64
+        })
+        .collect();
+    changes.iter().sum::<f64>() / changes.len().max(1) as f64
+}
+
+fn compression_score(rule: u8, generations: usize) -> f64 {
+    let (runs, cells) = take_layers(rule, generations).fold((0, 0), |(runs, cells), layer| {
+        (
+            runs + RleLayer::from_bits(&layer).runs(),
+            cells + layer.len(),
+        )
+    });
+    (2.0 * runs as f64 / cells.max(1) as f64).min(1.0)
+}
+
+// The number of generations before a ring starting from `layer` first reaches
+// a layer it's seen before.
+fn transient_length(rule: u8, mut layer: Vec<bool>) -> usize {
+    let mut seen = HashMap::new();
+    let mut generation = 0;
+    loop {
+        if let Some(&first) = seen.get(&layer) {
+            return first;
+        }
+        let next = next_layer_wrapping(rule, &layer);
+        seen.insert(layer, generation);
+        layer = next;
+        generation += 1;
+    }
+}
+
+fn mean_transient(rule: u8, opts: &ScoreOptions) -> f64 {
+    let mut rng = Rng::new(opts.seed);
+    let total: usize = (0..opts.rings)
+        .map(|_| transient_length(rule, seeds::random(opts.ring_width, 0.5, rng.next_u64())))
+        .sum();
+    total as f64 / opts.rings.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_rules() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    #[test]
+    fn uniform_rules_score_nothing() {
+        let opts = ScoreOptions::default();
+        for &rule in &[0, 255] {
+            let score = interestingness(rule, &opts);
+            assert_eq!(score.block_entropy, 0.0);
+            assert_eq!(score.total, 0.0);
+        }
+    }
+
+    #[test]
+    fn chaotic_rules_rank_highly() {
+        let ranked = rank_rules(&all_rules(), &ScoreOptions::default());
+        let top_decile: Vec<u8> = ranked[..26].iter().map(|score| score.rule).collect();
+        assert!(top_decile.contains(&30), "{:?}", top_decile);
+        assert!(top_decile.contains(&110), "{:?}", top_decile);
+
+        let bottom = &ranked[ranked.len() - 1];
+        assert_eq!(bottom.total, 0.0);
+    }
+
+    #[test]
+    fn breakdowns() {
+        let opts = ScoreOptions::default();
+
+        // Rule 204 copies the layer, so nothing ever changes.
+        let identity = interestingness(204, &opts);
+        assert!(identity.block_entropy > 0.9);
+        assert_eq!(identity.activity, 0.0);
+        assert_eq!(identity.transient, 0.0);
+        assert_eq!(identity.total, 0.0);
+
+        // Rule 170 shifts the layer, so it keeps its randomness and moves
+        // half its cells, but the triangle is a single diagonal line.
+        let shift = interestingness(170, &opts);
+        assert!(shift.block_entropy > 0.9);
+        assert!((0.4..0.6).contains(&shift.activity));
+        assert!(shift.compression_score < 0.1);
+
+        // Rule 30 does well on everything.
+        let chaos = interestingness(30, &opts);
+        assert!(chaos.block_entropy > 0.95);
+        assert!(chaos.activity_score > 0.95);
+        assert!(chaos.compression_score > 0.9);
+        assert!(chaos.transient > opts.ring_width as f64);
+        assert!(chaos.total > 0.9);
+    }
+
+    #[test]
+    fn transients() {
+        // Rule 0 clears any ring with a live cell straight away.
+        assert_eq!(transient_length(0, vec![true, false, false]), 1);
+        assert_eq!(transient_length(0, vec![false; 3]), 0);
+        // Rule 170 just rotates the ring round.
+        assert_eq!(transient_length(170, vec![true, false, true, true]), 0);
+    }
+}
@@ -1 +1,44 @@
+pub mod activation;
+pub mod age;
+pub mod asynchronous;
+pub mod background;
+pub mod batch;
 pub mod ca;
+pub mod ca_hash;
+pub mod checkpoint;
+pub mod cli;
+#[cfg(feature = "image")]
+pub mod compose;
+pub mod continuous;
+pub mod diff;
+pub mod ensemble;
+pub mod error;
+pub mod explore;
+pub mod fingerprint;
+pub mod formats;
+pub mod interest;
+pub mod life;
+pub mod linear;
+pub mod macrocell;
+pub mod majority;
+pub mod margolus;
+pub mod memo;
+pub mod npy;
+pub mod observer;
+pub mod packed;
+pub mod period;
+pub mod pipeline;
+pub mod pool;
+pub mod preimage;
+#[cfg(feature = "image")]
+pub mod raster;
+pub mod render;
+pub mod renderer;
+pub mod rle;
+pub mod rng;
+pub mod rule;
+pub mod seed_input;
+pub mod seeds;
+pub mod simulation;
+pub mod store;
+pub mod traffic;
@@ -0,0 +1,205 @@
+use crate::ca::Boundary;
+use crate::majority::{vote, TieBreak};
+
+/// A fixed-size grid for Conway's Game of Life (B3/S23).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    boundary: Boundary,
+
+    // The cells, row by row from the top.
+    cells: Vec<bool>,
+}
+
+impl Grid {
+    /// Creates an empty grid.
+    pub fn new(width: usize, height: usize, boundary: Boundary) -> Grid {
+        Grid {
+            width,
+            height,
+            boundary,
+            cells: vec![false; width * height],
+        }
+    }
+
+    /// Creates a grid exactly the size of `rows`, which must all be the same width.
+    pub fn from_rows(rows: &[Vec<bool>], boundary: Boundary) -> Grid {
+        let width = rows.first().map_or(0, Vec::len);
+        assert!(
+            rows.iter().all(|row| row.len() == width),
+            "rows must all be the same width"
+        );
+
+        Grid {
+            width,
+            height: rows.len(),
+            boundary,
+            cells: rows.concat(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width && y < self.height, "cell out of bounds");
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        assert!(x < self.width && y < self.height, "cell out of bounds");
+        self.cells[y * self.width + x] = alive;
+    }
+
+    /// Copies `pattern` onto the grid, with its top-left corner at (`x`, `y`).
+    pub fn place(&mut self, pattern: &[Vec<bool>], x: usize, y: usize) {
+        for (dy, row) in pattern.iter().enumerate() {
+            for (dx, &alive) in row.iter().enumerate() {
+                self.set(x + dx, y + dy, alive);
+            }
+        }
+    }
+
+    /// The grid's cells, row by row from the top.
+    pub fn rows(&self) -> Vec<Vec<bool>> {
+        if self.width == 0 {
+            return vec![vec![]; self.height];
+        }
+        self.cells
+            .chunks(self.width)
+            .map(<[bool]>::to_vec)
+            .collect()
+    }
+
+    /// The number of live cells.
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|&&cell| cell).count()
+    }
+
+    /// The number of live cells among the 8 around (`x`, `y`).
+    pub fn neighbors(&self, x: usize, y: usize) -> usize {
+        let mut count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) != (0, 0) && self.offset(x, y, dx, dy) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // Gets the cell offset from (`x`, `y`) by (`dx`, `dy`), following the boundary.
+    fn offset(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+        let (x, y) = (x as isize + dx, y as isize + dy);
+        let (w, h) = (self.width as isize, self.height as isize);
+        match self.boundary {
+            Boundary::Wrapping => self.get(x.rem_euclid(w) as usize, y.rem_euclid(h) as usize),
+            Boundary::Dead => {
+                (0..w).contains(&x) && (0..h).contains(&y) && self.get(x as usize, y as usize)
+            }
+        }
+    }
+
+    /// Generates the next generation of the grid.
+    pub fn step(&self) -> Grid {
+        let mut next = Grid::new(self.width, self.height, self.boundary);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let alive = match self.neighbors(x, y) {
+                    3 => true,
+                    2 => self.get(x, y),
+                    _ => false,
+                };
+                next.set(x, y, alive);
+            }
+        }
+        next
+    }
+
+    /// Like `step`, but each cell becomes whatever most of its 8 neighbors
+    /// are, with an even split settled by `ties`.
+    pub fn step_majority(&self, ties: TieBreak) -> Grid {
+        let mut rng = ties.rng();
+        let mut next = Grid::new(self.width, self.height, self.boundary);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let alive = vote(self.neighbors(x, y), 8, self.get(x, y), ties, &mut rng);
+                next.set(x, y, alive);
+            }
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinker() -> Vec<Vec<bool>> {
+        vec![vec![true, true, true]]
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let mut grid = Grid::new(5, 5, Boundary::Dead);
+        grid.place(&blinker(), 1, 2);
+
+        let next = grid.step();
+        assert!(next.get(2, 1) && next.get(2, 2) && next.get(2, 3));
+        assert_eq!(next.population(), 3);
+        assert_eq!(next.step(), grid);
+    }
+
+    #[test]
+    fn wrapping_neighbors() {
+        let mut grid = Grid::new(4, 4, Boundary::Wrapping);
+        grid.set(0, 0, true);
+        assert_eq!(grid.neighbors(3, 3), 1);
+        assert_eq!(grid.neighbors(1, 1), 1);
+        assert_eq!(grid.neighbors(2, 2), 0);
+
+        let dead = Grid::from_rows(&grid.rows(), Boundary::Dead);
+        assert_eq!(dead.neighbors(3, 3), 0);
+    }
+
+    #[test]
+    fn block_is_still() {
+        let block = vec![vec![true, true], vec![true, true]];
+        let mut grid = Grid::new(4, 4, Boundary::Dead);
+        grid.place(&block, 1, 1);
+        assert_eq!(grid.step(), grid);
+    }
+
+    #[test]
+    fn majority_voting() {
+        // A grid mostly alive fills in, and one mostly dead empties out.
+        for &(density, fills) in &[(0.6, true), (0.4, false)] {
+            let cells = crate::seeds::random(64 * 64, density, 1);
+            let rows: Vec<Vec<bool>> = cells.chunks(64).map(<[bool]>::to_vec).collect();
+            let mut grid = Grid::from_rows(&rows, Boundary::Wrapping);
+            let start = grid.population();
+            for _ in 0..10 {
+                grid = grid.step_majority(TieBreak::KeepCurrent);
+            }
+            let fraction = grid.population() as f64 / (64 * 64) as f64;
+            if fills {
+                assert!(grid.population() > start && fraction > 0.8, "{}", fraction);
+            } else {
+                assert!(grid.population() < start && fraction < 0.2, "{}", fraction);
+            }
+        }
+
+        // Four of eight neighbors is a tie.
+        let mut grid = Grid::new(3, 3, Boundary::Dead);
+        grid.place(&[vec![true, true, true], vec![true, false, false]], 0, 0);
+        assert!(!grid.step_majority(TieBreak::KeepCurrent).get(1, 1));
+        assert!(grid.step_majority(TieBreak::Alive).get(1, 1));
+    }
+}
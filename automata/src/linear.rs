@@ -0,0 +1,264 @@
+//! Stepping linear rules with matrices over GF(2), the integers mod 2.
+//!
+//! A rule is linear when each cell becomes the XOR of some of its left
+//! neighbor, itself and its right neighbor, like rule 90 (left XOR right). On
+//! a ring of fixed width a step is then multiplying by a matrix mod 2, so `n`
+//! steps are multiplying by its `n`th power, which takes `O(log n)`
+//! multiplications however big `n` is.
+
+use crate::ca::test_rule;
+
+/// Whether `rule` is linear over GF(2), meaning that stepping the XOR of two
+/// layers gives the XOR of stepping each of them. Rules like 105, which are
+/// linear apart from flipping every cell, don't count.
+pub fn is_linear(rule: u8) -> bool {
+    let step =
+        |pattern: u8| test_rule(rule, (pattern & 4 != 0, pattern & 2 != 0, pattern & 1 != 0));
+    (0..8).all(|x| (0..8).all(|y| step(x ^ y) == step(x) ^ step(y)))
+}
+
+/// A square matrix of bits, with each row packed into 64-bit words. Column
+/// `j` is bit `j % 64` of word `j / 64` of the row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitMatrix {
+    size: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitMatrix {
+    pub fn zero(size: usize) -> BitMatrix {
+        let words_per_row = size.div_ceil(64);
+        BitMatrix {
+            size,
+            words_per_row,
+            words: vec![0; size * words_per_row],
+        }
+    }
+
+    pub fn identity(size: usize) -> BitMatrix {
+        let mut matrix = BitMatrix::zero(size);
+        for i in 0..size {
+            matrix.set(i, i, true);
+        }
+        matrix
+    }
+
+    /// The number of rows, which is also the number of columns.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> bool {
+        assert!(row < self.size && column < self.size, "out of range");
+        self.row(row)[column / 64] >> (column % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, value: bool) {
+        assert!(row < self.size && column < self.size, "out of range");
+        let word = &mut self.row_mut(row)[column / 64];
+        let bit = 1 << (column % 64);
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    fn row(&self, row: usize) -> &[u64] {
+        &self.words[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    fn row_mut(&mut self, row: usize) -> &mut [u64] {
+        &mut self.words[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    /// The product `self · other`, mod 2. Both have to be the same size.
+    pub fn mul(&self, other: &BitMatrix) -> BitMatrix {
+        assert_eq!(self.size, other.size, "matrices are different sizes");
+        let mut out = BitMatrix::zero(self.size);
+        for i in 0..self.size {
+            // Row i of the product is the XOR of the rows of `other` picked
+            // out by row i of `self`.
+            for k in (0..self.size).filter(|&k| self.get(i, k)) {
+                let start = k * self.words_per_row;
+                let source = &other.words[start..start + self.words_per_row];
+                for (word, &bits) in out.row_mut(i).iter_mut().zip(source) {
+                    *word ^= bits;
+                }
+            }
+        }
+        out
+    }
+
+    /// `self` to the power `exponent`, by repeated squaring.
+    pub fn pow(&self, mut exponent: u64) -> BitMatrix {
+        let mut result = BitMatrix::identity(self.size);
+        let mut square = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(&square);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                square = square.mul(&square);
+            }
+        }
+        result
+    }
+
+    /// The product of `self` and the column vector `cells`, mod 2.
+    pub fn mul_vec(&self, cells: &[bool]) -> Vec<bool> {
+        assert_eq!(cells.len(), self.size, "vector is the wrong length");
+        (0..self.size)
+            .map(|i| {
+                (0..self.size)
+                    .filter(|&j| self.get(i, j) && cells[j])
+                    .count()
+                    % 2
+                    == 1
+            })
+            .collect()
+    }
+}
+
+/// The matrix taking a ring of `width` cells one step on under `rule`, or
+/// `None` if the rule isn't linear.
+pub fn rule_matrix(rule: u8, width: usize) -> Option<BitMatrix> {
+    if !is_linear(rule) {
+        return None;
+    }
+    let uses = |pattern| test_rule(rule, pattern);
+    let (left, center, right) = (
+        uses((true, false, false)),
+        uses((false, true, false)),
+        uses((false, false, true)),
+    );
+
+    let mut matrix = BitMatrix::zero(width);
+    for i in 0..width {
+        // On rings narrower than 3, neighbors can be the same cell, and then
+        // they cancel out.
+        for (used, j) in [
+            (left, (i + width - 1) % width),
+            (center, i),
+            (right, (i + 1) % width),
+        ] {
+            if used {
+                let value = matrix.get(i, j);
+                matrix.set(i, j, !value);
+            }
+        }
+    }
+    Some(matrix)
+}
+
+/// Generation `n` of `rule` on a ring, starting from `seed`, or `None` if the
+/// rule isn't linear. This takes `O(width³ log n)` time, so even `n = 10^18`
+/// is quick.
+pub fn nth_layer_linear(rule: u8, seed: &[bool], n: u64) -> Option<Vec<bool>> {
+    Some(rule_matrix(rule, seed.len())?.pow(n).mul_vec(seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::next_layer_wrapping;
+    use crate::seeds;
+    use std::collections::HashMap;
+
+    // The rules which XOR together some subset of left, center and right.
+    fn xor_rules() -> Vec<u8> {
+        let mut rules: Vec<u8> = (0..8u8)
+            .map(|subset| {
+                (0..8).fold(0, |rule, pattern: u8| {
+                    let parity = (pattern & subset).count_ones() % 2;
+                    rule | (parity as u8) << pattern
+                })
+            })
+            .collect();
+        rules.sort();
+        rules
+    }
+
+    fn random_matrix(size: usize, seed: u64) -> BitMatrix {
+        let cells = seeds::random(size * size, 0.5, seed);
+        let mut matrix = BitMatrix::zero(size);
+        for (i, &cell) in cells.iter().enumerate() {
+            matrix.set(i / size, i % size, cell);
+        }
+        matrix
+    }
+
+    #[test]
+    fn linear_rules() {
+        let linear: Vec<u8> = (0..=255).filter(|&rule| is_linear(rule)).collect();
+        assert_eq!(linear, xor_rules());
+        assert_eq!(linear, &[0, 60, 90, 102, 150, 170, 204, 240]);
+        assert!(!is_linear(105));
+        assert_eq!(rule_matrix(30, 8), None);
+        assert_eq!(nth_layer_linear(110, &[true], 3), None);
+    }
+
+    #[test]
+    fn matches_stepping() {
+        for rule in xor_rules() {
+            for width in [1, 2, 3, 7, 16, 65] {
+                let seed = seeds::random(width, 0.5, width as u64);
+                let mut layer = seed.clone();
+                for n in 0..3000 {
+                    if n % 97 == 0 || n < 70 {
+                        let linear = nth_layer_linear(rule, &seed, n).unwrap();
+                        assert_eq!(linear, layer, "rule {}, width {}, n {}", rule, width, n);
+                    }
+                    layer = next_layer_wrapping(rule, &layer);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn squaring() {
+        for size in [1, 5, 64, 70, 130] {
+            let matrix = random_matrix(size, size as u64);
+            let mut squared = matrix.clone();
+            let mut repeated = matrix.clone();
+            let mut power = 1;
+            for k in 1..7 {
+                squared = squared.mul(&squared);
+                while power < 1 << k {
+                    repeated = repeated.mul(&matrix);
+                    power += 1;
+                }
+                assert_eq!(squared, repeated, "size {}, k {}", size, k);
+                assert_eq!(matrix.pow(1 << k), repeated);
+            }
+        }
+        assert_eq!(BitMatrix::zero(3).pow(0), BitMatrix::identity(3));
+    }
+
+    #[test]
+    fn huge_generations() {
+        let n = 10u64.pow(18);
+
+        // Rule 90 on a ring of 2^k cells dies out after 2^k steps.
+        let seed = seeds::random(64, 0.5, 1);
+        assert_eq!(nth_layer_linear(90, &seed, n).unwrap(), vec![false; 64]);
+
+        // Otherwise, find where the run starts repeating and step to the
+        // same point in the cycle.
+        let seed = seeds::random(10, 0.5, 2);
+        let mut seen = HashMap::new();
+        let mut layers = Vec::new();
+        let mut layer = seed.clone();
+        while !seen.contains_key(&layer) {
+            seen.insert(layer.clone(), layers.len());
+            layers.push(layer.clone());
+            layer = next_layer_wrapping(150, &layer);
+        }
+        let start = seen[&layer] as u64;
+        let period = layers.len() as u64 - start;
+        let expected = &layers[(start + (n - start) % period) as usize];
+        assert_eq!(&nth_layer_linear(150, &seed, n).unwrap(), expected);
+    }
+}
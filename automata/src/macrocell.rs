@@ -0,0 +1,266 @@
+//! Writing Life grids in Golly's macrocell format, which stores a pattern as a
+//! quadtree with identical parts written only once, so big sparse patterns
+//! stay small.
+//!
+//! After the header, each line is a node, numbered from 1 in the order they
+//! appear. An 8×8 leaf is written as its rows, each ended by `$`, with `*` for
+//! live cells and `.` for dead ones, leaving off any trailing dead cells and
+//! empty rows. Bigger nodes are written as `level nw ne sw se`, where the
+//! level is the log2 of the node's size and the children are node numbers, or
+//! 0 for an empty child. The last node is the whole pattern.
+
+use crate::life::Grid;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// Leaves are 8×8, which is level 3.
+const LEAF_LEVEL: u32 = 3;
+const LEAF_SIZE: usize = 1 << LEAF_LEVEL;
+
+/// Writes `grid` as a macrocell file, with its top-left corner at the
+/// top-left of the root node. An empty grid is written as just the header.
+pub fn write_macrocell<W: Write>(grid: &Grid, mut w: W) -> io::Result<()> {
+    writeln!(w, "[M2] (automata)")?;
+    writeln!(w, "#R B3/S23")?;
+
+    let mut level = LEAF_LEVEL;
+    while (1 << level) < grid.width().max(grid.height()) {
+        level += 1;
+    }
+
+    let mut nodes = NodeTable::default();
+    nodes.build(grid, 0, 0, level);
+    for line in &nodes.lines {
+        writeln!(w, "{}", line)?;
+    }
+    Ok(())
+}
+
+// The nodes written so far. A node's number is its index in `lines`, plus 1.
+#[derive(Default)]
+struct NodeTable {
+    lines: Vec<String>,
+    numbers: HashMap<String, usize>,
+}
+
+impl NodeTable {
+    // Returns the number of the node covering the square of size `2^level`
+    // with its top-left at (`x`, `y`), adding it and any children not yet
+    // seen. Empty nodes are 0 and aren't written.
+    fn build(&mut self, grid: &Grid, x: usize, y: usize, level: u32) -> usize {
+        let line = if level == LEAF_LEVEL {
+            leaf_line(grid, x, y)
+        } else {
+            let half = 1 << (level - 1);
+            let children = [
+                self.build(grid, x, y, level - 1),
+                self.build(grid, x + half, y, level - 1),
+                self.build(grid, x, y + half, level - 1),
+                self.build(grid, x + half, y + half, level - 1),
+            ];
+            if children == [0; 4] {
+                return 0;
+            }
+            format!(
+                "{} {} {} {} {}",
+                level, children[0], children[1], children[2], children[3]
+            )
+        };
+        if line.is_empty() {
+            return 0;
+        }
+
+        if let Some(&number) = self.numbers.get(&line) {
+            return number;
+        }
+        self.lines.push(line.clone());
+        self.numbers.insert(line, self.lines.len());
+        self.lines.len()
+    }
+}
+
+// The line for the leaf with its top-left at (`x`, `y`), or an empty string if
+// it's empty. Cells beyond the grid's edges are dead.
+fn leaf_line(grid: &Grid, x: usize, y: usize) -> String {
+    let alive = |cx: usize, cy: usize| cx < grid.width() && cy < grid.height() && grid.get(cx, cy);
+
+    let mut line = String::new();
+    let mut empty_rows = 0;
+    for row in y..y + LEAF_SIZE {
+        let cells: String = (x..x + LEAF_SIZE)
+            .map(|column| if alive(column, row) { '*' } else { '.' })
+            .collect();
+        let cells = cells.trim_end_matches('.');
+        if cells.is_empty() {
+            empty_rows += 1;
+        } else {
+            // Empty rows in between still have to be written.
+            line.extend(std::iter::repeat_n('$', empty_rows));
+            empty_rows = 0;
+            line.push_str(cells);
+            line.push('$');
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::Boundary;
+    use std::collections::BTreeSet;
+
+    fn glider() -> Vec<Vec<bool>> {
+        vec![
+            vec![false, true, false],
+            vec![false, false, true],
+            vec![true, true, true],
+        ]
+    }
+
+    fn block() -> Vec<Vec<bool>> {
+        vec![vec![true, true], vec![true, true]]
+    }
+
+    fn to_string(grid: &Grid) -> String {
+        let mut out = Vec::new();
+        write_macrocell(grid, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn live_cells(grid: &Grid) -> BTreeSet<(usize, usize)> {
+        let mut cells = BTreeSet::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y) {
+                    cells.insert((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    // Reads a macrocell file back into its live cells, relative to the top-left
+    // of the root node.
+    fn parse(text: &str) -> BTreeSet<(usize, usize)> {
+        enum Node {
+            Leaf(Vec<(usize, usize)>),
+            Interior(u32, [usize; 4]),
+        }
+
+        let mut nodes = Vec::new();
+        for line in text.lines() {
+            if line.starts_with('[') || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with(|c: char| c.is_ascii_digit()) {
+                let numbers: Vec<usize> = line.split(' ').map(|n| n.parse().unwrap()).collect();
+                let children = [numbers[1], numbers[2], numbers[3], numbers[4]];
+                for &child in &children {
+                    assert!(
+                        child <= nodes.len(),
+                        "child {} used before it's defined",
+                        child
+                    );
+                }
+                nodes.push(Node::Interior(numbers[0] as u32, children));
+            } else {
+                let mut cells = Vec::new();
+                let (mut x, mut y) = (0, 0);
+                for c in line.chars() {
+                    match c {
+                        '$' => {
+                            x = 0;
+                            y += 1;
+                        }
+                        '*' => {
+                            cells.push((x, y));
+                            x += 1;
+                        }
+                        '.' => x += 1,
+                        other => panic!("unexpected {:?} in leaf", other),
+                    }
+                }
+                nodes.push(Node::Leaf(cells));
+            }
+        }
+
+        fn expand(
+            nodes: &[Node],
+            number: usize,
+            x: usize,
+            y: usize,
+            out: &mut BTreeSet<(usize, usize)>,
+        ) {
+            if number == 0 {
+                return;
+            }
+            match &nodes[number - 1] {
+                Node::Leaf(cells) => out.extend(cells.iter().map(|&(cx, cy)| (x + cx, y + cy))),
+                Node::Interior(level, children) => {
+                    let half = 1 << (level - 1);
+                    expand(nodes, children[0], x, y, out);
+                    expand(nodes, children[1], x + half, y, out);
+                    expand(nodes, children[2], x, y + half, out);
+                    expand(nodes, children[3], x + half, y + half, out);
+                }
+            }
+        }
+
+        let mut cells = BTreeSet::new();
+        expand(&nodes, nodes.len(), 0, 0, &mut cells);
+        cells
+    }
+
+    #[test]
+    fn known_pattern() {
+        // A glider in the top-right quarter of a 16×16 grid, and the same
+        // block in the top-left and bottom-right, which is only written once.
+        let mut grid = Grid::new(16, 16, Boundary::Dead);
+        grid.place(&glider(), 9, 1);
+        grid.place(&block(), 1, 1);
+        grid.place(&block(), 9, 9);
+
+        // The fixture's header can name a different program and carry
+        // comments, so only the rule and the nodes have to match. It's still
+        // the hand-made one, so this doesn't yet show that Golly reads what's
+        // written the same way; that needs Golly's own export checked in.
+        let fixture = include_str!("../tests/macrocell/known_pattern.mc");
+        let text = to_string(&grid);
+        assert_eq!(rule_and_nodes(&text), rule_and_nodes(fixture));
+        assert_eq!(parse(fixture), live_cells(&grid));
+    }
+
+    // The lines of a macrocell file after its first, leaving out comments.
+    fn rule_and_nodes(text: &str) -> Vec<&str> {
+        text.lines()
+            .skip(1)
+            .filter(|line| !line.starts_with("#C"))
+            .collect()
+    }
+
+    #[test]
+    fn empty_grid() {
+        assert_eq!(
+            to_string(&Grid::new(100, 40, Boundary::Dead)),
+            "[M2] (automata)\n#R B3/S23\n"
+        );
+        assert!(parse(&to_string(&Grid::new(0, 0, Boundary::Dead))).is_empty());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut grid = Grid::new(70, 45, Boundary::Dead);
+        for &(x, y) in &[(0, 0), (30, 2), (60, 40), (67, 42), (8, 33)] {
+            grid.place(&glider(), x, y);
+        }
+        grid.place(&block(), 68, 43);
+        let text = to_string(&grid);
+        assert_eq!(parse(&text), live_cells(&grid));
+
+        // Every glider here sits inside one leaf the same way, so that leaf is
+        // only written once.
+        let gliders = text.lines().filter(|&line| line == ".*$..*$***$").count();
+        assert_eq!(gliders, 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! Voting rules, where each cell becomes whatever most of its neighbors are.
+//!
+//! A cell's neighbors are the `radius` cells either side of it, not counting
+//! itself, so there can be a tie, which is settled by a `TieBreak`. With
+//! radius 1 and `TieBreak::KeepCurrent`, that's the same as taking the
+//! majority of the cell and its two neighbors, which is rule 232.
+
+use crate::ca::{next_layer_wrapping, rule_number_of};
+use crate::rng::Rng;
+use std::cmp::Ordering;
+
+/// What a cell becomes when its neighbors are evenly split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// It stays as it is.
+    KeepCurrent,
+    Dead,
+    Alive,
+    /// It's alive or dead with equal chance. Ties are drawn from
+    /// `Rng::new(seed)` afresh each step, in order along the layer, so change
+    /// the seed between steps if they should differ.
+    Random(u64),
+}
+
+impl TieBreak {
+    // A generator for `Random` ties, or any generator otherwise, since it
+    // won't be used.
+    pub(crate) fn rng(self) -> Rng {
+        match self {
+            TieBreak::Random(seed) => Rng::new(seed),
+            _ => Rng::new(0),
+        }
+    }
+}
+
+/// Decides a cell which is `current` now, from `alive` votes out of `voters`.
+pub(crate) fn vote(
+    alive: usize,
+    voters: usize,
+    current: bool,
+    ties: TieBreak,
+    rng: &mut Rng,
+) -> bool {
+    match (2 * alive).cmp(&voters) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => match ties {
+            TieBreak::KeepCurrent => current,
+            TieBreak::Dead => false,
+            TieBreak::Alive => true,
+            TieBreak::Random(_) => rng.gen_bool(0.5),
+        },
+    }
+}
+
+/// A voting rule, ready to step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleSpec {
+    /// A radius 1 rule with fixed ties, which is just an elementary rule.
+    Elementary(u8),
+    /// Any other voting rule.
+    Vote { radius: usize, ties: TieBreak },
+}
+
+impl RuleSpec {
+    /// Steps a ring of cells once.
+    pub fn step_ring(&self, layer: &[bool]) -> Vec<bool> {
+        let (radius, ties) = match *self {
+            RuleSpec::Elementary(rule) => return next_layer_wrapping(rule, layer),
+            RuleSpec::Vote { radius, ties } => (radius, ties),
+        };
+
+        let width = layer.len();
+        let mut rng = ties.rng();
+        (0..width)
+            .map(|i| {
+                let alive = (1..=radius)
+                    .filter(|&d| layer[(i + width - d % width) % width])
+                    .chain((1..=radius).filter(|&d| layer[(i + d) % width]))
+                    .count();
+                vote(alive, 2 * radius, layer[i], ties, &mut rng)
+            })
+            .collect()
+    }
+}
+
+/// The majority rule over `radius` cells either side, settling ties with
+/// `ties`. Radius 1 with fixed ties gives an elementary rule: 232 when ties
+/// keep the current cell, 160 when they're dead and 250 when they're alive.
+pub fn majority_rule(radius: u8, ties: TieBreak) -> RuleSpec {
+    if radius == 1 && !matches!(ties, TieBreak::Random(_)) {
+        return RuleSpec::Elementary(rule_number_of(|left, center, right| {
+            vote(
+                left as usize + right as usize,
+                2,
+                center,
+                ties,
+                &mut ties.rng(),
+            )
+        }));
+    }
+    RuleSpec::Vote {
+        radius: radius as usize,
+        ties,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::test_rule;
+    use crate::seeds;
+
+    const FIXED: [TieBreak; 3] = [TieBreak::KeepCurrent, TieBreak::Dead, TieBreak::Alive];
+
+    #[test]
+    fn radius_1() {
+        let rules: Vec<RuleSpec> = FIXED.iter().map(|&ties| majority_rule(1, ties)).collect();
+        assert_eq!(
+            rules,
+            &[
+                RuleSpec::Elementary(232),
+                RuleSpec::Elementary(160),
+                RuleSpec::Elementary(250)
+            ]
+        );
+
+        // Where the neighbors agree there's no tie, so every rule is 232.
+        for rule in [160, 250] {
+            for &cells in &[(false, false), (true, true)] {
+                for center in [false, true] {
+                    let pattern = (cells.0, center, cells.1);
+                    assert_eq!(test_rule(rule, pattern), test_rule(232, pattern));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_ties() {
+        let rule = majority_rule(1, TieBreak::Random(3));
+        assert!(matches!(rule, RuleSpec::Vote { radius: 1, .. }));
+
+        let layer = seeds::random(200, 0.5, 1);
+        let next = rule.step_ring(&layer);
+        let expected = next_layer_wrapping(232, &layer);
+        let mut differences = 0;
+        for i in 0..layer.len() {
+            let (left, right) = (layer[(i + 199) % 200], layer[(i + 1) % 200]);
+            if left == right {
+                assert_eq!(next[i], left);
+            } else if next[i] != expected[i] {
+                differences += 1;
+            }
+        }
+        // About half the ties go the other way from keeping the cell.
+        assert!((20..80).contains(&differences), "{}", differences);
+        assert_eq!(rule.step_ring(&layer), next);
+    }
+
+    #[test]
+    fn wider_rules() {
+        // Every cell of an alternating ring has as many neighbors like it as
+        // unlike it when the radius is even.
+        let alternating = seeds::alternating(20);
+        for radius in [2, 4] {
+            let keep = majority_rule(radius, TieBreak::KeepCurrent);
+            assert_eq!(keep.step_ring(&alternating), alternating);
+            let dead = majority_rule(radius, TieBreak::Dead);
+            assert_eq!(dead.step_ring(&alternating), vec![false; 20]);
+        }
+        // With radius 1 there are no ties, and the pattern flips.
+        let flipped: Vec<bool> = alternating.iter().map(|&cell| !cell).collect();
+        assert_eq!(
+            majority_rule(1, TieBreak::KeepCurrent).step_ring(&alternating),
+            flipped
+        );
+
+        // A lone cell dies, and a gap gets filled in.
+        let rule = majority_rule(2, TieBreak::KeepCurrent);
+        let layer = seeds::from_str("...#....####.###").unwrap();
+        assert_eq!(
+            rule.step_ring(&layer),
+            seeds::from_str("........########").unwrap()
+        );
+    }
+}
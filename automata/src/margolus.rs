@@ -0,0 +1,213 @@
+//! Block automata on the one-dimensional Margolus neighborhood.
+//!
+//! Rather than each cell looking at its neighbors, the layer is cut into pairs
+//! of cells, and each pair is replaced as a whole. The pairs start at even
+//! cells on even steps and at odd cells on odd steps, so information can cross
+//! between them. Layers wrap round, so they need an even number of cells.
+
+use std::mem;
+
+/// What each pair of cells turns into. A pair is numbered by reading it as a
+/// two-bit number, left cell first, so `table[0b10]` is what a live cell
+/// followed by a dead one becomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRule {
+    table: [u8; 4],
+}
+
+impl BlockRule {
+    /// Makes a rule from its table. Every entry has to be a pair, from 0 to 3.
+    pub fn new(table: [u8; 4]) -> BlockRule {
+        assert!(
+            table.iter().all(|&block| block < 4),
+            "blocks are only 2 bits"
+        );
+        BlockRule { table }
+    }
+
+    /// The rule which leaves every pair as it is.
+    pub fn identity() -> BlockRule {
+        BlockRule::new([0, 1, 2, 3])
+    }
+
+    pub fn table(&self) -> [u8; 4] {
+        self.table
+    }
+
+    /// Whether no two pairs turn into the same pair, so that every step can
+    /// be undone.
+    pub fn is_reversible(&self) -> bool {
+        let mut seen = [false; 4];
+        for &block in &self.table {
+            seen[block as usize] = true;
+        }
+        seen.iter().all(|&seen| seen)
+    }
+
+    /// The rule which undoes this one, if it's reversible.
+    pub fn inverse(&self) -> Option<BlockRule> {
+        if !self.is_reversible() {
+            return None;
+        }
+        let mut table = [0; 4];
+        for (from, &to) in self.table.iter().enumerate() {
+            table[to as usize] = from as u8;
+        }
+        Some(BlockRule { table })
+    }
+
+    /// Whether every pair keeps its number of live cells.
+    pub fn conserves_population(&self) -> bool {
+        self.table
+            .iter()
+            .enumerate()
+            .all(|(from, &to)| (from as u8).count_ones() == to.count_ones())
+    }
+
+    fn apply(&self, left: bool, right: bool) -> (bool, bool) {
+        let block = self.table[(left as usize) << 1 | right as usize];
+        (block & 0b10 != 0, block & 0b01 != 0)
+    }
+}
+
+/// Applies `rule` to every pair of `layer`, with pairs starting at even cells
+/// if `phase` is even, and at odd cells (wrapping round) if it's odd.
+pub fn step_blocks(rule: BlockRule, layer: &[bool], phase: usize) -> Vec<bool> {
+    let width = layer.len();
+    assert!(
+        width.is_multiple_of(2),
+        "layers need an even number of cells"
+    );
+
+    let mut next = vec![false; width];
+    for start in (phase % 2..width + phase % 2).step_by(2) {
+        let (left, right) = (start % width, (start + 1) % width);
+        let (a, b) = rule.apply(layer[left], layer[right]);
+        next[left] = a;
+        next[right] = b;
+    }
+    next
+}
+
+/// Iterates through the layers of a block automaton run from `seed`, starting
+/// with the even phase.
+pub fn iter_blocks(rule: BlockRule, seed: Vec<bool>) -> BlockLayers {
+    BlockLayers {
+        rule,
+        current: seed,
+        phase: 0,
+    }
+}
+
+/// The layers of a block automaton, alternating phases. Made by
+/// `iter_blocks`.
+#[derive(Debug, Clone)]
+pub struct BlockLayers {
+    rule: BlockRule,
+    /// The layer which will be yielded next.
+    current: Vec<bool>,
+    /// The phase of the step after the next layer.
+    phase: usize,
+}
+
+impl Iterator for BlockLayers {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        let next = step_blocks(self.rule, &self.current, self.phase);
+        self.phase += 1;
+        Some(mem::replace(&mut self.current, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds;
+
+    // Swaps the cells of each pair, so a lone cell moves to the other side.
+    const SWAP: [u8; 4] = [0b00, 0b10, 0b01, 0b11];
+
+    fn population(layer: &[bool]) -> usize {
+        layer.iter().filter(|&&cell| cell).count()
+    }
+
+    #[test]
+    fn identity() {
+        let seed = seeds::random(40, 0.5, 1);
+        for layer in iter_blocks(BlockRule::identity(), seed.clone()).take(10) {
+            assert_eq!(layer, seed);
+        }
+    }
+
+    #[test]
+    fn phases() {
+        let seed = seeds::from_str("#.....").unwrap();
+        let rendered: Vec<String> = iter_blocks(BlockRule::new(SWAP), seed)
+            .take(5)
+            .map(|layer| crate::render::render_layer(&layer))
+            .collect();
+        // Swapping with alternating partners, a lone cell keeps moving.
+        assert_eq!(
+            rendered,
+            &["#.....", ".#....", "..#...", "...#..", "....#."]
+        );
+
+        // On an odd step, the last cell pairs up with the first.
+        let wrapped = step_blocks(BlockRule::new(SWAP), &seeds::from_str("#...").unwrap(), 1);
+        assert_eq!(wrapped, seeds::from_str("...#").unwrap());
+    }
+
+    #[test]
+    fn reversible_runs_back() {
+        // Counts each pair up by one, wrapping round from 0b11 to 0b00. That's
+        // a permutation, but not its own inverse, and it doesn't keep the
+        // population.
+        let rule = BlockRule::new([0b01, 0b10, 0b11, 0b00]);
+        assert!(rule.is_reversible());
+        let inverse = rule.inverse().unwrap();
+        assert_eq!(inverse.table(), [0b11, 0b00, 0b01, 0b10]);
+
+        let seed = seeds::random(64, 0.5, 2);
+        let steps = 25;
+        let mut layer = seed.clone();
+        for phase in 0..steps {
+            layer = step_blocks(rule, &layer, phase);
+        }
+        assert_ne!(layer, seed);
+        for phase in (0..steps).rev() {
+            layer = step_blocks(inverse, &layer, phase);
+        }
+        assert_eq!(layer, seed);
+    }
+
+    #[test]
+    fn irreversible_rules() {
+        let kill = BlockRule::new([0, 0, 0, 0]);
+        assert!(!kill.is_reversible());
+        assert_eq!(kill.inverse(), None);
+        assert!(BlockRule::identity().is_reversible());
+        assert_eq!(BlockRule::new(SWAP).inverse(), Some(BlockRule::new(SWAP)));
+    }
+
+    #[test]
+    fn conserving_rules() {
+        // Swap pairs, or make the pair fall left.
+        for &table in &[SWAP, [0b00, 0b10, 0b10, 0b11]] {
+            let rule = BlockRule::new(table);
+            assert!(rule.conserves_population());
+            let seed = seeds::random(100, 0.4, 3);
+            let expected = population(&seed);
+            for layer in iter_blocks(rule, seed).take(50) {
+                assert_eq!(population(&layer), expected);
+            }
+        }
+        assert!(!BlockRule::new([0b01, 0b10, 0b11, 0b00]).conserves_population());
+    }
+
+    #[test]
+    #[should_panic(expected = "even number of cells")]
+    fn odd_widths() {
+        step_blocks(BlockRule::identity(), &[true, false, true], 0);
+    }
+}
@@ -0,0 +1,357 @@
+//! Skipping far ahead through the layers of a rule by remembering how blocks
+//! of cells turn out, the way hashlife does for Life.
+//!
+//! The layer is held as a binary tree of blocks, with each distinct block only
+//! stored once. Since nothing moves faster than a cell a generation, a block
+//! of `2^k` cells decides the middle `2^(k-1)` cells for the next `2^(k-2)`
+//! generations, whatever is around it. Once that's been worked out for a
+//! block, it's looked up rather than worked out again, so layers made of
+//! repeating blocks can be jumped through in huge strides.
+//!
+//! This relies on empty space staying empty, so rules which turn `000` into a
+//! live cell are just stepped as normal.
+
+use crate::ca::{iter_layers_from, test_rule};
+use std::collections::HashMap;
+
+// Leaves hold 32 cells, with cell `i` in bit `i`.
+const LEAF_LEVEL: u32 = 5;
+const LEAF_SIZE: isize = 1 << LEAF_LEVEL;
+
+/// The most results remembered at once. Past this, the memo table is cleared
+/// and starts again.
+pub const MAX_RESULTS: usize = 1 << 22;
+
+/// Generation `n` of `rule`, starting from a single live cell. The same as
+/// `iter_layers(rule).nth(n)`, but much faster for large `n` when the run is
+/// regular.
+pub fn nth_layer_memo(rule: u8, n: usize) -> Vec<bool> {
+    nth_layer_memo_from(rule, &[true], n)
+}
+
+/// Like `nth_layer_memo`, but starting from `seed`.
+pub fn nth_layer_memo_from(rule: u8, seed: &[bool], n: usize) -> Vec<bool> {
+    if test_rule(rule, (false, false, false)) {
+        return iter_layers_from(rule, seed.to_vec()).nth(n).unwrap();
+    }
+
+    let mut memo = Memo::new(rule);
+    let mut level = LEAF_LEVEL + 1;
+    while (1 << level) < seed.len() {
+        level += 1;
+    }
+    let mut root = memo.build(seed, 0, level);
+    // The position of the root's first cell, where the seed starts at 0.
+    let mut origin: isize = 0;
+
+    // Live cells can only be within `done` cells of the seed, so jump ahead
+    // a power of two at a time, growing the root whenever they might spread
+    // out of its middle half.
+    let len = seed.len() as isize;
+    let mut done = 0;
+    for j in 0..usize::BITS {
+        if n >> j & 1 == 0 {
+            continue;
+        }
+        let stride = 1 << j;
+        loop {
+            let level = memo.level(root);
+            let quarter = 1 << (level - 2);
+            if level >= j + 2
+                && -done - stride >= origin + quarter
+                && len + done + stride <= origin + 3 * quarter
+            {
+                break;
+            }
+            root = memo.expand(root);
+            origin -= 1 << (level - 1);
+        }
+        origin += 1 << (memo.level(root) - 2);
+        root = memo.step(root, j);
+        done += stride;
+    }
+
+    let mut out = vec![false; seed.len() + 2 * n];
+    memo.write_cells(root, origin, -(n as isize), &mut out);
+    out
+}
+
+type NodeId = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Leaf(u32),
+    /// Two nodes one level down, side by side.
+    Branch {
+        level: u32,
+        left: NodeId,
+        right: NodeId,
+    },
+}
+
+struct Memo {
+    rule: u8,
+    nodes: Vec<Node>,
+    ids: HashMap<Node, NodeId>,
+    /// The result of stepping a node `2^j` generations, by node and `j`.
+    results: HashMap<(NodeId, u32), NodeId>,
+    /// The empty node at each level, counting up from the leaves.
+    empty: Vec<NodeId>,
+}
+
+impl Memo {
+    fn new(rule: u8) -> Memo {
+        Memo {
+            rule,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            results: HashMap::new(),
+            empty: Vec::new(),
+        }
+    }
+
+    fn intern(&mut self, node: Node) -> NodeId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        self.ids.insert(node, id);
+        id
+    }
+
+    fn level(&self, id: NodeId) -> u32 {
+        match self.nodes[id as usize] {
+            Node::Leaf(_) => LEAF_LEVEL,
+            Node::Branch { level, .. } => level,
+        }
+    }
+
+    fn children(&self, id: NodeId) -> (NodeId, NodeId) {
+        match self.nodes[id as usize] {
+            Node::Branch { left, right, .. } => (left, right),
+            Node::Leaf(_) => unreachable!("leaves have no children"),
+        }
+    }
+
+    fn branch(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        let level = self.level(left) + 1;
+        self.intern(Node::Branch { level, left, right })
+    }
+
+    fn empty(&mut self, level: u32) -> NodeId {
+        while self.empty.len() <= (level - LEAF_LEVEL) as usize {
+            let node = match self.empty.last() {
+                Some(&below) => self.branch(below, below),
+                None => self.intern(Node::Leaf(0)),
+            };
+            self.empty.push(node);
+        }
+        self.empty[(level - LEAF_LEVEL) as usize]
+    }
+
+    // The node at `level` holding `cells[start..]`, with dead cells beyond
+    // either end.
+    fn build(&mut self, cells: &[bool], start: isize, level: u32) -> NodeId {
+        let len = cells.len() as isize;
+        if start >= len || start + (1 << level) <= 0 {
+            return self.empty(level);
+        }
+        if level == LEAF_LEVEL {
+            let bits = (0..LEAF_SIZE)
+                .filter(|&i| (0..len).contains(&(start + i)) && cells[(start + i) as usize])
+                .fold(0, |bits, i| bits | 1 << i);
+            return self.intern(Node::Leaf(bits));
+        }
+        let left = self.build(cells, start, level - 1);
+        let right = self.build(cells, start + (1 << (level - 1)), level - 1);
+        self.branch(left, right)
+    }
+
+    // Doubles the size of `id`, keeping it in the middle.
+    fn expand(&mut self, id: NodeId) -> NodeId {
+        let level = self.level(id);
+        let (left, right) = self.children(id);
+        let empty = self.empty(level - 1);
+        let left = self.branch(empty, left);
+        let right = self.branch(right, empty);
+        self.branch(left, right)
+    }
+
+    // The middle half of `id`, one level down.
+    fn center(&mut self, id: NodeId) -> NodeId {
+        let (left, right) = self.children(id);
+        match (self.nodes[left as usize], self.nodes[right as usize]) {
+            (Node::Leaf(left), Node::Leaf(right)) => {
+                let half = LEAF_SIZE / 2;
+                self.intern(Node::Leaf(left >> half | right << half))
+            }
+            _ => {
+                let (_, inner_left) = self.children(left);
+                let (inner_right, _) = self.children(right);
+                self.branch(inner_left, inner_right)
+            }
+        }
+    }
+
+    // The middle half of `id`, `2^j` generations on. `j` can be at most two
+    // less than the node's level.
+    fn step(&mut self, id: NodeId, j: u32) -> NodeId {
+        if let Some(&result) = self.results.get(&(id, j)) {
+            return result;
+        }
+
+        let level = self.level(id);
+        debug_assert!(j + 2 <= level);
+        let result = if id == self.empty(level) {
+            self.empty(level - 1)
+        } else if level == LEAF_LEVEL + 1 {
+            self.step_leaves(id, j)
+        } else {
+            let (a, b) = self.children(id);
+            let (_, a1) = self.children(a);
+            let (b0, _) = self.children(b);
+            let middle = self.branch(a1, b0);
+
+            // Get three overlapping nodes a quarter of the way down, either
+            // stepped halfway or not at all, and then step the two halves
+            // made from them the rest of the way.
+            let (j, first) = if j == level - 2 {
+                (
+                    j - 1,
+                    [
+                        self.step(a, j - 1),
+                        self.step(middle, j - 1),
+                        self.step(b, j - 1),
+                    ],
+                )
+            } else {
+                (j, [self.center(a), self.center(middle), self.center(b)])
+            };
+            let left = self.branch(first[0], first[1]);
+            let right = self.branch(first[1], first[2]);
+            let left = self.step(left, j);
+            let right = self.step(right, j);
+            self.branch(left, right)
+        };
+
+        if self.results.len() >= MAX_RESULTS {
+            self.results.clear();
+        }
+        self.results.insert((id, j), result);
+        result
+    }
+
+    // Steps a node made of two leaves directly, a generation at a time.
+    fn step_leaves(&mut self, id: NodeId, j: u32) -> NodeId {
+        let (left, right) = self.children(id);
+        let (Node::Leaf(left), Node::Leaf(right)) =
+            (self.nodes[left as usize], self.nodes[right as usize])
+        else {
+            unreachable!("level {} nodes are made of leaves", LEAF_LEVEL + 1)
+        };
+
+        let mut cells = left as u64 | (right as u64) << LEAF_SIZE;
+        for _ in 0..1 << j {
+            cells = step_word(self.rule, cells);
+        }
+        self.intern(Node::Leaf((cells >> (LEAF_SIZE / 2)) as u32))
+    }
+
+    // Copies the cells of `id`, whose first cell is at `start`, into `out`,
+    // whose first cell is at `out_start`.
+    fn write_cells(&self, id: NodeId, start: isize, out_start: isize, out: &mut [bool]) {
+        let out_end = out_start + out.len() as isize;
+        if start + (1 << self.level(id)) <= out_start || start >= out_end {
+            return;
+        }
+        match self.nodes[id as usize] {
+            Node::Leaf(bits) => {
+                for i in 0..LEAF_SIZE {
+                    if (out_start..out_end).contains(&(start + i)) {
+                        out[(start + i - out_start) as usize] = bits >> i & 1 == 1;
+                    }
+                }
+            }
+            Node::Branch { level, left, right } => {
+                self.write_cells(left, start, out_start, out);
+                self.write_cells(right, start + (1 << (level - 1)), out_start, out);
+            }
+        }
+    }
+}
+
+// Steps 64 cells held in a word once, with dead cells beyond either end.
+fn step_word(rule: u8, cells: u64) -> u64 {
+    // Line up each cell's left and right neighbors with it.
+    let left = cells << 1;
+    let right = cells >> 1;
+    (0..8)
+        .filter(|&pattern| rule >> pattern & 1 == 1)
+        .fold(0, |out, pattern| {
+            let matches = |bit: u8, word: u64| if pattern >> bit & 1 == 1 { word } else { !word };
+            out | matches(2, left) & matches(1, cells) & matches(0, right)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::{iter_layers, next_layer};
+    use crate::seeds;
+
+    #[test]
+    fn word_steps() {
+        let cells: Vec<bool> = (0..64).map(|i| i % 3 == 0 || i % 7 == 2).collect();
+        let word = (0..64).fold(0, |word, i| word | (cells[i] as u64) << i);
+        for rule in (0..=255).step_by(2) {
+            // `next_layer` grows by a cell either side, so drop those.
+            let expected = &next_layer(rule, &cells)[1..65];
+            let stepped = step_word(rule, word);
+            let actual: Vec<bool> = (0..64).map(|i| stepped >> i & 1 == 1).collect();
+            assert_eq!(actual, expected, "rule {}", rule);
+        }
+    }
+
+    // Checks `nth_layer_memo` against stepping one layer at a time, for each
+    // generation in `checked` below `generations`.
+    fn check_rule(rule: u8, generations: usize) {
+        let checked = [
+            0, 1, 2, 31, 32, 33, 64, 100, 255, 256, 700, 1024, 1500, 1999,
+        ];
+        let mut checked = checked.iter().peekable();
+        for (n, expected) in iter_layers(rule).enumerate().take(generations) {
+            if checked.next_if_eq(&&n).is_some() {
+                assert_eq!(nth_layer_memo(rule, n), expected, "rule {}, n {}", rule, n);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_stepping() {
+        for rule in 0..=255 {
+            check_rule(rule, 300);
+        }
+        // Regular, chaotic and complex rules, and ones that spread at full speed.
+        for &rule in &[22, 30, 90, 110, 150, 250] {
+            check_rule(rule, 2000);
+        }
+    }
+
+    #[test]
+    fn seeded_runs() {
+        for (i, &rule) in [30, 54, 90, 110, 150, 184, 45].iter().enumerate() {
+            let seed = seeds::random(100 + i * 37, 0.5, rule as u64);
+            for n in [0, 1, 500, 1500] {
+                let expected = iter_layers_from(rule, seed.clone()).nth(n).unwrap();
+                assert_eq!(
+                    nth_layer_memo_from(rule, &seed, n),
+                    expected,
+                    "rule {}",
+                    rule
+                );
+            }
+        }
+        assert_eq!(nth_layer_memo_from(30, &[], 3), &[false; 6]);
+    }
+}
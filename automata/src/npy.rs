@@ -0,0 +1,132 @@
+use crate::renderer::{RenderCommon, Renderer};
+use std::io::{self, Write};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Writes `layers` as a NumPy `.npy` file (version 1.0) holding a 2D `uint8`
+/// array, with a row of 0s and 1s for each layer.
+///
+/// Narrower layers are centered in the widest one by padding them out with
+/// dead cells, the same as when rendering them as text, so that cells which
+/// line up on screen line up in the array.
+pub fn write_npy<W: Write>(layers: &[Vec<bool>], mut w: W) -> io::Result<()> {
+    NpyRenderer::default().render(&mut layers.iter().cloned(), &mut w)
+}
+
+/// Writes layers as a `.npy` file, like `write_npy`, with them lined up as
+/// set by `alignment`.
+#[derive(Debug, Clone, Default)]
+pub struct NpyRenderer {
+    pub common: RenderCommon,
+}
+
+impl Renderer for NpyRenderer {
+    fn render(
+        &self,
+        layers: &mut dyn Iterator<Item = Vec<bool>>,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        // The header needs the number of rows as well as the width, so they
+        // can only be written as they come if the count is known too.
+        let (width, rows) = self.common.padded_layers(layers);
+        let rows: Box<dyn Iterator<Item = Vec<bool>>> = match rows.size_hint() {
+            (lower, Some(upper)) if lower == upper => rows,
+            _ => Box::new(rows.collect::<Vec<_>>().into_iter()),
+        };
+        let count = rows.size_hint().0;
+
+        let mut header = format!(
+            "{{'descr': '|u1', 'fortran_order': False, 'shape': ({}, {}), }}",
+            count, width
+        );
+        // The header is padded with spaces and ends in a newline, so that the
+        // data starts on a multiple of 64 bytes, counting the magic, version
+        // and length.
+        let unpadded = MAGIC.len() + 4 + header.len() + 1;
+        header.extend(std::iter::repeat_n(' ', (64 - unpadded % 64) % 64));
+        header.push('\n');
+
+        w.write_all(MAGIC)?;
+        w.write_all(&[1, 0])?;
+        w.write_all(&(header.len() as u16).to_le_bytes())?;
+        w.write_all(header.as_bytes())?;
+
+        let mut written = 0;
+        for row in rows {
+            let bytes: Vec<u8> = row.iter().map(|&cell| cell as u8).collect();
+            w.write_all(&bytes)?;
+            written += 1;
+        }
+        if written != count {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected {} layers but there were {}", count, written),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+
+    // Just enough of a .npy reader to read back what `write_npy` writes,
+    // returning the header and the array's rows.
+    fn read_npy(bytes: &[u8]) -> (String, Vec<Vec<u8>>) {
+        assert_eq!(&bytes[..6], MAGIC);
+        assert_eq!(&bytes[6..8], &[1, 0]);
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = String::from_utf8(bytes[10..10 + header_len].to_vec()).unwrap();
+
+        let shape = header.split("'shape': (").nth(1).unwrap();
+        let shape: Vec<usize> = shape
+            .split(')')
+            .next()
+            .unwrap()
+            .split(", ")
+            .map(|n| n.parse().unwrap())
+            .collect();
+        let (rows, cols) = (shape[0], shape[1]);
+
+        let data = &bytes[10 + header_len..];
+        assert_eq!(data.len(), rows * cols);
+        let rows = (0..rows)
+            .map(|r| data[r * cols..(r + 1) * cols].to_vec())
+            .collect();
+        (header, rows)
+    }
+
+    fn npy_bytes(layers: &[Vec<bool>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_npy(layers, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn header() {
+        let layers: Vec<_> = iter_layers(30).take(10).collect();
+        let bytes = npy_bytes(&layers);
+        let (header, _) = read_npy(&bytes);
+
+        assert!(header.starts_with("{'descr': '|u1', 'fortran_order': False, 'shape': (10, 19), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!((10 + header.len()) % 64, 0);
+        assert_eq!(bytes.len(), 10 + header.len() + 10 * 19);
+    }
+
+    #[test]
+    fn round_trip() {
+        let layers: Vec<_> = iter_layers(90).take(3).collect();
+        let (_, rows) = read_npy(&npy_bytes(&layers));
+        assert_eq!(rows, &[[0, 0, 1, 0, 0], [0, 1, 0, 1, 0], [1, 0, 0, 0, 1]]);
+    }
+
+    #[test]
+    fn empty() {
+        let (header, rows) = read_npy(&npy_bytes(&[]));
+        assert!(header.contains("'shape': (0, 0)"));
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,411 @@
+//! Hooks for watching a `Simulation` as it runs, to time it, log it or report
+//! progress, without changing how it's stepped.
+//!
+//! Observers are attached with `Simulation::observe`, and then hear about
+//! every generation as it's stepped and about the end of each run. Steps are
+//! only timed while there are observers attached.
+
+use crate::simulation::RunResult;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Something which watches a run. Observers have to be `Send`, like the
+/// simulations they're attached to.
+pub trait RunObserver: Send {
+    /// Called after each step, with the generation just reached, its layer
+    /// and how long the step took.
+    fn on_generation(&mut self, generation: usize, layer: &[bool], step_duration: Duration);
+
+    /// Called when a run ends, with how it went.
+    fn on_finish(&mut self, _result: &RunResult) {}
+}
+
+/// Shared observers, so that they can still be looked at after they've been
+/// handed to a simulation.
+impl<T: RunObserver> RunObserver for Arc<Mutex<T>> {
+    fn on_generation(&mut self, generation: usize, layer: &[bool], step_duration: Duration) {
+        self.lock()
+            .unwrap()
+            .on_generation(generation, layer, step_duration);
+    }
+
+    fn on_finish(&mut self, result: &RunResult) {
+        self.lock().unwrap().on_finish(result);
+    }
+}
+
+/// The observers attached to a simulation. A cloned simulation starts off
+/// with none, since observers can't be cloned.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn RunObserver>>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Box<dyn RunObserver>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn on_generation(&mut self, generation: usize, layer: &[bool], step: Duration) {
+        for observer in &mut self.0 {
+            observer.on_generation(generation, layer, step);
+        }
+    }
+
+    pub(crate) fn on_finish(&mut self, result: &RunResult) {
+        for observer in &mut self.0 {
+            observer.on_finish(result);
+        }
+    }
+}
+
+impl Clone for Observers {
+    fn clone(&self) -> Observers {
+        Observers::default()
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+// One bucket for each power of two nanoseconds.
+const BUCKETS: usize = 64;
+
+/// Step durations, counted in buckets which double in width, from under 2ns
+/// up.
+#[derive(Debug, Clone)]
+pub struct TimingHistogram {
+    /// How many steps took from `2^i` up to `2^(i + 1)` nanoseconds, by `i`.
+    /// Steps under a nanosecond go in the first bucket.
+    buckets: [u64; BUCKETS],
+    total: Duration,
+    longest: Duration,
+}
+
+impl Default for TimingHistogram {
+    fn default() -> TimingHistogram {
+        TimingHistogram::new()
+    }
+}
+
+impl TimingHistogram {
+    pub fn new() -> TimingHistogram {
+        TimingHistogram {
+            buckets: [0; BUCKETS],
+            total: Duration::from_secs(0),
+            longest: Duration::from_secs(0),
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos().clamp(1, u64::MAX as u128) as u64;
+        self.buckets[nanos.ilog2() as usize] += 1;
+        self.total += duration;
+        self.longest = self.longest.max(duration);
+    }
+
+    /// The number of steps recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// The time taken by all the steps together.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// An upper bound on the duration which a `fraction` of the steps took
+    /// no longer than: the top of the bucket it falls in, or the longest
+    /// step, whichever is shorter. Zero if nothing's been recorded.
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let wanted = (fraction.clamp(0.0, 1.0) * self.count() as f64).ceil() as u64;
+        let mut seen = 0;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if count > 0 && seen >= wanted {
+                let top = Duration::from_nanos(2u64.saturating_pow(i as u32 + 1));
+                return top.min(self.longest);
+            }
+        }
+        self.longest
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(0.5)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(0.99)
+    }
+}
+
+impl RunObserver for TimingHistogram {
+    fn on_generation(&mut self, _generation: usize, _layer: &[bool], step_duration: Duration) {
+        self.record(step_duration);
+    }
+}
+
+/// The header of the stats CSV, which has a row for each generation.
+pub const STATS_CSV_HEADER: &str = "generation,width,population,density";
+
+fn write_stats_row<W: Write>(w: &mut W, generation: usize, layer: &[bool]) -> io::Result<()> {
+    let population = layer.iter().filter(|&&cell| cell).count();
+    let density = population as f64 / layer.len().max(1) as f64;
+    writeln!(
+        w,
+        "{},{},{},{}",
+        generation,
+        layer.len(),
+        population,
+        density
+    )
+}
+
+/// Writes the stats CSV for `layers`, numbering them from generation 0.
+pub fn write_stats_csv<W: Write>(
+    layers: impl IntoIterator<Item = Vec<bool>>,
+    mut w: W,
+) -> io::Result<()> {
+    writeln!(w, "{}", STATS_CSV_HEADER)?;
+    for (generation, layer) in layers.into_iter().enumerate() {
+        write_stats_row(&mut w, generation, &layer)?;
+    }
+    Ok(())
+}
+
+/// Writes the stats CSV a row at a time as the run goes, starting with the
+/// header. The first error writing stops any more being written, and is
+/// handed back by `into_inner`.
+pub struct CsvLogger<W: Write> {
+    w: W,
+    wrote_header: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> CsvLogger<W> {
+    pub fn new(w: W) -> CsvLogger<W> {
+        CsvLogger {
+            w,
+            wrote_header: false,
+            error: None,
+        }
+    }
+
+    /// Writes a row for `layer`, which is in generation `generation`. This
+    /// can be used to log the generation a run starts from.
+    pub fn log(&mut self, generation: usize, layer: &[bool]) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut write = || {
+            if !self.wrote_header {
+                writeln!(self.w, "{}", STATS_CSV_HEADER)?;
+                self.wrote_header = true;
+            }
+            write_stats_row(&mut self.w, generation, layer)
+        };
+        if let Err(err) = write() {
+            self.error = Some(err);
+        }
+    }
+
+    /// The writer, or the first error writing to it.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => {
+                self.w.flush()?;
+                Ok(self.w)
+            }
+        }
+    }
+}
+
+impl<W: Write + Send> RunObserver for CsvLogger<W> {
+    fn on_generation(&mut self, generation: usize, layer: &[bool], _step_duration: Duration) {
+        self.log(generation, layer);
+    }
+
+    fn on_finish(&mut self, _result: &RunResult) {
+        if self.error.is_none() {
+            self.error = self.w.flush().err();
+        }
+    }
+}
+
+/// Prints how far the run has got every so often, and how long it took once
+/// it's done.
+pub struct ProgressPrinter<W: Write> {
+    w: W,
+    every: usize,
+}
+
+impl ProgressPrinter<io::Stdout> {
+    /// Prints to stdout every `every` generations.
+    pub fn stdout(every: usize) -> ProgressPrinter<io::Stdout> {
+        ProgressPrinter::new(io::stdout(), every)
+    }
+}
+
+impl<W: Write> ProgressPrinter<W> {
+    pub fn new(w: W, every: usize) -> ProgressPrinter<W> {
+        assert!(every > 0, "can't print progress every 0 generations");
+        ProgressPrinter { w, every }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+// Progress is only for looking at, so failing to print it isn't worth
+// stopping the run over.
+impl<W: Write + Send> RunObserver for ProgressPrinter<W> {
+    fn on_generation(&mut self, generation: usize, layer: &[bool], _step_duration: Duration) {
+        if generation.is_multiple_of(self.every) {
+            let _ = writeln!(self.w, "Generation {} ({} cells)", generation, layer.len());
+        }
+    }
+
+    fn on_finish(&mut self, result: &RunResult) {
+        let _ = writeln!(self.w, "Done in {:?}", result.elapsed);
+        let _ = self.w.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+    use crate::simulation::Simulation;
+
+    // Remembers every generation it's told about.
+    #[derive(Default)]
+    struct Recorder {
+        generations: Vec<usize>,
+        widths: Vec<usize>,
+        finished: Vec<usize>,
+    }
+
+    impl RunObserver for Recorder {
+        fn on_generation(&mut self, generation: usize, layer: &[bool], _step: Duration) {
+            self.generations.push(generation);
+            self.widths.push(layer.len());
+        }
+
+        fn on_finish(&mut self, result: &RunResult) {
+            self.finished.push(result.generations_completed);
+        }
+    }
+
+    #[test]
+    fn call_counts() {
+        let mut sim = Simulation::new(30, vec![true]);
+        let first = Arc::new(Mutex::new(Recorder::default()));
+        let second = Arc::new(Mutex::new(Recorder::default()));
+        sim.observe(Box::new(first.clone()));
+        sim.observe(Box::new(second.clone()));
+
+        let result = sim.run_until(100);
+        assert_eq!(result.generations_completed, 100);
+        for recorder in [&first, &second] {
+            let recorder = recorder.lock().unwrap();
+            assert_eq!(recorder.generations, (1..=100).collect::<Vec<_>>());
+            let widths: Vec<usize> = (1..=100).map(|n| 2 * n + 1).collect();
+            assert_eq!(recorder.widths, widths);
+            assert_eq!(recorder.finished, &[100]);
+        }
+
+        // Clones don't take the observers with them.
+        let mut clone = sim.clone();
+        clone.step();
+        assert_eq!(first.lock().unwrap().generations.len(), 100);
+    }
+
+    #[test]
+    fn histogram_totals() {
+        let histogram = Arc::new(Mutex::new(TimingHistogram::new()));
+        let mut sim = Simulation::new(30, vec![true]);
+        sim.observe(Box::new(histogram.clone()));
+        let result = sim.run_until(2000);
+
+        let histogram = histogram.lock().unwrap();
+        assert_eq!(histogram.count(), 2000);
+        // The steps are most of the run, but not the time spent watching it.
+        assert!(histogram.total() <= result.elapsed);
+        assert!(histogram.total() >= result.elapsed / 2, "{:?}", histogram);
+        assert!(histogram.p50() <= histogram.p99());
+        assert!(histogram.p99() <= histogram.total());
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut histogram = TimingHistogram::new();
+        assert_eq!(histogram.p50(), Duration::from_secs(0));
+        for _ in 0..98 {
+            histogram.record(Duration::from_nanos(100));
+        }
+        histogram.record(Duration::from_micros(10));
+        histogram.record(Duration::from_millis(1));
+        // 100ns is in the bucket from 64ns to 128ns.
+        assert_eq!(histogram.p50(), Duration::from_nanos(128));
+        assert_eq!(histogram.p99(), Duration::from_nanos(16384));
+        assert_eq!(histogram.percentile(1.0), Duration::from_millis(1));
+        assert_eq!(histogram.count(), 100);
+    }
+
+    #[test]
+    fn csv_matches_batch() {
+        let logger = Arc::new(Mutex::new(CsvLogger::new(Vec::new())));
+        let mut sim = Simulation::new(110, vec![true]);
+        logger.lock().unwrap().log(0, sim.layer());
+        sim.observe(Box::new(logger.clone()));
+        sim.run_until(100);
+        drop(sim);
+
+        let logged = Arc::try_unwrap(logger)
+            .ok()
+            .unwrap()
+            .into_inner()
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let mut batch = Vec::new();
+        write_stats_csv(iter_layers(110).take(101), &mut batch).unwrap();
+        assert_eq!(
+            String::from_utf8(logged).unwrap(),
+            String::from_utf8(batch).unwrap()
+        );
+    }
+
+    #[test]
+    fn progress() {
+        let printer = Arc::new(Mutex::new(ProgressPrinter::new(Vec::new(), 40)));
+        let mut sim = Simulation::new(30, vec![true]);
+        sim.observe(Box::new(printer.clone()));
+        sim.run_until(100);
+        drop(sim);
+
+        let out = Arc::try_unwrap(printer)
+            .ok()
+            .unwrap()
+            .into_inner()
+            .unwrap()
+            .into_inner();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            &lines[..2],
+            &["Generation 40 (81 cells)", "Generation 80 (161 cells)"]
+        );
+        assert!(lines[2].starts_with("Done in "));
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,106 @@
+//! Layers packed eight cells to a byte, for files and for handing to other code.
+//!
+//! Cell `i` is bit `i % 8` of byte `i / 8`, counting bit 0 as the least
+//! significant, and a live cell is a set bit. So cell 0 is the lowest bit of
+//! byte 0, and cell 8 the lowest bit of byte 1. Any bits past the end of the
+//! layer in the last byte are written as zero, and ignored when reading.
+
+use crate::error::CaError;
+
+/// A layer packed into bytes, as described in the module docs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PackedLayer {
+    pub bytes: Vec<u8>,
+    /// The number of cells in the layer.
+    pub bit_len: usize,
+}
+
+/// The number of bytes needed to pack `bit_len` cells.
+pub fn packed_len(bit_len: usize) -> usize {
+    bit_len.div_ceil(8)
+}
+
+pub fn layer_to_packed(layer: &[bool]) -> PackedLayer {
+    let mut bytes = vec![0; packed_len(layer.len())];
+    for (i, &cell) in layer.iter().enumerate() {
+        bytes[i / 8] |= (cell as u8) << (i % 8);
+    }
+    PackedLayer {
+        bytes,
+        bit_len: layer.len(),
+    }
+}
+
+/// Unpacks a layer, which is an `InvalidDimensions` error if it has the wrong
+/// number of bytes for its length.
+pub fn layer_from_packed(packed: &PackedLayer) -> Result<Vec<bool>, CaError> {
+    if packed.bytes.len() != packed_len(packed.bit_len) {
+        return Err(CaError::InvalidDimensions(format!(
+            "{} cells need {} bytes, but there are {}",
+            packed.bit_len,
+            packed_len(packed.bit_len),
+            packed.bytes.len()
+        )));
+    }
+    Ok((0..packed.bit_len)
+        .map(|i| packed.bytes[i / 8] >> (i % 8) & 1 == 1)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds;
+
+    #[test]
+    fn bit_order() {
+        let packed = layer_to_packed(&seeds::from_str("#.......#..#").unwrap());
+        assert_eq!(packed.bytes, &[0b0000_0001, 0b0000_1001]);
+        assert_eq!(packed.bit_len, 12);
+        assert_eq!(layer_to_packed(&[]), PackedLayer::default());
+    }
+
+    #[test]
+    fn round_trips() {
+        for len in 0..=130 {
+            let layer = seeds::random(len, 0.5, len as u64);
+            let packed = layer_to_packed(&layer);
+            assert_eq!(packed.bytes.len(), packed_len(len));
+            assert_eq!(layer_from_packed(&packed).unwrap(), layer);
+        }
+    }
+
+    #[test]
+    fn trailing_bits() {
+        // Exported as zeros...
+        let packed = layer_to_packed(&[true; 3]);
+        assert_eq!(packed.bytes, &[0b0000_0111]);
+
+        // ...and ignored on import.
+        let noisy = PackedLayer {
+            bytes: vec![0xff],
+            bit_len: 3,
+        };
+        assert_eq!(layer_from_packed(&noisy).unwrap(), &[true; 3]);
+    }
+
+    #[test]
+    fn length_mismatch() {
+        for &(bit_len, bytes) in &[(9, 1), (8, 2), (0, 1)] {
+            let packed = PackedLayer {
+                bytes: vec![0; bytes],
+                bit_len,
+            };
+            let err = layer_from_packed(&packed).unwrap_err();
+            assert!(matches!(err, CaError::InvalidDimensions(_)), "{:?}", err);
+        }
+        let packed = PackedLayer {
+            bytes: vec![0],
+            bit_len: 9,
+        };
+        assert_eq!(
+            layer_from_packed(&packed).unwrap_err().to_string(),
+            "wrong size: 9 cells need 2 bytes, but there are 1"
+        );
+    }
+}
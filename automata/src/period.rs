@@ -0,0 +1,216 @@
+use crate::life::Grid;
+use crate::pool::LayerPool;
+use std::collections::VecDeque;
+
+/// A pattern which repeats every `period` generations, moved `offset` cells to
+/// the right (or left, if negative). Pure oscillators have an offset of 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Translation {
+    pub period: usize,
+    pub offset: isize,
+}
+
+/// The 2D version of `Translation`, where `offset` is `(right, down)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Translation2d {
+    pub period: usize,
+    pub offset: (isize, isize),
+}
+
+// Offsets to try, smallest first: 0, -1, 1, -2, 2, ...
+fn offsets(width: usize) -> impl Iterator<Item = isize> {
+    let width = width as isize;
+    (0..width).map(|i| if i % 2 == 0 { i / 2 } else { -(i + 1) / 2 })
+}
+
+// Whether `after` is `before` cyclically shifted right by `offset` cells.
+fn is_shifted(before: &[bool], after: &[bool], offset: isize) -> bool {
+    let width = before.len() as isize;
+    before.len() == after.len()
+        && (0..width).all(|i| after[i as usize] == before[(i - offset).rem_euclid(width) as usize])
+}
+
+/// Finds the smallest period (up to `max_period`), and then the smallest
+/// offset, with which the last of `layers` is a cyclic shift of an earlier
+/// layer. Layers should come from a fixed-width wrapping run.
+pub fn find_translating_period<L: AsRef<[bool]>>(
+    layers: &[L],
+    max_period: usize,
+) -> Option<Translation> {
+    let last = layers.last()?.as_ref();
+    for period in 1..=max_period.min(layers.len() - 1) {
+        let before = layers[layers.len() - 1 - period].as_ref();
+        if let Some(offset) = offsets(last.len()).find(|&d| is_shifted(before, last, d)) {
+            return Some(Translation { period, offset });
+        }
+    }
+    None
+}
+
+/// Watches the layers of a fixed-width wrapping run as they're generated,
+/// keeping just enough of them to spot when the run becomes periodic.
+pub struct TranslationDetector {
+    max_period: usize,
+    recent: VecDeque<Vec<bool>>,
+    pool: LayerPool,
+}
+
+impl TranslationDetector {
+    pub fn new(max_period: usize) -> TranslationDetector {
+        TranslationDetector {
+            max_period,
+            recent: VecDeque::with_capacity(max_period + 1),
+            pool: LayerPool::new(1),
+        }
+    }
+
+    /// Records the next layer, returning how it repeats an earlier one, if it does.
+    pub fn observe(&mut self, layer: &[bool]) -> Option<Translation> {
+        if self.recent.len() > self.max_period {
+            let oldest = self.recent.pop_front().unwrap();
+            self.pool.give_back(oldest);
+        }
+
+        let mut copy = self.pool.take(layer.len());
+        copy.extend_from_slice(layer);
+        self.recent.push_back(copy);
+
+        find_translating_period(self.recent.make_contiguous(), self.max_period)
+    }
+}
+
+// Whether `after` is `before` shifted by `offset` on a torus.
+fn is_shifted_2d(before: &Grid, after: &Grid, (dx, dy): (isize, isize)) -> bool {
+    let (w, h) = (before.width() as isize, before.height() as isize);
+    (after.width(), after.height()) == (before.width(), before.height())
+        && (0..h).all(|y| {
+            (0..w).all(|x| {
+                let (bx, by) = ((x - dx).rem_euclid(w), (y - dy).rem_euclid(h));
+                after.get(x as usize, y as usize) == before.get(bx as usize, by as usize)
+            })
+        })
+}
+
+/// The 2D version of `find_translating_period`, for grids with wrapping edges.
+/// Offsets are tried in order of the total distance moved.
+pub fn find_translating_period_2d(grids: &[Grid], max_period: usize) -> Option<Translation2d> {
+    let last = grids.last()?;
+    let mut candidates: Vec<(isize, isize)> = offsets(last.width())
+        .flat_map(|dx| offsets(last.height()).map(move |dy| (dx, dy)))
+        .collect();
+    candidates.sort_by_key(|&(dx, dy)| dx.abs() + dy.abs());
+
+    for period in 1..=max_period.min(grids.len() - 1) {
+        let before = &grids[grids.len() - 1 - period];
+        if let Some(&offset) = candidates.iter().find(|&&d| is_shifted_2d(before, last, d)) {
+            return Some(Translation2d { period, offset });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::{next_layer_wrapping, Boundary};
+    use crate::seeds;
+
+    fn ring_run(rule: u8, seed: Vec<bool>, generations: usize) -> Vec<Vec<bool>> {
+        std::iter::successors(Some(seed), |layer| Some(next_layer_wrapping(rule, layer)))
+            .take(generations)
+            .collect()
+    }
+
+    fn single_on_ring(width: usize) -> Vec<bool> {
+        let mut seed = vec![false; width];
+        seed[width / 2] = true;
+        seed
+    }
+
+    #[test]
+    fn rule_2_translates() {
+        let layers = ring_run(2, single_on_ring(11), 5);
+        assert_eq!(
+            find_translating_period(&layers, 4),
+            Some(Translation {
+                period: 1,
+                offset: -1
+            })
+        );
+    }
+
+    #[test]
+    fn oscillator() {
+        // Rule 51 inverts every cell each step.
+        let layers = ring_run(51, seeds::from_str("#..##.#").unwrap(), 4);
+        assert_eq!(
+            find_translating_period(&layers, 4),
+            Some(Translation {
+                period: 2,
+                offset: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rule_30_is_aperiodic() {
+        let layers = ring_run(30, seeds::random(64, 0.5, 3), 200);
+        assert_eq!(find_translating_period(&layers, 50), None);
+    }
+
+    #[test]
+    fn too_few_layers() {
+        assert_eq!(find_translating_period::<Vec<bool>>(&[], 4), None);
+        assert_eq!(find_translating_period(&[vec![true]], 4), None);
+    }
+
+    #[test]
+    fn streaming_detection() {
+        let mut detector = TranslationDetector::new(8);
+
+        // Rule 184 from this seed settles down into traffic moving right.
+        let layers = ring_run(184, seeds::from_str("##..#..##.....").unwrap(), 30);
+        let found: Vec<_> = layers.iter().map(|layer| detector.observe(layer)).collect();
+
+        assert_eq!(found[0], None);
+        assert_eq!(
+            found.last().unwrap(),
+            &Some(Translation {
+                period: 1,
+                offset: 1
+            })
+        );
+    }
+
+    #[test]
+    fn glider_translates() {
+        let mut grid = Grid::new(8, 8, Boundary::Wrapping);
+        grid.place(&seeds::glider(), 2, 2);
+
+        let grids: Vec<Grid> = std::iter::successors(Some(grid), |g| Some(g.step()))
+            .take(9)
+            .collect();
+        assert_eq!(
+            find_translating_period_2d(&grids, 8),
+            Some(Translation2d {
+                period: 4,
+                offset: (1, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn blinker_oscillates_2d() {
+        let mut grid = Grid::new(5, 5, Boundary::Wrapping);
+        grid.place(&seeds::blinker(), 1, 2);
+
+        let grids = vec![grid.clone(), grid.step(), grid.step().step()];
+        assert_eq!(
+            find_translating_period_2d(&grids, 2),
+            Some(Translation2d {
+                period: 2,
+                offset: (0, 0)
+            })
+        );
+    }
+}
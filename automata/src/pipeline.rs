@@ -0,0 +1,167 @@
+use std::panic;
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs `producer` and `consumer` on separate threads, passing everything the
+/// producer makes over to the consumer, in order. Returns the number of items
+/// consumed.
+///
+/// The producer is called until it returns `Ok(None)`. At most `buffer` items
+/// wait between the two at once, so a producer which gets ahead is held up
+/// until the consumer catches up. If either side returns an error, the other
+/// stops as soon as it next hands over or waits for an item, and the error is
+/// returned here. Panics on either side are re-raised here once both have
+/// stopped.
+pub fn run_pipelined<T, E, P, C>(
+    mut producer: P,
+    mut consumer: C,
+    buffer: usize,
+) -> Result<usize, E>
+where
+    T: Send,
+    E: Send,
+    P: FnMut() -> Result<Option<T>, E> + Send,
+    C: FnMut(T) -> Result<(), E>,
+{
+    let (tx, rx) = mpsc::sync_channel(buffer);
+
+    thread::scope(|scope| {
+        let producing = scope.spawn(move || -> Result<(), E> {
+            while let Some(item) = producer()? {
+                if tx.send(item).is_err() {
+                    // The consumer has hung up, so there's no one left to make
+                    // items for.
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        let mut consumed = 0;
+        let consuming = rx.iter().try_for_each(|item| {
+            consumed += 1;
+            consumer(item)
+        });
+        // Hang up, so that a producer still sending notices and stops.
+        drop(rx);
+
+        let produced = match producing.join() {
+            Ok(produced) => produced,
+            Err(payload) => panic::resume_unwind(payload),
+        };
+        consuming?;
+        produced?;
+        Ok(consumed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+    use crate::render::render_layer;
+    use crate::simulation::Simulation;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn matches_single_threaded() {
+        let mut sim = Simulation::new(30, vec![true]);
+        let mut lines = Vec::new();
+        let consumed = run_pipelined(
+            || -> Result<_, ()> {
+                if sim.generation() == 50 {
+                    return Ok(None);
+                }
+                let layer = sim.layer().to_vec();
+                sim.step();
+                Ok(Some(layer))
+            },
+            |layer| {
+                lines.push(render_layer(&layer));
+                Ok(())
+            },
+            4,
+        );
+
+        let expected: Vec<String> = iter_layers(30).take(50).map(|l| render_layer(&l)).collect();
+        assert_eq!(consumed, Ok(50));
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn consumer_error_stops_producer() {
+        let produced = AtomicUsize::new(0);
+        let result = run_pipelined(
+            || -> Result<_, String> { Ok(Some(produced.fetch_add(1, Ordering::SeqCst))) },
+            |item| match item {
+                5 => Err("consumer gave up".to_string()),
+                _ => Ok(()),
+            },
+            2,
+        );
+
+        assert_eq!(result, Err("consumer gave up".to_string()));
+        // Items 0 to 5, up to two more in the buffer, and one being sent.
+        assert!(produced.load(Ordering::SeqCst) <= 9);
+    }
+
+    #[test]
+    fn producer_error() {
+        let mut count = 0;
+        let result = run_pipelined(
+            || {
+                count += 1;
+                if count > 3 {
+                    Err("out of layers")
+                } else {
+                    Ok(Some(count))
+                }
+            },
+            |_| Ok(()),
+            1,
+        );
+        assert_eq!(result, Err("out of layers"));
+    }
+
+    #[test]
+    #[should_panic(expected = "producer broke")]
+    fn producer_panic() {
+        let _ = run_pipelined(
+            || -> Result<Option<u8>, ()> { panic!("producer broke") },
+            |_| Ok(()),
+            1,
+        );
+    }
+
+    #[test]
+    fn buffer_limits_items_in_flight() {
+        // Items which have been made but not yet consumed.
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let mut remaining = 30;
+        run_pipelined(
+            || -> Result<_, ()> {
+                if remaining == 0 {
+                    return Ok(None);
+                }
+                remaining -= 1;
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                Ok(Some(vec![true; 1000]))
+            },
+            |_| {
+                thread::sleep(Duration::from_millis(1));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            },
+            3,
+        )
+        .unwrap();
+
+        // The buffer, plus one being sent, plus one being consumed.
+        assert!(peak.load(Ordering::SeqCst) <= 5);
+        assert!(peak.load(Ordering::SeqCst) >= 3);
+    }
+}
@@ -0,0 +1,101 @@
+/// Keeps hold of layers which are no longer needed, so that their memory can
+/// be reused for new ones rather than allocated afresh every generation.
+#[derive(Debug, Clone, Default)]
+pub struct LayerPool {
+    free: Vec<Vec<bool>>,
+    capacity: usize,
+}
+
+/// How many layers a pool keeps by default.
+pub const DEFAULT_POOL_CAPACITY: usize = 4;
+
+impl LayerPool {
+    /// Makes a pool which holds on to at most `capacity` layers at a time.
+    pub fn new(capacity: usize) -> LayerPool {
+        LayerPool {
+            free: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The number of layers waiting to be reused.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    /// Takes an empty layer with room for at least `min_cells` cells.
+    ///
+    /// When a layer has to grow, it grows to the next power of two, so that a
+    /// run whose layers get wider only allocates every so often.
+    pub fn take(&mut self, min_cells: usize) -> Vec<bool> {
+        let found = self
+            .free
+            .iter()
+            .position(|layer| layer.capacity() >= min_cells);
+        let mut layer = match found {
+            Some(i) => self.free.swap_remove(i),
+            None => self.free.pop().unwrap_or_default(),
+        };
+
+        layer.clear();
+        if layer.capacity() < min_cells {
+            layer.reserve_exact(min_cells.next_power_of_two());
+        }
+        layer
+    }
+
+    /// Hands back a layer for reuse. It's dropped if the pool is already full.
+    pub fn give_back(&mut self, layer: Vec<bool>) {
+        if self.free.len() < self.capacity {
+            self.free.push(layer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_layers() {
+        let mut pool = LayerPool::new(2);
+        let mut layer = pool.take(10);
+        assert!(layer.capacity() >= 10);
+        layer.extend_from_slice(&[true; 10]);
+        let ptr = layer.as_ptr();
+
+        pool.give_back(layer);
+        assert_eq!(pool.len(), 1);
+
+        let again = pool.take(5);
+        assert!(again.is_empty());
+        assert_eq!(again.as_ptr(), ptr);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn prefers_big_enough_layers() {
+        let mut pool = LayerPool::new(2);
+        pool.give_back(Vec::with_capacity(100));
+        pool.give_back(Vec::with_capacity(1));
+
+        assert!(pool.take(50).capacity() >= 100);
+        assert!(pool.take(50).capacity() >= 50);
+    }
+
+    #[test]
+    fn drops_when_full() {
+        let mut pool = LayerPool::new(1);
+        pool.give_back(vec![true]);
+        pool.give_back(vec![false]);
+        assert_eq!(pool.len(), 1);
+
+        let mut empty = LayerPool::new(0);
+        empty.give_back(vec![true]);
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use crate::ca::{test_rule, Boundary};
+
+// Preimages are found by walking the rule's de Bruijn graph. Each node is a pair
+// of adjacent cells (a, b), packed as `a << 1 | b`. Following the edge labelled
+// `c` moves to the node (b, c), and the edge's output is the rule applied to
+// (a, b, c). A preimage of a layer of width `n` is then a walk of `n` edges whose
+// outputs spell out the layer, where node `i` of the walk is (x[i-1], x[i]).
+
+// The node reached from `node` by appending the cell `c`.
+fn successor(node: u8, c: bool) -> u8 {
+    ((node & 0b01) << 1) | c as u8
+}
+
+// The output of the edge leaving `node` labelled `c`.
+fn edge_output(rule: u8, node: u8, c: bool) -> bool {
+    test_rule(rule, (node & 0b10 != 0, node & 0b01 != 0, c))
+}
+
+// One depth-first search over the de Bruijn graph, starting at a single node.
+//
+// `viable[i][node]` records whether there's any way to finish a walk which is at
+// `node` after `i` steps. Consulting it before descending means the search never
+// enters a dead branch, so every descent ends in a preimage.
+struct Search {
+    start: u8,
+    viable: Vec<[bool; 4]>,
+}
+
+impl Search {
+    fn new(rule: u8, layer: &[bool], start: u8, accept: impl Fn(u8) -> bool) -> Search {
+        let n = layer.len();
+        let mut viable = vec![[false; 4]; n + 1];
+        for node in 0..4 {
+            viable[n][node as usize] = accept(node);
+        }
+
+        // Fill in the table back to front, each step depending on the one after.
+        for i in (0..n).rev() {
+            for node in 0..4 {
+                viable[i][node as usize] = [false, true].iter().any(|&c| {
+                    edge_output(rule, node, c) == layer[i]
+                        && viable[i + 1][successor(node, c) as usize]
+                });
+            }
+        }
+
+        Search { start, viable }
+    }
+
+    fn has_preimage(&self) -> bool {
+        self.viable[0][self.start as usize]
+    }
+}
+
+/// Iterates through the preimages of a layer, in lexicographic order within
+/// each starting node. Preimages are produced lazily, so it's safe to `take()` a
+/// handful from a layer with an enormous number of them.
+pub struct Preimages {
+    rule: u8,
+    layer: Vec<bool>,
+    searches: Vec<Search>,
+
+    // The index into `searches` of the search in progress.
+    search: usize,
+
+    // The nodes on the current walk through the de Bruijn graph.
+    path: Vec<u8>,
+}
+
+impl Preimages {
+    fn new(rule: u8, layer: &[bool], boundary: Boundary) -> Preimages {
+        let mut searches: Vec<Search> = match boundary {
+            // With dead boundaries, the walk starts at (0, x[0]) and ends at (x[n-1], 0).
+            Boundary::Dead => (0..2)
+                .map(|start| Search::new(rule, layer, start, |node| node & 0b01 == 0))
+                .collect(),
+
+            // On a ring, the walk starts at (x[n-1], x[0]) and has to come back there.
+            Boundary::Wrapping => (0..4)
+                .map(|start| Search::new(rule, layer, start, |node| node == start))
+                .collect(),
+        };
+
+        // An empty layer has exactly one (empty) preimage, which every search would find.
+        if layer.is_empty() {
+            searches.truncate(1);
+        }
+
+        Preimages {
+            rule,
+            layer: layer.to_vec(),
+            searches,
+            search: 0,
+            path: Vec::new(),
+        }
+    }
+
+    // Extends the current path to full length, always taking the first viable edge.
+    fn descend(&mut self) {
+        while self.path.len() <= self.layer.len() {
+            let i = self.path.len() - 1;
+            let node = self.path[i];
+
+            // The node is viable, so if the `false` edge isn't, the `true` one must be.
+            let c = !self.edge_viable(node, false, i);
+            self.path.push(successor(node, c));
+        }
+    }
+
+    // Moves the current path on to the next one, returning false if there isn't one.
+    fn advance(&mut self) -> bool {
+        while self.path.len() > 1 {
+            let last = self.path.pop().unwrap();
+            let i = self.path.len() - 1;
+
+            // If we took the `false` edge here last time, try the `true` one.
+            if last & 0b01 == 0 && self.edge_viable(self.path[i], true, i) {
+                self.path.push(successor(self.path[i], true));
+                self.descend();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn edge_viable(&self, node: u8, c: bool, i: usize) -> bool {
+        let viable = &self.searches[self.search].viable;
+        edge_output(self.rule, node, c) == self.layer[i]
+            && viable[i + 1][successor(node, c) as usize]
+    }
+}
+
+impl Iterator for Preimages {
+    type Item = Vec<bool>;
+
+    fn next(&mut self) -> Option<Vec<bool>> {
+        while self.search < self.searches.len() {
+            let found = if self.path.is_empty() {
+                let search = &self.searches[self.search];
+                if search.has_preimage() {
+                    self.path.push(search.start);
+                    self.descend();
+                    true
+                } else {
+                    false
+                }
+            } else {
+                self.advance()
+            };
+
+            if found {
+                // Node `i` of the path is (x[i-1], x[i]).
+                let cells = self.path[..self.layer.len()]
+                    .iter()
+                    .map(|node| node & 0b01 != 0)
+                    .collect();
+                return Some(cells);
+            }
+
+            self.path.clear();
+            self.search += 1;
+        }
+
+        None
+    }
+}
+
+/// Lazily enumerates every fixed-width layer which steps to `layer` under `rule`.
+pub fn iter_preimages(rule: u8, layer: &[bool], boundary: Boundary) -> Preimages {
+    Preimages::new(rule, layer, boundary)
+}
+
+/// Finds every fixed-width layer which steps to `layer` under `rule`. Some
+/// layers have exponentially many preimages---use `iter_preimages` to cap them.
+pub fn preimages(rule: u8, layer: &[bool], boundary: Boundary) -> Vec<Vec<bool>> {
+    iter_preimages(rule, layer, boundary).collect()
+}
+
+/// Determines whether `layer` is a Garden of Eden under `rule`---that is,
+/// whether no layer at all steps to it.
+pub fn is_garden_of_eden(rule: u8, layer: &[bool], boundary: Boundary) -> bool {
+    !Preimages::new(rule, layer, boundary)
+        .searches
+        .iter()
+        .any(Search::has_preimage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::next_layer_fixed;
+
+    // All the layers of the given width, where layer `n` has the bits of `n`.
+    fn all_layers(width: usize) -> impl Iterator<Item = Vec<bool>> {
+        (0..1usize << width).map(move |n| (0..width).map(|i| n & (1 << i) != 0).collect())
+    }
+
+    fn layer_index(layer: &[bool]) -> usize {
+        layer
+            .iter()
+            .rev()
+            .fold(0, |n, &cell| n << 1 | cell as usize)
+    }
+
+    #[test]
+    fn preimages_step_to_layer() {
+        let layer = vec![
+            true, true, false, true, false, false, true, true, true, false,
+        ];
+        for &rule in &[30, 90, 110, 150, 184] {
+            for &boundary in &[Boundary::Wrapping, Boundary::Dead] {
+                for pre in preimages(rule, &layer, boundary) {
+                    assert_eq!(next_layer_fixed(rule, &pre, boundary), layer);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn preimages_match_brute_force() {
+        for &rule in &[0, 30, 54, 90, 110, 232] {
+            for &boundary in &[Boundary::Wrapping, Boundary::Dead] {
+                let mut counts = vec![0; 1 << 6];
+                for layer in all_layers(6) {
+                    counts[layer_index(&next_layer_fixed(rule, &layer, boundary))] += 1;
+                }
+
+                for (i, layer) in all_layers(6).enumerate() {
+                    assert_eq!(preimages(rule, &layer, boundary).len(), counts[i]);
+                    assert_eq!(is_garden_of_eden(rule, &layer, boundary), counts[i] == 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn surjective_rules_have_no_orphans() {
+        // Rule 90 is a bijection on even-width layers with dead boundaries.
+        for layer in all_layers(8) {
+            assert_eq!(preimages(90, &layer, Boundary::Dead).len(), 1);
+        }
+
+        // Rule 150 is a bijection on rings whose width isn't a multiple of 3.
+        for layer in all_layers(7) {
+            assert_eq!(preimages(150, &layer, Boundary::Wrapping).len(), 1);
+        }
+    }
+
+    #[test]
+    fn garden_of_eden_detected() {
+        // Under majority voting, the isolated live cell forces its preimage to
+        // alternate around it, which leaves no way to make the live pair after it.
+        let layer = vec![false, false, true, false, false, true, true];
+        assert!(is_garden_of_eden(232, &layer, Boundary::Wrapping));
+        assert!(preimages(232, &layer, Boundary::Wrapping).is_empty());
+    }
+
+    #[test]
+    fn empty_layer() {
+        assert_eq!(
+            preimages(30, &[], Boundary::Wrapping),
+            vec![Vec::<bool>::new()]
+        );
+        assert_eq!(preimages(30, &[], Boundary::Dead), vec![Vec::<bool>::new()]);
+    }
+
+    #[test]
+    fn preimages_stream() {
+        // Rule 204 is the identity, so this is the only preimage of a blank layer.
+        let blank = vec![false; 1000];
+        assert_eq!(preimages(204, &blank, Boundary::Dead), vec![blank.clone()]);
+
+        // Rule 0 sends everything to a blank layer, so there are 2^1000 preimages.
+        let first: Vec<_> = iter_preimages(0, &blank, Boundary::Wrapping)
+            .take(3)
+            .collect();
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|pre| pre.len() == 1000));
+    }
+}
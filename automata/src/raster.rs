@@ -0,0 +1,121 @@
+use crate::renderer::{RenderCommon, Renderer};
+use std::io::{self, Write};
+
+/// An RGB color.
+pub type Rgb = [u8; 3];
+
+/// Writes an 8-bit RGB PNG of the given size. `pixels` holds the image row by
+/// row, top to bottom.
+pub fn write_png<W: Write>(w: W, width: usize, height: usize, pixels: &[Rgb]) -> io::Result<()> {
+    assert_eq!(
+        pixels.len(),
+        width * height,
+        "wrong number of pixels for image size"
+    );
+
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = pixels.iter().flatten().copied().collect();
+    let mut writer = encoder.write_header().map_err(into_io_error)?;
+    writer.write_image_data(&data).map_err(into_io_error)
+}
+
+fn into_io_error(err: png::EncodingError) -> io::Error {
+    match err {
+        png::EncodingError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidInput, err),
+    }
+}
+
+/// Mixes the colors `from` and `to`, where `t` runs from 0 (all `from`) to 1 (all `to`).
+pub fn lerp(from: Rgb, to: Rgb, t: f64) -> Rgb {
+    let t = t.clamp(0.0, 1.0);
+    let mut out = [0; 3];
+    for i in 0..3 {
+        out[i] = (from[i] as f64 + (to[i] as f64 - from[i] as f64) * t).round() as u8;
+    }
+    out
+}
+
+/// Writes the layers of a growing run as a black-on-white PNG, one row per
+/// layer, with each layer centered in the width of the widest.
+pub fn write_layers_png<W: Write>(mut w: W, layers: &[Vec<bool>]) -> io::Result<()> {
+    PngRenderer::default().render(&mut layers.iter().cloned(), &mut w)
+}
+
+/// Writes layers as a PNG, with each cell a square `cell_size` pixels across
+/// in one of `colors`.
+#[derive(Debug, Clone, Default)]
+pub struct PngRenderer {
+    pub common: RenderCommon,
+}
+
+impl Renderer for PngRenderer {
+    fn render(
+        &self,
+        layers: &mut dyn Iterator<Item = Vec<bool>>,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let rows = self.common.pad_layers(layers);
+        let size = self.common.cell_size;
+        let width = rows.first().map_or(0, Vec::len) * size;
+        let (live, dead) = self.common.colors;
+
+        let mut pixels = Vec::with_capacity(width * rows.len() * size);
+        for row in &rows {
+            let line: Vec<Rgb> = row
+                .iter()
+                .flat_map(|&cell| std::iter::repeat_n(if cell { live } else { dead }, size))
+                .collect();
+            for _ in 0..size {
+                pixels.extend_from_slice(&line);
+            }
+        }
+        write_png(w, width, rows.len() * size, &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIVE: Rgb = [0, 0, 0];
+    const DEAD: Rgb = [255, 255, 255];
+
+    #[test]
+    fn png_header() {
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 1, &[[255, 0, 0], [0, 0, 255]]).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoder = png::Decoder::new(&out[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(&buf[..6], &[255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn layers_png() {
+        let mut out = Vec::new();
+        write_layers_png(&mut out, &[vec![true], vec![true, false, true]]).unwrap();
+
+        let decoder = png::Decoder::new(&out[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (3, 2));
+        let pixels: Vec<Rgb> = buf.chunks(3).map(|p| [p[0], p[1], p[2]]).collect();
+        assert_eq!(pixels, &[DEAD, LIVE, DEAD, LIVE, DEAD, LIVE]);
+    }
+
+    #[test]
+    fn lerp_ends() {
+        assert_eq!(lerp([0, 0, 0], [200, 100, 50], 0.0), [0, 0, 0]);
+        assert_eq!(lerp([0, 0, 0], [200, 100, 50], 0.5), [100, 50, 25]);
+        assert_eq!(lerp([0, 0, 0], [200, 100, 50], 2.0), [200, 100, 50]);
+    }
+}
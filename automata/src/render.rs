@@ -0,0 +1,192 @@
+use crate::ca::take_layers_from;
+use crate::renderer::{RenderCommon, Renderer};
+use std::io::{self, Write};
+
+/// The characters live and dead cells are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellStyle {
+    pub live: char,
+    pub dead: char,
+}
+
+impl Default for CellStyle {
+    fn default() -> CellStyle {
+        CellStyle {
+            live: '#',
+            dead: '.',
+        }
+    }
+}
+
+/// Renders a layer as text, with `#` for live cells and `.` for dead ones.
+pub fn render_layer(layer: &[bool]) -> String {
+    render_layer_with(layer, CellStyle::default())
+}
+
+/// Like `render_layer`, but drawing cells with `style`.
+pub fn render_layer_with(layer: &[bool], style: CellStyle) -> String {
+    layer
+        .iter()
+        .map(|&cell| if cell { style.live } else { style.dead })
+        .collect()
+}
+
+/// Writes the first `generations` layers of `rule` as text, one per line. Each
+/// layer is centered under the first by padding it out with dead cells.
+pub fn render_rule<W: Write>(w: W, rule: u8, generations: usize) -> io::Result<()> {
+    render_rule_fitted(w, rule, generations, usize::MAX)
+}
+
+/// Which part of each line of text gets shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewport {
+    /// The lines fit, so all of them is shown.
+    Full,
+    /// Only the middle `width` cells are shown, with an ellipsis either side.
+    Centered { width: usize },
+}
+
+/// Decides how to show lines `layer_width` cells wide in `term_width` columns.
+pub fn choose_viewport(layer_width: usize, term_width: usize) -> Viewport {
+    if layer_width <= term_width {
+        Viewport::Full
+    } else {
+        // Leave room for the ellipses.
+        Viewport::Centered {
+            width: term_width.saturating_sub(2),
+        }
+    }
+}
+
+/// Like `render_rule`, but keeps every line within `term_width` columns by
+/// clipping both sides once the triangle gets too wide.
+pub fn render_rule_fitted<W: Write>(
+    w: W,
+    rule: u8,
+    generations: usize,
+    term_width: usize,
+) -> io::Result<()> {
+    render_seed_fitted(w, rule, &[true], generations, term_width)
+}
+
+/// Like `render_rule_fitted`, but starting from `seed` rather than a single cell.
+pub fn render_seed_fitted<W: Write>(
+    mut w: W,
+    rule: u8,
+    seed: &[bool],
+    generations: usize,
+    term_width: usize,
+) -> io::Result<()> {
+    let mut layers = take_layers_from(rule, seed.to_vec(), generations);
+    let renderer = TextRenderer {
+        common: RenderCommon {
+            max_width: Some(term_width),
+            layer_width: Some(layers.last_width()),
+            ..RenderCommon::default()
+        },
+    };
+    renderer.render(&mut layers, &mut w)
+}
+
+/// Writes layers as lines of text, clipping them to fit in `max_width`
+/// columns as `choose_viewport` decides.
+#[derive(Debug, Clone, Default)]
+pub struct TextRenderer {
+    pub common: RenderCommon,
+}
+
+impl Renderer for TextRenderer {
+    fn render(
+        &self,
+        layers: &mut dyn Iterator<Item = Vec<bool>>,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (width, rows) = self.common.padded_layers(layers);
+        let viewport = choose_viewport(width, self.common.max_width.unwrap_or(usize::MAX));
+
+        let (live, dead) = self.common.glyphs;
+        let text = |cells: &[bool]| -> String {
+            cells
+                .iter()
+                .map(|&cell| if cell { live } else { dead })
+                .collect()
+        };
+        for row in rows {
+            match viewport {
+                Viewport::Full => writeln!(w, "{}", text(&row))?,
+                Viewport::Centered { width: shown } => {
+                    let start = (width - shown) / 2;
+                    writeln!(w, "…{}…", text(&row[start..start + shown]))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_text() {
+        assert_eq!(render_layer(&[true, false, false, true]), "#..#");
+        assert_eq!(render_layer(&[]), "");
+        let blocks = CellStyle {
+            live: '█',
+            dead: ' ',
+        };
+        assert_eq!(render_layer_with(&[true, false, true], blocks), "█ █");
+    }
+
+    #[test]
+    fn rule_90_triangle() {
+        let mut out = Vec::new();
+        render_rule(&mut out, 90, 4).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "...#...\n..#.#..\n.#...#.\n#.#.#.#\n"
+        );
+    }
+
+    #[test]
+    fn viewport_choice() {
+        assert_eq!(choose_viewport(79, 80), Viewport::Full);
+        assert_eq!(choose_viewport(80, 80), Viewport::Full);
+        assert_eq!(choose_viewport(81, 80), Viewport::Centered { width: 78 });
+        assert_eq!(choose_viewport(399, 80), Viewport::Centered { width: 78 });
+        assert_eq!(choose_viewport(5, 1), Viewport::Centered { width: 0 });
+        assert_eq!(choose_viewport(0, 0), Viewport::Full);
+    }
+
+    #[test]
+    fn fitted_triangle() {
+        let mut out = Vec::new();
+        render_rule_fitted(&mut out, 90, 4, 7).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "...#...\n..#.#..\n.#...#.\n#.#.#.#\n"
+        );
+
+        let mut out = Vec::new();
+        render_rule_fitted(&mut out, 90, 4, 5).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "….#.…\n…#.#…\n…...…\n…#.#…\n"
+        );
+    }
+
+    #[test]
+    fn seeded_triangle() {
+        let mut out = Vec::new();
+        render_seed_fitted(&mut out, 90, &[true, true], 3, usize::MAX).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "..##..\n.####.\n##..##\n");
+    }
+
+    #[test]
+    fn no_generations() {
+        let mut out = Vec::new();
+        render_rule(&mut out, 30, 0).unwrap();
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! A common interface to the output formats, so that a run can be written out
+//! without caring which format it's going to.
+//!
+//! Each renderer takes the layers of a run, top to bottom. Narrower layers are
+//! padded out to the width of the widest with dead cells, as set by the
+//! `alignment` of the shared `RenderCommon`. If that width is given up front
+//! as `layer_width`, text and `.npy` output is written a layer at a time;
+//! otherwise the whole run is collected first to find it.
+
+use crate::npy::NpyRenderer;
+use crate::render::TextRenderer;
+use std::io::{self, Write};
+
+/// Something which can write out the layers of a run.
+pub trait Renderer {
+    fn render(
+        &self,
+        layers: &mut dyn Iterator<Item = Vec<bool>>,
+        w: &mut dyn Write,
+    ) -> io::Result<()>;
+}
+
+/// Where narrower layers go within the width of the widest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// Padded equally either side, which lines up the cells of a growing run.
+    Center,
+    /// Padded on the right.
+    Left,
+}
+
+/// Settings shared by every renderer. Each one ignores any that don't apply
+/// to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderCommon {
+    /// The characters for live and dead cells in text.
+    pub glyphs: (char, char),
+    /// The colors of live and dead cells in images.
+    pub colors: ([u8; 3], [u8; 3]),
+    /// How many pixels across each cell is in images.
+    pub cell_size: usize,
+    /// The most columns a line of text can take up, beyond which lines are
+    /// clipped as described by `render::Viewport`, or `None` for no limit.
+    pub max_width: Option<usize>,
+    pub alignment: Alignment,
+    /// The width of the widest layer, if it's known before rendering, such as
+    /// from `TakeLayers::last_width`. No layer may be wider.
+    pub layer_width: Option<usize>,
+}
+
+impl Default for RenderCommon {
+    fn default() -> RenderCommon {
+        RenderCommon {
+            glyphs: ('#', '.'),
+            colors: ([0, 0, 0], [255, 255, 255]),
+            cell_size: 1,
+            max_width: None,
+            alignment: Alignment::Center,
+            layer_width: None,
+        }
+    }
+}
+
+impl RenderCommon {
+    /// Collects `layers`, padding each out to the width of the widest.
+    pub fn pad_layers(&self, layers: &mut dyn Iterator<Item = Vec<bool>>) -> Vec<Vec<bool>> {
+        let mut rows: Vec<Vec<bool>> = layers.collect();
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut rows {
+            self.pad_layer(row, width);
+        }
+        rows
+    }
+
+    /// Pads each of `layers` out to the width of the widest, returning that
+    /// width along with them. With `layer_width` set, they're padded as
+    /// they're produced; without it, they're all collected first.
+    pub fn padded_layers<'a>(
+        &'a self,
+        layers: &'a mut dyn Iterator<Item = Vec<bool>>,
+    ) -> (usize, Box<dyn Iterator<Item = Vec<bool>> + 'a>) {
+        match self.layer_width {
+            Some(width) => {
+                let rows = layers.map(move |mut row| {
+                    self.pad_layer(&mut row, width);
+                    row
+                });
+                (width, Box::new(rows))
+            }
+            None => {
+                let rows = self.pad_layers(layers);
+                (rows.first().map_or(0, Vec::len), Box::new(rows.into_iter()))
+            }
+        }
+    }
+
+    /// Pads `row` out to `width` cells, as set by `alignment`.
+    pub fn pad_layer(&self, row: &mut Vec<bool>, width: usize) {
+        assert!(
+            row.len() <= width,
+            "a layer of {} cells is wider than {}",
+            row.len(),
+            width
+        );
+        let padding = match self.alignment {
+            Alignment::Center => (width - row.len()) / 2,
+            Alignment::Left => 0,
+        };
+        row.splice(0..0, std::iter::repeat_n(false, padding));
+        row.resize(width, false);
+    }
+}
+
+/// The output formats there are renderers for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Npy,
+    #[cfg(feature = "image")]
+    Png,
+}
+
+impl Format {
+    /// Every format available in this build.
+    pub fn all() -> &'static [Format] {
+        #[cfg(feature = "image")]
+        return &[Format::Text, Format::Npy, Format::Png];
+        #[cfg(not(feature = "image"))]
+        return &[Format::Text, Format::Npy];
+    }
+
+    /// The name of the format, as given to `--format`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Npy => "npy",
+            #[cfg(feature = "image")]
+            Format::Png => "png",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        Format::all()
+            .iter()
+            .copied()
+            .find(|format| format.name() == name)
+    }
+
+    pub fn renderer(self, common: RenderCommon) -> Box<dyn Renderer> {
+        match self {
+            Format::Text => Box::new(TextRenderer { common }),
+            Format::Npy => Box::new(NpyRenderer { common }),
+            #[cfg(feature = "image")]
+            Format::Png => Box::new(crate::raster::PngRenderer { common }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::take_layers;
+
+    fn render(format: Format, common: RenderCommon) -> Vec<u8> {
+        let mut out = Vec::new();
+        format
+            .renderer(common)
+            .render(&mut take_layers(30, 16), &mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn every_format() {
+        for &format in Format::all() {
+            assert_eq!(Format::from_name(format.name()), Some(format));
+            let out = render(format, RenderCommon::default());
+            assert!(!out.is_empty(), "{} is empty", format.name());
+
+            // The golden fixtures are written by the standalone functions,
+            // which should be no different. There's no fixture for plain PNGs.
+            match format {
+                Format::Text => {
+                    assert_eq!(
+                        &out[..],
+                        &include_bytes!("../tests/golden/rule_30_16.txt")[..]
+                    );
+                    let text = String::from_utf8(out).unwrap();
+                    assert_eq!(text.lines().count(), 16);
+                    assert!(text.lines().all(|line| line.len() == 31));
+                }
+                Format::Npy => {
+                    assert_eq!(
+                        &out[..],
+                        &include_bytes!("../tests/golden/rule_30_16.npy")[..]
+                    );
+                    assert!(String::from_utf8_lossy(&out).contains("'shape': (16, 31)"));
+                }
+                #[cfg(feature = "image")]
+                Format::Png => {
+                    let decoder = ::png::Decoder::new(&out[..]);
+                    let mut reader = decoder.read_info().unwrap();
+                    let mut buf = vec![0; reader.output_buffer_size()];
+                    let info = reader.next_frame(&mut buf).unwrap();
+                    assert_eq!((info.width, info.height), (31, 16));
+                    // The single live cell at the top.
+                    assert_eq!(&buf[15 * 3..16 * 3], &[0, 0, 0]);
+                    assert_eq!(&buf[..3], &[255, 255, 255]);
+                }
+            }
+        }
+        assert_eq!(Format::from_name("gif"), None);
+    }
+
+    #[test]
+    fn shared_settings() {
+        let common = RenderCommon {
+            glyphs: ('o', ' '),
+            max_width: Some(12),
+            ..RenderCommon::default()
+        };
+        let text = String::from_utf8(render(Format::Text, common)).unwrap();
+        assert_eq!(text.lines().next(), Some("…     o    …"));
+        assert!(text.lines().all(|line| line.chars().count() == 12));
+
+        let common = RenderCommon {
+            alignment: Alignment::Left,
+            ..RenderCommon::default()
+        };
+        let text = String::from_utf8(render(Format::Text, common)).unwrap();
+        assert_eq!(text.lines().nth(1), Some("###............................"));
+    }
+
+    #[test]
+    fn known_layer_width() {
+        for &format in Format::all() {
+            let streamed = RenderCommon {
+                layer_width: Some(take_layers(30, 16).last_width()),
+                ..RenderCommon::default()
+            };
+            assert_eq!(
+                render(format, streamed),
+                render(format, RenderCommon::default()),
+                "{}",
+                format.name()
+            );
+        }
+    }
+
+    #[test]
+    fn streams_text() {
+        // A writer which checks that each line is written before the layer
+        // after it is produced.
+        struct Lines<'a>(&'a std::cell::Cell<usize>);
+        impl Write for Lines<'_> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let newlines = buf.iter().filter(|&&b| b == b'\n').count();
+                self.0.set(self.0.get() + newlines);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let written = std::cell::Cell::new(0);
+        let mut produced = 0;
+        let mut layers = take_layers(30, 16).inspect(|_| {
+            assert_eq!(written.get(), produced);
+            produced += 1;
+        });
+        let common = RenderCommon {
+            layer_width: Some(31),
+            ..RenderCommon::default()
+        };
+        Format::Text
+            .renderer(common)
+            .render(&mut layers, &mut Lines(&written))
+            .unwrap();
+        assert_eq!(written.get(), 16);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn png_cell_size() {
+        let common = RenderCommon {
+            cell_size: 3,
+            colors: ([255, 0, 0], [0, 0, 0]),
+            ..RenderCommon::default()
+        };
+        let out = render(Format::Png, common);
+        let decoder = ::png::Decoder::new(&out[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (93, 48));
+        // The top cell covers columns 45 to 47 of the first three rows.
+        let row = 93 * 3;
+        for y in 0..3 {
+            assert_eq!(&buf[y * row + 44 * 3..y * row + 45 * 3], &[0, 0, 0]);
+            assert_eq!(
+                &buf[y * row + 45 * 3..y * row + 48 * 3],
+                &[255, 0, 0, 255, 0, 0, 255, 0, 0]
+            );
+        }
+    }
+}
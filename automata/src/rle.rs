@@ -0,0 +1,183 @@
+use std::mem;
+
+/// A layer stored as runs of identical cells, which takes far less memory than
+/// a `Vec<bool>` for layers made of long runs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RleLayer {
+    /// Each run's value and length. No run is empty, and neighbouring runs only
+    /// share a value when the first is `u32::MAX` long.
+    runs: Vec<(bool, u32)>,
+    len: usize,
+}
+
+impl RleLayer {
+    pub fn from_bits(layer: &[bool]) -> RleLayer {
+        let mut runs: Vec<(bool, u32)> = Vec::new();
+        for &cell in layer {
+            match runs.last_mut() {
+                Some((value, run)) if *value == cell && *run < u32::MAX => *run += 1,
+                _ => runs.push((cell, 1)),
+            }
+        }
+        runs.shrink_to_fit();
+
+        RleLayer {
+            runs,
+            len: layer.len(),
+        }
+    }
+
+    pub fn to_bits(&self) -> Vec<bool> {
+        let mut layer = Vec::with_capacity(self.len);
+        for &(value, run) in &self.runs {
+            layer.extend(std::iter::repeat_n(value, run as usize));
+        }
+        layer
+    }
+
+    /// The number of cells.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of runs the layer is stored as.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// The number of live cells.
+    pub fn population(&self) -> usize {
+        self.runs
+            .iter()
+            .filter(|&&(value, _)| value)
+            .map(|&(_, run)| run as usize)
+            .sum()
+    }
+
+    /// Gets the cell at `index`, which has to be less than `len()`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len, "index {} out of range", index);
+        let mut start = 0;
+        for &(value, run) in &self.runs {
+            start += run as usize;
+            if index < start {
+                return value;
+            }
+        }
+        unreachable!("runs cover the whole layer")
+    }
+
+    /// Roughly how much memory the layer takes up, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<Self>() + self.runs.capacity() * mem::size_of::<(bool, u32)>()
+    }
+}
+
+/// A layer stored either as plain cells or as runs, whichever is smaller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredLayer {
+    Bits(Vec<bool>),
+    Rle(RleLayer),
+}
+
+impl StoredLayer {
+    /// Stores `layer` in whichever form takes less memory.
+    pub fn compact(layer: Vec<bool>) -> StoredLayer {
+        let rle = RleLayer::from_bits(&layer);
+        if rle.memory_bytes() < bits_memory_bytes(&layer) {
+            StoredLayer::Rle(rle)
+        } else {
+            StoredLayer::Bits(layer)
+        }
+    }
+
+    pub fn to_bits(&self) -> Vec<bool> {
+        match self {
+            StoredLayer::Bits(layer) => layer.clone(),
+            StoredLayer::Rle(rle) => rle.to_bits(),
+        }
+    }
+
+    /// Roughly how much memory the layer takes up, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        match self {
+            StoredLayer::Bits(layer) => bits_memory_bytes(layer),
+            StoredLayer::Rle(rle) => rle.memory_bytes(),
+        }
+    }
+}
+
+fn bits_memory_bytes(layer: &Vec<bool>) -> usize {
+    mem::size_of::<Vec<bool>>() + layer.capacity()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::{iter_layers, next_layer_wrapping};
+    use crate::seeds;
+
+    fn population(layer: &[bool]) -> usize {
+        layer.iter().filter(|&&cell| cell).count()
+    }
+
+    fn check_agrees(layer: &[bool]) {
+        let rle = RleLayer::from_bits(layer);
+        assert_eq!(rle.to_bits(), layer);
+        assert_eq!(rle.len(), layer.len());
+        assert_eq!(rle.population(), population(layer));
+        for (i, &cell) in layer.iter().enumerate() {
+            assert_eq!(rle.get(i), cell);
+        }
+        assert_eq!(StoredLayer::compact(layer.to_vec()).to_bits(), layer);
+    }
+
+    #[test]
+    fn random_layers() {
+        for seed in 0..50 {
+            let width = seed as usize * 7;
+            check_agrees(&seeds::random(width, 0.5, seed));
+            check_agrees(&seeds::random(width, 0.05, seed));
+        }
+    }
+
+    #[test]
+    fn structured_layers() {
+        for rule in &[30, 90, 110, 184, 0, 255] {
+            for layer in iter_layers(*rule).take(40) {
+                check_agrees(&layer);
+            }
+        }
+
+        // Fixed-width traffic, after it's had time to settle.
+        let mut traffic = seeds::random(200, 0.3, 1);
+        for _ in 0..300 {
+            traffic = next_layer_wrapping(184, &traffic);
+        }
+        check_agrees(&traffic);
+    }
+
+    #[test]
+    fn runs_are_merged() {
+        let rle = RleLayer::from_bits(&seeds::from_str("..###.#").unwrap());
+        assert_eq!(rle.runs, &[(false, 2), (true, 3), (false, 1), (true, 1)]);
+        assert_eq!(RleLayer::from_bits(&[]).runs(), 0);
+        assert!(RleLayer::from_bits(&[]).is_empty());
+    }
+
+    #[test]
+    fn saves_memory() {
+        let dead = vec![false; 1_000_000];
+        let rle = RleLayer::from_bits(&dead);
+        assert_eq!(rle.runs(), 1);
+        assert!(rle.memory_bytes() * 1000 < bits_memory_bytes(&dead));
+
+        assert!(matches!(StoredLayer::compact(dead), StoredLayer::Rle(_)));
+        let noise = seeds::random(1000, 0.5, 3);
+        assert!(matches!(StoredLayer::compact(noise), StoredLayer::Bits(_)));
+    }
+}
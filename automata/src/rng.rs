@@ -0,0 +1,75 @@
+/// A small pseudorandom number generator (SplitMix64). It's nowhere near good
+/// enough for cryptography, but it's fast, and the same seed gives the same
+/// numbers on every platform, which is what reproducible runs need.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float chosen uniformly from `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // Use the top 53 bits, which is all an f64 can hold.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns true with probability `p`.
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// An integer chosen uniformly from `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "can't choose from an empty range");
+        // Multiply-shift avoids the bias of taking the modulus.
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let a: Vec<_> = (0..10)
+            .scan(Rng::new(42), |r, _| Some(r.next_u64()))
+            .collect();
+        let b: Vec<_> = (0..10)
+            .scan(Rng::new(42), |r, _| Some(r.next_u64()))
+            .collect();
+        let c: Vec<_> = (0..10)
+            .scan(Rng::new(43), |r, _| Some(r.next_u64()))
+            .collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        // Reference value for SplitMix64 seeded with 0.
+        assert_eq!(Rng::new(0).next_u64(), 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            assert!(rng.below(5) < 5);
+        }
+
+        let hits = (0..10_000).filter(|_| rng.gen_bool(0.25)).count();
+        assert!((2300..2700).contains(&hits));
+    }
+}
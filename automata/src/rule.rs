@@ -0,0 +1,139 @@
+use crate::error::CaError;
+use std::str::FromStr;
+
+/// An elementary rule, by its Wolfram number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rule(pub u8);
+
+// Separators which may be used to group the digits of a truth table.
+fn is_separator(c: char) -> bool {
+    c == '_' || c == ' '
+}
+
+impl Rule {
+    /// Parses a rule's truth table: its outputs for neighbourhoods `111` down
+    /// to `000`, so `00011110` is rule 30. Underscores and spaces are ignored.
+    pub fn from_binary_str(text: &str) -> Result<Rule, CaError> {
+        let bad_digit = text
+            .chars()
+            .enumerate()
+            .find(|&(_, c)| !is_separator(c) && c != '0' && c != '1');
+        if let Some((i, found)) = bad_digit {
+            return Err(CaError::Parse {
+                format: "truth table",
+                line: 1,
+                column: i + 1,
+                message: format!("unexpected '{}'", found),
+            });
+        }
+        let digits: Vec<char> = text.chars().filter(|&c| !is_separator(c)).collect();
+        if digits.len() != 8 {
+            return Err(CaError::InvalidRule(format!(
+                "truth table has {} digits, not 8",
+                digits.len()
+            )));
+        }
+
+        let number = digits
+            .iter()
+            .fold(0, |number, &digit| number << 1 | (digit == '1') as u8);
+        Ok(Rule(number))
+    }
+
+    /// Writes out the rule's truth table, the way `from_binary_str` reads it.
+    pub fn to_binary_string(self) -> String {
+        format!("{:08b}", self.0)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = CaError;
+
+    /// Parses either a truth table of exactly 8 binary digits, or otherwise a
+    /// rule number in decimal.
+    fn from_str(text: &str) -> Result<Rule, CaError> {
+        let digits = text.chars().filter(|&c| !is_separator(c));
+        if digits.clone().count() == 8 && digits.clone().all(|c| c == '0' || c == '1') {
+            return Rule::from_binary_str(text);
+        }
+        text.parse().map(Rule).map_err(|_| {
+            CaError::InvalidRule(format!(
+                "'{}' isn't a number from 0 to 255, or an 8-digit truth table",
+                text
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Rule {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn known_rules() {
+        assert_eq!(Rule::from_binary_str("00011110").unwrap(), Rule(30));
+        assert_eq!(Rule::from_binary_str("01101110").unwrap(), Rule(110));
+        assert_eq!(Rule::from_binary_str("0110_1110").unwrap(), Rule(110));
+        assert_eq!(Rule::from_binary_str("0001 1110").unwrap(), Rule(30));
+        assert_eq!(Rule(30).to_binary_string(), "00011110");
+        assert_eq!(Rule(110).to_binary_string(), "01101110");
+    }
+
+    #[test]
+    fn round_trips() {
+        for number in 0..=255 {
+            let rule = Rule(number);
+            assert_eq!(
+                Rule::from_binary_str(&rule.to_binary_string()).unwrap(),
+                rule
+            );
+            assert_eq!(parse(&rule.to_binary_string()), rule);
+            assert_eq!(parse(&number.to_string()), rule);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_tables() {
+        for text in &["0001111", "000111100"] {
+            let err = Rule::from_binary_str(text).unwrap_err();
+            assert!(matches!(err, CaError::InvalidRule(_)), "{:?}", err);
+        }
+        assert_eq!(
+            Rule::from_binary_str("0001111").unwrap_err().to_string(),
+            "invalid rule: truth table has 7 digits, not 8"
+        );
+
+        // The column counts the separators too.
+        match Rule::from_binary_str("0001_1210").unwrap_err() {
+            CaError::Parse {
+                format,
+                line,
+                column,
+                message,
+            } => {
+                assert_eq!((format, line, column), ("truth table", 1, 7));
+                assert_eq!(message, "unexpected '2'");
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn auto_detection() {
+        assert_eq!(parse("30"), Rule(30));
+        assert_eq!(parse("00011110"), Rule(30));
+        assert_eq!(parse("11111111"), Rule(255));
+        for text in &["0001111", "256", "-1", ""] {
+            let err = text.parse::<Rule>().unwrap_err();
+            assert!(matches!(err, CaError::InvalidRule(_)), "{:?}", err);
+        }
+        assert_eq!(
+            "256".parse::<Rule>().unwrap_err().to_string(),
+            "invalid rule: '256' isn't a number from 0 to 255, or an 8-digit truth table"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::error::CaError;
+use crate::formats::{parse_layer, parse_rle_row};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Where to read a seed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedSource {
+    Stdin,
+    Path(PathBuf),
+    /// The seed itself, such as one given on the command line.
+    Literal(String),
+}
+
+impl SeedSource {
+    /// What to call the source in error messages.
+    pub fn name(&self) -> String {
+        match self {
+            SeedSource::Stdin => "<stdin>".to_string(),
+            SeedSource::Path(path) => path.display().to_string(),
+            SeedSource::Literal(_) => "<seed>".to_string(),
+        }
+    }
+}
+
+/// How a seed is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedFormat {
+    /// The first line, written like `..#..`.
+    Text,
+    /// The first row of a run-length encoded pattern.
+    Rle,
+}
+
+impl SeedFormat {
+    /// Guesses the format of a file from its extension: `.rle` files are RLE,
+    /// and everything else is text.
+    pub fn from_path(path: &Path) -> SeedFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("rle") => SeedFormat::Rle,
+            _ => SeedFormat::Text,
+        }
+    }
+}
+
+/// Loads a seed from `source`. Files ending in `.rle` are read as RLE, and
+/// everything else as text.
+pub fn load_seed(source: SeedSource) -> Result<Vec<bool>, CaError> {
+    let format = match &source {
+        SeedSource::Path(path) => SeedFormat::from_path(path),
+        _ => SeedFormat::Text,
+    };
+    load_seed_as(source, format)
+}
+
+/// Loads a seed from `source`, written in `format`.
+pub fn load_seed_as(source: SeedSource, format: SeedFormat) -> Result<Vec<bool>, CaError> {
+    let name = source.name();
+    match source {
+        SeedSource::Stdin => read_seed(io::stdin().lock(), format, &name),
+        SeedSource::Path(path) => {
+            let file = File::open(&path).map_err(|err| CaError::Io(err).in_source(&name))?;
+            read_seed(BufReader::new(file), format, &name)
+        }
+        SeedSource::Literal(text) => read_seed(text.as_bytes(), format, &name),
+    }
+}
+
+/// Reads a seed written in `format` from `r`, calling it `name` in errors.
+pub fn read_seed<R: BufRead>(
+    mut r: R,
+    format: SeedFormat,
+    name: &str,
+) -> Result<Vec<bool>, CaError> {
+    let io_error = |err| CaError::Io(err).in_source(name);
+
+    let mut text = String::new();
+    match format {
+        SeedFormat::Text => r.read_line(&mut text).map_err(io_error)?,
+        SeedFormat::Rle => r.read_to_string(&mut text).map_err(io_error)?,
+    };
+    if text.trim().is_empty() {
+        return Err(CaError::Empty.in_source(name));
+    }
+
+    let parsed = match format {
+        SeedFormat::Text => parse_layer(&text),
+        SeedFormat::Rle => parse_rle_row(&text),
+    };
+    parsed.map_err(|err| err.in_source(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_first_line() {
+        let read = |text: &str| read_seed(text.as_bytes(), SeedFormat::Text, "test");
+        assert_eq!(
+            read("..#.#\n##\n").unwrap(),
+            &[false, false, true, false, true]
+        );
+        assert_eq!(read("#.#\r\n").unwrap(), &[true, false, true]);
+        assert_eq!(read("#").unwrap(), &[true]);
+    }
+
+    #[test]
+    fn reads_rle() {
+        let text = "#C a comment\nx = 4, y = 1\n2bob!\n";
+        let seed = read_seed(text.as_bytes(), SeedFormat::Rle, "test").unwrap();
+        assert_eq!(seed, &[false, false, true, false]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = read_seed("..x\n".as_bytes(), SeedFormat::Text, "seed.txt").unwrap_err();
+        assert!(matches!(err.innermost(), CaError::Parse { column: 3, .. }));
+        assert_eq!(
+            err.to_string(),
+            "seed.txt: unexpected 'x' at line 1, column 3 of layer"
+        );
+
+        let err = read_seed("\r\n".as_bytes(), SeedFormat::Text, "seed.txt").unwrap_err();
+        assert_eq!(err.to_string(), "seed.txt: nothing to read");
+        let err = read_seed("".as_bytes(), SeedFormat::Rle, "seed.rle").unwrap_err();
+        assert!(matches!(err.innermost(), CaError::Empty));
+
+        let missing = PathBuf::from("/definitely/not/here.txt");
+        let err = load_seed(SeedSource::Path(missing)).unwrap_err();
+        assert!(matches!(err.innermost(), CaError::Io(_)));
+        assert!(err.to_string().starts_with("/definitely/not/here.txt: "));
+    }
+
+    #[test]
+    fn files() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = dir.path().join("seed.txt");
+        let rle = dir.path().join("seed.RLE");
+        fs::write(&text, "#..#\n").unwrap();
+        fs::write(&rle, "x = 3\n3o!\n").unwrap();
+
+        assert_eq!(
+            load_seed(SeedSource::Path(text)).unwrap(),
+            &[true, false, false, true]
+        );
+        assert_eq!(load_seed(SeedSource::Path(rle)).unwrap(), &[true; 3]);
+    }
+
+    #[test]
+    fn literals() {
+        let literal = || SeedSource::Literal("bo".to_string());
+        assert!(matches!(
+            load_seed(literal()).unwrap_err().innermost(),
+            CaError::Parse { .. }
+        ));
+        assert_eq!(
+            load_seed_as(literal(), SeedFormat::Rle).unwrap(),
+            &[false, true]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::error::CaError;
+use crate::formats::{parse_cells, parse_layer};
+use crate::rng::Rng;
+
+/// A single live cell, which is what `iter_layers` starts from.
+pub fn single() -> Vec<bool> {
+    vec![true]
+}
+
+/// `n` live cells in a row.
+pub fn block(n: usize) -> Vec<bool> {
+    vec![true; n]
+}
+
+/// `width` cells alternating between live and dead, starting with a live one.
+pub fn alternating(width: usize) -> Vec<bool> {
+    (0..width).map(|i| i % 2 == 0).collect()
+}
+
+/// `width` cells, each alive with probability `density`. The same `seed`
+/// always gives the same layer.
+pub fn random(width: usize, density: f64, seed: u64) -> Vec<bool> {
+    let mut rng = Rng::new(seed);
+    (0..width).map(|_| rng.gen_bool(density)).collect()
+}
+
+/// A layer written out as text, like `..#.#..`.
+pub fn from_str(text: &str) -> Result<Vec<bool>, CaError> {
+    parse_layer(text)
+}
+
+/// A seed looked up by name, which is either a layer for a 1D rule or a
+/// pattern for the Life module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamedSeed {
+    Layer(Vec<bool>),
+    Pattern(Vec<Vec<bool>>),
+}
+
+/// Looks up a seed by the name used on the command line.
+///
+/// The 1D seeds are `single`, or one of `block:N`, `alternating:N` or
+/// `random:N[:DENSITY[:SEED]]` for a seed `N` cells wide. The random seed's
+/// density defaults to 0.5, and its RNG seed to 0. The 2D patterns are those
+/// of `pattern_by_name`.
+pub fn by_name(name: &str) -> Option<NamedSeed> {
+    let mut parts = name.split(':');
+    let kind = parts.next()?;
+    let args: Vec<&str> = parts.collect();
+
+    let layer = match (kind, &args[..]) {
+        ("single", []) => single(),
+        ("block", [n]) => block(n.parse().ok()?),
+        ("alternating", [n]) => alternating(n.parse().ok()?),
+        ("random", [n, rest @ ..]) if rest.len() <= 2 => {
+            let density: f64 = rest.first().map_or(Ok(0.5), |d| d.parse()).ok()?;
+            let seed: u64 = rest.get(1).map_or(Ok(0), |s| s.parse()).ok()?;
+            if !(0.0..=1.0).contains(&density) {
+                return None;
+            }
+            random(n.parse().ok()?, density, seed)
+        }
+        (name, []) => return pattern_by_name(name).map(NamedSeed::Pattern),
+        _ => return None,
+    };
+    Some(NamedSeed::Layer(layer))
+}
+
+// 2D patterns for the Life module, in plaintext format.
+
+const GLIDER: &str = "\
+!Name: Glider
+.O.
+..O
+OOO
+";
+
+const BLINKER: &str = "\
+!Name: Blinker
+OOO
+";
+
+const R_PENTOMINO: &str = "\
+!Name: R-pentomino
+.OO
+OO.
+.O.
+";
+
+const GOSPER_GUN: &str = "\
+!Name: Gosper glider gun
+........................O...........
+......................O.O...........
+............OO......OO............OO
+...........O...O....OO............OO
+OO........O.....O...OO..............
+OO........O...O.OO....O.O...........
+..........O.....O.......O...........
+...........O...O....................
+............OO......................
+";
+
+fn pattern(text: &str) -> Vec<Vec<bool>> {
+    parse_cells(text).expect("built-in patterns are valid")
+}
+
+/// The glider, which moves one cell down and right every 4 generations.
+pub fn glider() -> Vec<Vec<bool>> {
+    pattern(GLIDER)
+}
+
+/// The blinker, which oscillates with period 2.
+pub fn blinker() -> Vec<Vec<bool>> {
+    pattern(BLINKER)
+}
+
+/// The R-pentomino, which takes over a thousand generations to settle down.
+pub fn r_pentomino() -> Vec<Vec<bool>> {
+    pattern(R_PENTOMINO)
+}
+
+/// Gosper's glider gun, which emits a glider every 30 generations.
+pub fn gosper_gun() -> Vec<Vec<bool>> {
+    pattern(GOSPER_GUN)
+}
+
+/// Looks up a 2D pattern by name: `glider`, `blinker`, `r_pentomino` or `gosper_gun`.
+pub fn pattern_by_name(name: &str) -> Option<Vec<Vec<bool>>> {
+    match name {
+        "glider" => Some(glider()),
+        "blinker" => Some(blinker()),
+        "r_pentomino" => Some(r_pentomino()),
+        "gosper_gun" => Some(gosper_gun()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::Boundary;
+    use crate::life::Grid;
+
+    fn population(cells: &[bool]) -> usize {
+        cells.iter().filter(|&&cell| cell).count()
+    }
+
+    #[test]
+    fn one_dimensional() {
+        assert_eq!(single(), &[true]);
+        assert_eq!(block(3), &[true, true, true]);
+        assert_eq!(alternating(5), &[true, false, true, false, true]);
+        assert_eq!(
+            from_str("..#.#").unwrap(),
+            &[false, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn random_seeds() {
+        assert_eq!(random(100, 0.3, 1), random(100, 0.3, 1));
+        assert_ne!(random(100, 0.3, 1), random(100, 0.3, 2));
+        assert_eq!(population(&random(100, 0.0, 1)), 0);
+        assert_eq!(population(&random(100, 1.0, 1)), 100);
+
+        let pop = population(&random(10_000, 0.3, 5));
+        assert!((2800..3200).contains(&pop));
+    }
+
+    #[test]
+    fn names() {
+        let layer = |name| match by_name(name) {
+            Some(NamedSeed::Layer(layer)) => Some(layer),
+            _ => None,
+        };
+        assert_eq!(layer("single"), Some(single()));
+        assert_eq!(layer("block:4"), Some(block(4)));
+        assert_eq!(layer("alternating:7"), Some(alternating(7)));
+        assert_eq!(by_name("block"), None);
+        assert_eq!(by_name("block:x"), None);
+        assert_eq!(by_name("block:4:5"), None);
+        assert_eq!(by_name("single:3"), None);
+        assert_eq!(by_name("planet"), None);
+        assert_eq!(by_name("glider:3"), None);
+
+        for (name, pattern) in [
+            ("glider", glider()),
+            ("blinker", blinker()),
+            ("r_pentomino", r_pentomino()),
+            ("gosper_gun", gosper_gun()),
+        ] {
+            assert_eq!(by_name(name), Some(NamedSeed::Pattern(pattern)));
+        }
+
+        assert_eq!(pattern_by_name("glider"), Some(glider()));
+        assert_eq!(pattern_by_name("single"), None);
+    }
+
+    #[test]
+    fn random_names() {
+        let layer = |name| match by_name(name) {
+            Some(NamedSeed::Layer(layer)) => Some(layer),
+            _ => None,
+        };
+        assert_eq!(layer("random:9"), Some(random(9, 0.5, 0)));
+        assert_eq!(layer("random:9:0.2"), Some(random(9, 0.2, 0)));
+        assert_eq!(layer("random:9:0.2:7"), Some(random(9, 0.2, 7)));
+        assert_eq!(layer("random:50:1"), Some(block(50)));
+        assert_eq!(by_name("random"), None);
+        assert_eq!(by_name("random:9:1.5"), None);
+        assert_eq!(by_name("random:9:-0.1"), None);
+        assert_eq!(by_name("random:9:half"), None);
+        assert_eq!(by_name("random:9:0.2:x"), None);
+        assert_eq!(by_name("random:9:0.2:7:1"), None);
+    }
+
+    #[test]
+    fn pattern_sizes() {
+        let size = |rows: &[Vec<bool>]| (rows[0].len(), rows.len());
+        let pop = |rows: &[Vec<bool>]| rows.iter().map(|row| population(row)).sum::<usize>();
+
+        assert_eq!((size(&glider()), pop(&glider())), ((3, 3), 5));
+        assert_eq!((size(&blinker()), pop(&blinker())), ((3, 1), 3));
+        assert_eq!((size(&r_pentomino()), pop(&r_pentomino())), ((3, 3), 5));
+        assert_eq!((size(&gosper_gun()), pop(&gosper_gun())), ((36, 9), 36));
+    }
+
+    #[test]
+    fn gosper_gun_emits_glider() {
+        let mut grid = Grid::new(60, 40, Boundary::Dead);
+        grid.place(&gosper_gun(), 1, 1);
+        let start = grid.clone();
+
+        for _ in 0..30 {
+            grid = grid.step();
+        }
+
+        // After a full period, the gun is back where it started, plus a glider.
+        let mut new_cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if start.get(x, y) {
+                    assert!(grid.get(x, y));
+                } else if grid.get(x, y) {
+                    new_cells.push((x, y));
+                }
+            }
+        }
+        assert_eq!(
+            new_cells,
+            &[(24, 10), (25, 11), (26, 11), (24, 12), (25, 12)]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::ca::next_layer_into;
+use crate::observer::{Observers, RunObserver};
+use crate::pool::{LayerPool, DEFAULT_POOL_CAPACITY};
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// A run of a rule in progress, holding the latest layer.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    rule: u8,
+    layer: Vec<bool>,
+    generation: usize,
+    /// The global column of the layer's first cell. The seed is centered on
+    /// column 0.
+    left: isize,
+    max_width: Option<usize>,
+    /// The first generation at which the layer was clipped to `max_width`.
+    clipped_at: Option<usize>,
+    /// Old layers, kept so that stepping doesn't allocate.
+    pool: LayerPool,
+    observers: Observers,
+}
+
+/// How far a time-limited run got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunResult {
+    /// The number of steps taken during the run.
+    pub generations_completed: usize,
+    /// The layer the run finished on.
+    pub final_layer: Vec<bool>,
+    /// How long the run actually took.
+    pub elapsed: Duration,
+    /// The first generation at which the layer was clipped, if it ever was.
+    pub clipped_at: Option<usize>,
+}
+
+/// Counts over the cells a run is holding on to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerStats {
+    pub population: usize,
+    /// The fraction of cells which are alive.
+    pub density: f64,
+    /// Whether the layer has been clipped, so these only count the cells
+    /// inside the window rather than the whole run.
+    pub truncated: bool,
+}
+
+// Roughly how many cells to compute between checks of the clock. Checking
+// every generation would cost more than the narrow early layers themselves.
+const CELLS_PER_CLOCK_CHECK: usize = 1 << 14;
+const MAX_STEPS_PER_CLOCK_CHECK: usize = 1 << 7;
+
+impl Simulation {
+    /// Starts a run of `rule` from `seed`, at generation 0.
+    pub fn new(rule: u8, seed: Vec<bool>) -> Simulation {
+        Simulation::resume(rule, seed, 0)
+    }
+
+    /// Picks up a run of `rule` which had reached `layer` at `generation`.
+    pub fn resume(rule: u8, layer: Vec<bool>, generation: usize) -> Simulation {
+        Simulation {
+            rule,
+            left: -(layer.len() as isize / 2),
+            layer,
+            generation,
+            max_width: None,
+            clipped_at: None,
+            pool: LayerPool::new(DEFAULT_POOL_CAPACITY),
+            observers: Observers::default(),
+        }
+    }
+
+    /// Stops the layer growing past `max_width` cells. Once it would, it's
+    /// clipped to the window of that width centered on the seed, columns
+    /// `-(max_width / 2)` up to but not including `max_width - max_width / 2`,
+    /// and cells outside the window are treated as dead from then on. So the
+    /// cells near the window's edges stop being exact as soon as clipping
+    /// starts, and the error spreads inwards by up to a cell each generation.
+    pub fn with_max_width(mut self, max_width: usize) -> Simulation {
+        self.max_width = Some(max_width);
+        self.clip();
+        self
+    }
+
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    /// The number of steps taken since the seed.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The latest layer.
+    pub fn layer(&self) -> &[bool] {
+        &self.layer
+    }
+
+    /// The global column of the latest layer's first cell.
+    pub fn left(&self) -> isize {
+        self.left
+    }
+
+    pub fn max_width(&self) -> Option<usize> {
+        self.max_width
+    }
+
+    /// The first generation at which the layer was clipped to the maximum
+    /// width, if it has been.
+    pub fn clipped_at(&self) -> Option<usize> {
+        self.clipped_at
+    }
+
+    pub fn stats(&self) -> LayerStats {
+        let population = self.layer.iter().filter(|&&cell| cell).count();
+        LayerStats {
+            population,
+            density: population as f64 / self.layer.len().max(1) as f64,
+            truncated: self.clipped_at.is_some(),
+        }
+    }
+
+    /// Attaches `observer`, which hears about every step from now on. Any
+    /// number of observers can be attached, and they're told about each step
+    /// in the order they were attached. Clones of the simulation don't keep
+    /// them.
+    pub fn observe(&mut self, observer: Box<dyn RunObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Advances the run by one generation.
+    pub fn step(&mut self) {
+        // Don't look at the clock unless someone wants to know.
+        if self.observers.is_empty() {
+            return self.advance();
+        }
+        let start = Instant::now();
+        self.advance();
+        let step_duration = start.elapsed();
+        self.observers
+            .on_generation(self.generation, &self.layer, step_duration);
+    }
+
+    fn advance(&mut self) {
+        let mut next = self.pool.take(self.layer.len() + 2);
+        next_layer_into(self.rule, &self.layer, false, &mut next);
+        self.pool.give_back(mem::replace(&mut self.layer, next));
+        self.generation += 1;
+        self.left -= 1;
+        self.clip();
+    }
+
+    // Cuts the layer down to the window allowed by `max_width`, if it's grown
+    // out of it.
+    fn clip(&mut self) {
+        let max_width = match self.max_width {
+            Some(max_width) => max_width,
+            None => return,
+        };
+        let window_left = -(max_width as isize / 2);
+        let window_right = window_left + max_width as isize;
+
+        let mut clipped = false;
+        if self.left < window_left {
+            self.layer.drain(..(window_left - self.left) as usize);
+            self.left = window_left;
+            clipped = true;
+        }
+        let right = self.left + self.layer.len() as isize;
+        if right > window_right {
+            self.layer.truncate((window_right - self.left) as usize);
+            clipped = true;
+        }
+        if clipped && self.clipped_at.is_none() {
+            self.clipped_at = Some(self.generation);
+        }
+    }
+
+    /// Steps as many times as will fit in `budget`. The clock is only checked
+    /// every so often, so the run may overshoot by a little.
+    pub fn run_for(&mut self, budget: Duration) -> RunResult {
+        let start = Instant::now();
+        let first_generation = self.generation;
+
+        while start.elapsed() < budget {
+            // Check the clock less often while the layers are narrow. Layers grow
+            // as we go, so also limit the steps to keep that growth in check.
+            let steps = (CELLS_PER_CLOCK_CHECK / self.layer.len().max(1))
+                .clamp(1, MAX_STEPS_PER_CLOCK_CHECK);
+            for _ in 0..steps {
+                self.step();
+            }
+        }
+
+        self.finish(first_generation, start)
+    }
+
+    /// Steps until the run reaches generation `until`.
+    pub fn run_until(&mut self, until: usize) -> RunResult {
+        let start = Instant::now();
+        let first_generation = self.generation;
+        while self.generation < until {
+            self.step();
+        }
+        self.finish(first_generation, start)
+    }
+
+    /// Sums up a run which started at `first_generation` and `start`, and
+    /// tells the observers it's over.
+    pub(crate) fn finish(&mut self, first_generation: usize, start: Instant) -> RunResult {
+        let result = RunResult {
+            generations_completed: self.generation - first_generation,
+            final_layer: self.layer.clone(),
+            elapsed: start.elapsed(),
+            clipped_at: self.clipped_at,
+        };
+        self.observers.on_finish(&result);
+        result
+    }
+}
+
+/// Runs `rule` from `seed` for as many generations as will fit in `budget`.
+pub fn run_for(rule: u8, seed: Vec<bool>, budget: Duration) -> RunResult {
+    Simulation::new(rule, seed).run_for(budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::iter_layers;
+
+    #[test]
+    fn steps_like_iter_layers() {
+        let mut sim = Simulation::new(30, vec![true]);
+        for layer in iter_layers(30).take(20) {
+            assert_eq!(sim.layer(), &layer[..]);
+            sim.step();
+        }
+        assert_eq!(sim.generation(), 20);
+    }
+
+    #[test]
+    fn generous_budget() {
+        let result = run_for(204, vec![true], Duration::from_millis(200));
+        assert!(result.generations_completed >= 100);
+        assert_eq!(
+            result.final_layer.len(),
+            1 + 2 * result.generations_completed
+        );
+    }
+
+    #[test]
+    fn zero_budget() {
+        let result = run_for(30, vec![true, false, true], Duration::from_secs(0));
+        assert_eq!(result.generations_completed, 0);
+        assert_eq!(result.final_layer, &[true, false, true]);
+    }
+
+    #[test]
+    fn elapsed_within_budget() {
+        let budget = Duration::from_millis(50);
+        let mut sim = Simulation::new(30, vec![true]);
+        let result = sim.run_for(budget);
+
+        // The run only stops once the budget's used up, and then only after
+        // the batch of steps it's in the middle of. So it can overrun by as
+        // long as a batch takes, which is measured here by timing one more
+        // at the width the run got to, plus a margin for a busy machine.
+        assert!(result.elapsed >= budget);
+        assert_eq!(sim.generation(), result.generations_completed);
+        let steps =
+            (CELLS_PER_CLOCK_CHECK / sim.layer().len().max(1)).clamp(1, MAX_STEPS_PER_CLOCK_CHECK);
+        let mut batch = sim.clone();
+        let start = Instant::now();
+        for _ in 0..steps {
+            batch.step();
+        }
+        let slack = start.elapsed() + Duration::from_millis(100);
+        assert!(
+            result.elapsed <= budget + slack,
+            "took {:?} for a budget of {:?}",
+            result.elapsed,
+            budget
+        );
+
+        // Running again carries on from where it left off.
+        let again = sim.run_for(Duration::from_millis(10));
+        assert_eq!(
+            sim.generation(),
+            result.generations_completed + again.generations_completed
+        );
+    }
+
+    // The cells of an unclipped layer in generation `generation` of a run
+    // from a single cell, between global columns `from` and `to`.
+    fn window(layer: &[bool], generation: usize, from: isize, to: isize) -> Vec<bool> {
+        (from..to)
+            .map(|column| layer[(column + generation as isize) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn capped_matches_uncapped_window() {
+        let mut sim = Simulation::new(30, vec![true]).with_max_width(20);
+        for (generation, layer) in iter_layers(30).take(11).enumerate() {
+            if generation < 10 {
+                assert_eq!(sim.layer(), &layer[..]);
+            } else {
+                // Clipping the layer that first outgrows the window is exact.
+                assert_eq!(sim.left(), -10);
+                assert_eq!(sim.layer(), &window(&layer, generation, -10, 10)[..]);
+            }
+            sim.step();
+        }
+    }
+
+    #[test]
+    fn clips_at_predicted_generation() {
+        for max_width in 1..30 {
+            let mut sim = Simulation::new(110, vec![true]).with_max_width(max_width);
+            // Layer `n` is `2n + 1` cells wide, and the window's left edge is
+            // at `-(max_width / 2)`, so it's outgrown at this generation.
+            let expected = max_width.div_ceil(2);
+            while sim.generation() < expected {
+                assert_eq!(sim.clipped_at(), None);
+                assert!(!sim.stats().truncated);
+                sim.step();
+            }
+            assert_eq!(sim.clipped_at(), Some(expected));
+            assert!(sim.stats().truncated);
+            assert_eq!(sim.layer().len(), max_width);
+        }
+
+        // Seeds which start out too wide are clipped straight away.
+        let sim = Simulation::new(30, vec![true; 9]).with_max_width(5);
+        assert_eq!(sim.clipped_at(), Some(0));
+        assert_eq!(sim.left(), -2);
+        assert_eq!(sim.layer(), &[true; 5]);
+    }
+
+    #[test]
+    fn stops_growing() {
+        let mut sim = Simulation::new(30, vec![true]).with_max_width(100);
+        for _ in 0..60 {
+            sim.step();
+        }
+        let capacity = sim.layer.capacity();
+        for _ in 0..1000 {
+            sim.step();
+            assert_eq!(sim.layer().len(), 100);
+            assert!(sim.layer.capacity() <= capacity);
+        }
+        assert_eq!(sim.run_for(Duration::from_millis(1)).clipped_at, Some(50));
+
+        let stats = sim.stats();
+        assert_eq!(stats.population, sim.layer().iter().filter(|&&c| c).count());
+        assert_eq!(stats.density, stats.population as f64 / 100.0);
+    }
+}
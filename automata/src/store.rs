@@ -0,0 +1,184 @@
+//! Different ways of holding a layer's cells in memory, so that they can be
+//! compared like for like. `Vec<bool>` is what the rest of the crate uses.
+
+use crate::ca::test_rule;
+use crate::packed::PackedLayer;
+
+/// Something that can hold the cells of a layer.
+pub trait LayerStore: Clone {
+    /// An empty layer, with room for `cells` cells.
+    fn with_capacity(cells: usize) -> Self;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets cell `i`, which has to be less than `len()`.
+    fn get(&self, i: usize) -> bool;
+
+    /// Adds a cell to the right-hand end.
+    fn push(&mut self, cell: bool);
+
+    fn from_bools(cells: &[bool]) -> Self {
+        let mut store = Self::with_capacity(cells.len());
+        for &cell in cells {
+            store.push(cell);
+        }
+        store
+    }
+
+    fn to_bools(&self) -> Vec<bool> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+}
+
+impl LayerStore for Vec<bool> {
+    fn with_capacity(cells: usize) -> Self {
+        Vec::with_capacity(cells)
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self[i]
+    }
+
+    fn push(&mut self, cell: bool) {
+        Vec::push(self, cell)
+    }
+
+    fn from_bools(cells: &[bool]) -> Self {
+        cells.to_vec()
+    }
+
+    fn to_bools(&self) -> Vec<bool> {
+        self.clone()
+    }
+}
+
+/// Eight cells to a byte, laid out as described in `packed`.
+impl LayerStore for PackedLayer {
+    fn with_capacity(cells: usize) -> Self {
+        PackedLayer {
+            bytes: Vec::with_capacity(cells.div_ceil(8)),
+            bit_len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bit_len
+    }
+
+    fn get(&self, i: usize) -> bool {
+        assert!(i < self.bit_len, "cell {} out of range", i);
+        self.bytes[i / 8] >> (i % 8) & 1 == 1
+    }
+
+    fn push(&mut self, cell: bool) {
+        if self.bit_len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        self.bytes[self.bit_len / 8] |= (cell as u8) << (self.bit_len % 8);
+        self.bit_len += 1;
+    }
+}
+
+/// Sixty-four cells to a word, with cell `i` in bit `i % 64` of word `i / 64`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Packed64 {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl LayerStore for Packed64 {
+    fn with_capacity(cells: usize) -> Self {
+        Packed64 {
+            words: Vec::with_capacity(cells.div_ceil(64)),
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "cell {} out of range", i);
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn push(&mut self, cell: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.words[self.len / 64] |= (cell as u64) << (self.len % 64);
+        self.len += 1;
+    }
+}
+
+/// Like `ca::next_layer`, but for any kind of layer store: the next layer is a
+/// cell wider on each side, with dead cells beyond the edges of `input`.
+pub fn next_layer_generic_store<S: LayerStore>(rule: u8, input: &S) -> S {
+    let cell = |i: isize| i >= 0 && (i as usize) < input.len() && input.get(i as usize);
+
+    let mut out = S::with_capacity(input.len() + 2);
+    for i in -1..(input.len() + 1) as isize {
+        out.push(test_rule(rule, (cell(i - 1), cell(i), cell(i + 1))));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::next_layer;
+    use crate::packed::layer_to_packed;
+    use crate::seeds;
+
+    fn run<S: LayerStore>(rule: u8, seed: &[bool], generations: usize) -> Vec<bool> {
+        let mut layer = S::from_bools(seed);
+        for _ in 0..generations {
+            layer = next_layer_generic_store(rule, &layer);
+        }
+        layer.to_bools()
+    }
+
+    #[test]
+    fn backends_agree() {
+        for rule in 0..=255 {
+            let seed = seeds::random(70, 0.5, rule as u64);
+            let mut expected = seed.clone();
+            for _ in 0..5 {
+                expected = next_layer(rule, &expected);
+            }
+
+            assert_eq!(run::<Vec<bool>>(rule, &seed, 5), expected, "rule {}", rule);
+            assert_eq!(
+                run::<PackedLayer>(rule, &seed, 5),
+                expected,
+                "rule {}",
+                rule
+            );
+            assert_eq!(run::<Packed64>(rule, &seed, 5), expected, "rule {}", rule);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for len in [0, 1, 7, 8, 9, 63, 64, 65, 200] {
+            let cells = seeds::random(len, 0.5, len as u64);
+            let packed = PackedLayer::from_bools(&cells);
+            let wide = Packed64::from_bools(&packed.to_bools());
+            assert_eq!(wide.len(), len);
+            assert_eq!(Vec::<bool>::from_bools(&wide.to_bools()), cells);
+
+            // Packing cell by cell gives the same bytes as packing all at once.
+            assert_eq!(packed, layer_to_packed(&cells));
+        }
+        assert!(Packed64::default().is_empty());
+    }
+}
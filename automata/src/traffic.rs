@@ -0,0 +1,169 @@
+//! Rule 184 as single-lane traffic: each live cell is a car, which moves one
+//! cell to the right whenever the cell in front of it is empty. Everything here
+//! works on wrapping runs, so cars leaving the right edge come back on the left.
+
+use crate::ca::next_layer_wrapping;
+use crate::rng::Rng;
+use crate::seeds;
+
+const TRAFFIC: u8 = 184;
+
+/// The fraction of cells where a car moved right between `before` and the
+/// layer after it, `after`.
+pub fn flow(before: &[bool], after: &[bool]) -> f64 {
+    assert_eq!(before.len(), after.len(), "layers have different widths");
+    let width = before.len();
+    let moved = (0..width)
+        .filter(|&i| {
+            let ahead = (i + 1) % width;
+            before[i] && !before[ahead] && after[ahead]
+        })
+        .count();
+    moved as f64 / width.max(1) as f64
+}
+
+/// Measures the flow of traffic at each of `densities`, on a ring of `width`
+/// cells started from random cars. Each run is left to settle for
+/// `relax_steps` generations, then its flow is averaged over the next
+/// `measure_steps`.
+///
+/// Returns a `(density, flow)` point for each run, where the density is the
+/// fraction of cells the random layer actually filled, which will be near but
+/// not exactly the density asked for.
+pub fn fundamental_diagram(
+    width: usize,
+    densities: &[f64],
+    relax_steps: usize,
+    measure_steps: usize,
+    seed: u64,
+) -> Vec<(f64, f64)> {
+    let mut rng = Rng::new(seed);
+    densities
+        .iter()
+        .map(|&density| {
+            let mut layer = seeds::random(width, density, rng.next_u64());
+            let cars = layer.iter().filter(|&&cell| cell).count();
+
+            for _ in 0..relax_steps {
+                layer = next_layer_wrapping(TRAFFIC, &layer);
+            }
+            let mut total = 0.0;
+            for _ in 0..measure_steps {
+                let next = next_layer_wrapping(TRAFFIC, &layer);
+                total += flow(&layer, &next);
+                layer = next;
+            }
+
+            (
+                cars as f64 / width.max(1) as f64,
+                total / measure_steps.max(1) as f64,
+            )
+        })
+        .collect()
+}
+
+/// Follows each car through consecutive layers of a rule 184 run, returning
+/// the cell each one was in at every generation. Cars are listed from left to
+/// right as they were in the first layer.
+pub fn trajectories(layers: &[Vec<bool>]) -> Vec<Vec<usize>> {
+    let first = match layers.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let width = first.len();
+
+    let mut cars: Vec<Vec<usize>> = (0..width).filter(|&i| first[i]).map(|i| vec![i]).collect();
+
+    for pair in layers.windows(2) {
+        let (before, after) = (&pair[0], &pair[1]);
+        for car in &mut cars {
+            let at = *car.last().unwrap();
+            let ahead = (at + 1) % width;
+            let next = if before[ahead] { at } else { ahead };
+            assert!(after[next], "layers aren't consecutive rule 184 layers");
+            car.push(next);
+        }
+    }
+    cars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn population(layer: &[bool]) -> usize {
+        layer.iter().filter(|&&cell| cell).count()
+    }
+
+    fn run(layer: Vec<bool>, generations: usize) -> Vec<Vec<bool>> {
+        std::iter::successors(Some(layer), |layer| {
+            Some(next_layer_wrapping(TRAFFIC, layer))
+        })
+        .take(generations)
+        .collect()
+    }
+
+    #[test]
+    fn free_flow_and_jams() {
+        let densities = [0.1, 0.2, 0.3, 0.4, 0.6, 0.7, 0.8, 0.9];
+        for (density, flow) in fundamental_diagram(400, &densities, 800, 100, 5) {
+            // Below half full every car ends up moving, and above it every
+            // gap does.
+            let expected = density.min(1.0 - density);
+            assert!(
+                (flow - expected).abs() < 0.01,
+                "density {}: flow {}, expected {}",
+                density,
+                flow,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn flow_of_single_steps() {
+        let before = seeds::from_str("##..#.#").unwrap();
+        let after = next_layer_wrapping(TRAFFIC, &before);
+        assert_eq!(after, seeds::from_str("#.#..##").unwrap());
+        // The second and third cars move, while the last is held up by the
+        // first, across the wrap.
+        assert_eq!(flow(&before, &after), 2.0 / 7.0);
+    }
+
+    #[test]
+    fn cars_are_conserved() {
+        let layers = run(seeds::random(150, 0.45, 9), 200);
+        let cars = population(&layers[0]);
+        for layer in &layers {
+            assert_eq!(population(layer), cars);
+        }
+        assert_eq!(trajectories(&layers).len(), cars);
+    }
+
+    #[test]
+    fn cars_never_cross() {
+        let width = 100;
+        let layers = run(seeds::random(width, 0.6, 2), 300);
+        let cars = trajectories(&layers);
+
+        for generation in 0..layers.len() {
+            // Going round the ring from the first car, the cars are always met
+            // in the same order.
+            let first = cars[0][generation];
+            let gaps: Vec<usize> = cars
+                .iter()
+                .map(|car| (car[generation] + width - first) % width)
+                .collect();
+            assert!(gaps.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", gaps);
+            for car in &cars {
+                assert!(layers[generation][car[generation]]);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_runs() {
+        assert!(trajectories(&[]).is_empty());
+        assert!(trajectories(&run(vec![false; 5], 3)).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Renders a fixed set of runs in each output format and compares them against
+//! the files in `tests/golden/`, to catch changes in output which would
+//! otherwise go unnoticed.
+//!
+//! Run with `UPDATE_GOLDEN=1` to write the current output over the fixtures
+//! instead, after checking that any change is intended.
+
+use automata::ca::iter_layers;
+use automata::npy::write_npy;
+use automata::render::render_rule;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const RULES: [u8; 3] = [30, 90, 110];
+const GENERATIONS: [usize; 2] = [16, 64];
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(name)
+}
+
+// Describes the size of a rendering, as far as can be told from its bytes.
+fn dimensions(name: &str, bytes: &[u8]) -> String {
+    if name.ends_with(".txt") {
+        let text = String::from_utf8_lossy(bytes);
+        let width = text.lines().map(|line| line.chars().count()).max();
+        format!(
+            "{} lines, {} wide",
+            text.lines().count(),
+            width.unwrap_or(0)
+        )
+    } else if name.ends_with(".npy") {
+        let header = String::from_utf8_lossy(&bytes[10.min(bytes.len())..]);
+        let shape = header
+            .split("'shape': ")
+            .nth(1)
+            .and_then(|s| s.split(')').next());
+        format!("shape {})", shape.unwrap_or("(?"))
+    } else if name.ends_with(".png") && bytes.len() >= 24 {
+        let word =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        format!("{}x{} pixels", word(16), word(20))
+    } else {
+        format!("{} bytes", bytes.len())
+    }
+}
+
+// Where two renderings first differ.
+fn first_difference(name: &str, expected: &[u8], actual: &[u8]) -> String {
+    if name.ends_with(".txt") {
+        let expected = String::from_utf8_lossy(expected);
+        let actual = String::from_utf8_lossy(actual);
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(e, a)| e != a)
+            .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+        format!("first differing line is {}", line + 1)
+    } else {
+        let byte = expected
+            .iter()
+            .zip(actual)
+            .position(|(e, a)| e != a)
+            .unwrap_or_else(|| expected.len().min(actual.len()));
+        format!("first differing byte is at offset {}", byte)
+    }
+}
+
+// Checks `actual` against the fixture `name`, or replaces the fixture with it
+// if `UPDATE_GOLDEN` is set. Returns a description of any mismatch.
+fn check(name: &str, actual: &[u8]) -> Option<String> {
+    let path = fixture_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return None;
+    }
+
+    let expected = match fs::read(&path) {
+        Ok(expected) => expected,
+        Err(err) => return Some(format!("{}: can't read fixture: {}", name, err)),
+    };
+    if expected == actual {
+        return None;
+    }
+
+    let saved = env::temp_dir().join(format!("golden-{}", name));
+    fs::write(&saved, actual).unwrap();
+    Some(format!(
+        "{}: expected {}, got {}; {}; actual output saved to {}",
+        name,
+        dimensions(name, &expected),
+        dimensions(name, actual),
+        first_difference(name, &expected, actual),
+        saved.display()
+    ))
+}
+
+// Renders every case with `render`, checking each against its fixture.
+fn check_all<F: Fn(u8, usize) -> Vec<u8>>(extension: &str, render: F) {
+    let mut failures = Vec::new();
+    for &rule in &RULES {
+        for &generations in &GENERATIONS {
+            let name = format!("rule_{}_{}.{}", rule, generations, extension);
+            failures.extend(check(&name, &render(rule, generations)));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn text() {
+    check_all("txt", |rule, generations| {
+        let mut out = Vec::new();
+        render_rule(&mut out, rule, generations).unwrap();
+        out
+    });
+}
+
+#[test]
+fn npy() {
+    check_all("npy", |rule, generations| {
+        let layers: Vec<_> = iter_layers(rule).take(generations).collect();
+        let mut out = Vec::new();
+        write_npy(&layers, &mut out).unwrap();
+        out
+    });
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn ages_png() {
+    use automata::age::{write_ages_png, AgeTracker};
+
+    check_all("png", |rule, generations| {
+        // Line each generation's ages up under the last, which is the widest.
+        let width = 2 * generations - 1;
+        let mut tracker = AgeTracker::new();
+        let rows: Vec<Vec<u32>> = iter_layers(rule)
+            .take(generations)
+            .map(|layer| {
+                tracker.observe_centered(&layer);
+                let mut row = vec![0; width];
+                let start = (tracker.left() + generations as isize - 1) as usize;
+                row[start..start + tracker.ages().len()].copy_from_slice(tracker.ages());
+                row
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        write_ages_png(&mut out, &rows, generations as u32).unwrap();
+        out
+    });
+}
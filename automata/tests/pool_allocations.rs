@@ -0,0 +1,47 @@
+//! Checks that long runs stop allocating once their layer pools warm up. This
+//! lives in its own test binary because it swaps out the global allocator.
+
+use automata::simulation::Simulation;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn simulation_reuses_layers() {
+    let mut sim = Simulation::new(30, vec![true]);
+    for _ in 0..100 {
+        sim.step();
+    }
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..2000 {
+        sim.step();
+    }
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+
+    // The layers keep growing, so there's a new allocation each time they pass
+    // a power of two---once for each of the two buffers being swapped.
+    assert!(allocations <= 10, "{} allocations", allocations);
+}
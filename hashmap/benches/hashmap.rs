@@ -0,0 +1,315 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{black_box, BatchSize, Criterion, Fun};
+use hashmap::dense::DenseMap;
+use hashmap::hashmap::{FixedHashBuilder, GrowthPolicy, HashMap, ProbeStrategy};
+use std::collections::HashMap as StdHashMap;
+
+const KEYS: u64 = 10_000;
+
+fn map_with(policy: GrowthPolicy) -> HashMap<u64, u64, FixedHashBuilder> {
+    let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+    map.set_growth_policy(policy);
+    map
+}
+
+// Fills a map from empty, growing as it goes.
+fn insert_all(policy: GrowthPolicy) -> HashMap<u64, u64, FixedHashBuilder> {
+    let mut map = map_with(policy);
+    for key in 0..KEYS {
+        map.insert(key, key);
+    }
+    map
+}
+
+// Puts and gets on u64 keys, with tables sized by dividing (Doubling and
+// Prime) against masking (PowerOfTwo).
+fn criterion_benchmark(c: &mut Criterion) {
+    let policies = [
+        ("doubling", GrowthPolicy::Doubling),
+        ("prime", GrowthPolicy::Prime),
+        ("power_of_two", GrowthPolicy::PowerOfTwo),
+    ];
+    for &(name, policy) in &policies {
+        c.bench_function(&format!("put_{}", name), move |b| {
+            b.iter(|| insert_all(black_box(policy)))
+        });
+
+        let map = insert_all(policy);
+        c.bench_function(&format!("get_{}", name), move |b| {
+            b.iter(|| {
+                let mut sum = 0;
+                for key in 0..KEYS {
+                    sum += map.get(black_box(&key)).unwrap();
+                }
+                sum
+            })
+        });
+    }
+}
+
+// A xorshift generator, so random keys are the same every run.
+fn random_keys(count: usize, mut state: u64) -> Vec<u64> {
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect()
+}
+
+// A map with room for up to 95% load, filled to `load` of its capacity with
+// random keys, which are returned with it.
+fn loaded_map(load: f32) -> (HashMap<u64, u64>, Vec<u64>) {
+    let mut map = HashMap::with_load_factor(0, 0.95);
+    map.grow(KEYS as usize);
+    let keys = random_keys((map.raw_capacity() as f32 * load) as usize, 1);
+    for &key in &keys {
+        map.insert(key, key);
+    }
+    (map, keys)
+}
+
+// The same workloads run on this map and on std's, both with the default
+// hasher, so std's times are a baseline. std's load factor can't be set, so
+// at a given load only this map's table is that full.
+fn std_benchmark(c: &mut Criterion) {
+    c.bench_functions(
+        "insert_sequential",
+        vec![
+            Fun::new("hashmap", |b, &n: &u64| {
+                b.iter(|| {
+                    let mut map = HashMap::new();
+                    for key in 0..n {
+                        map.insert(key, key);
+                    }
+                    map
+                })
+            }),
+            Fun::new("std", |b, &n: &u64| {
+                b.iter(|| {
+                    let mut map = StdHashMap::new();
+                    for key in 0..n {
+                        map.insert(key, key);
+                    }
+                    map
+                })
+            }),
+        ],
+        KEYS,
+    );
+    c.bench_functions(
+        "insert_reserved",
+        vec![
+            Fun::new("hashmap", |b, &n: &u64| {
+                b.iter(|| {
+                    let mut map = HashMap::with_capacity(n as usize);
+                    for key in 0..n {
+                        map.insert(key, key);
+                    }
+                    map
+                })
+            }),
+            Fun::new("std", |b, &n: &u64| {
+                b.iter(|| {
+                    let mut map = StdHashMap::with_capacity(n as usize);
+                    for key in 0..n {
+                        map.insert(key, key);
+                    }
+                    map
+                })
+            }),
+        ],
+        KEYS,
+    );
+
+    for &load in &[0.5, 0.9] {
+        let (map, keys) = loaded_map(load);
+        let std_map: StdHashMap<u64, u64> = keys.iter().map(|&key| (key, key)).collect();
+        let std_keys = keys.clone();
+        c.bench_functions(
+            &format!("get_random_{}", (load * 100.0) as u32),
+            vec![
+                Fun::new("hashmap", move |b, _: &()| {
+                    b.iter(|| keys.iter().map(|key| map.get(key).unwrap()).sum::<u64>())
+                }),
+                Fun::new("std", move |b, _: &()| {
+                    b.iter(|| std_keys.iter().map(|key| std_map[key]).sum::<u64>())
+                }),
+            ],
+            (),
+        );
+    }
+
+    // Removes the oldest key and puts in a new one each time, so the map
+    // stays the same size but keeps leaving gaps behind. Quadratic probing
+    // leaves tombstones in them, and the others shift keys back.
+    let churn_hashmap = |strategy| {
+        move |b: &mut criterion::Bencher, &n: &u64| {
+            let mut map = HashMap::with_growth_policy(0, GrowthPolicy::PowerOfTwo);
+            map.set_probe_strategy(strategy);
+            for key in 0..n {
+                map.insert(key, key);
+            }
+            let mut next = n;
+            b.iter(|| {
+                map.remove(&(next - n));
+                map.insert(next, next);
+                next += 1;
+            })
+        }
+    };
+    c.bench_functions(
+        "remove_churn",
+        vec![
+            Fun::new("hashmap_linear", churn_hashmap(ProbeStrategy::Linear)),
+            Fun::new("hashmap_quadratic", churn_hashmap(ProbeStrategy::Quadratic)),
+            Fun::new(
+                "hashmap_robin_hood",
+                churn_hashmap(ProbeStrategy::RobinHood),
+            ),
+            Fun::new("std", |b, &n: &u64| {
+                let mut map: StdHashMap<u64, u64> = (0..n).map(|key| (key, key)).collect();
+                let mut next = n;
+                b.iter(|| {
+                    map.remove(&(next - n));
+                    map.insert(next, next);
+                    next += 1;
+                })
+            }),
+        ],
+        KEYS,
+    );
+
+    let map: HashMap<u64, u64> = (0..KEYS).map(|key| (key, key)).collect();
+    let std_map: StdHashMap<u64, u64> = (0..KEYS).map(|key| (key, key)).collect();
+    c.bench_functions(
+        "iterate",
+        vec![
+            Fun::new("hashmap", move |b, _: &()| {
+                b.iter(|| map.iter().map(|(_, value)| value).sum::<u64>())
+            }),
+            Fun::new("std", move |b, _: &()| {
+                b.iter(|| std_map.values().sum::<u64>())
+            }),
+        ],
+        (),
+    );
+}
+
+// Looking up keys whose values are big, where probing through the slots
+// themselves would drag all of them through the cache. Against the layout
+// from before the control bytes, run alternately five times each, the
+// median miss went from 530us to 455us, while hits stayed within noise
+// (441us to 421us).
+fn large_values_benchmark(c: &mut Criterion) {
+    let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+    for key in 0..KEYS {
+        map.insert(key, [key; 32]);
+    }
+    let map = std::rc::Rc::new(map);
+    let hits = map.clone();
+    c.bench_function("get_large_values_hit", move |b| {
+        b.iter(|| (0..KEYS).map(|key| hits.get(&key).unwrap()[0]).sum::<u64>())
+    });
+    c.bench_function("get_large_values_miss", move |b| {
+        b.iter(|| {
+            (KEYS..2 * KEYS)
+                .filter(|key| map.get(key).is_some())
+                .count()
+        })
+    });
+}
+
+// Going through a map which had 100k keys and has 10k left, where a
+// HashMap still has to look at every slot of its table, and a DenseMap only
+// at the entries left.
+fn sparse_iteration_benchmark(c: &mut Criterion) {
+    let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+    let mut dense = DenseMap::with_hasher(FixedHashBuilder::default());
+    for key in 0..10 * KEYS {
+        map.insert(key, key);
+        dense.insert(key, key);
+    }
+    for key in (0..10 * KEYS).filter(|key| key % 10 != 0) {
+        map.remove(&key);
+        dense.remove(&key);
+    }
+    c.bench_functions(
+        "iterate_sparse",
+        vec![
+            Fun::new("hashmap", move |b, _: &()| {
+                b.iter(|| map.values().sum::<u64>())
+            }),
+            Fun::new("dense", move |b, _: &()| {
+                b.iter(|| dense.values().sum::<u64>())
+            }),
+        ],
+        (),
+    );
+}
+
+// Putting long String keys into a map with room for them, where hashing
+// each key is a good part of the cost of inserting it.
+fn string_insert_benchmark(c: &mut Criterion) {
+    let keys: Vec<String> = (0..10_000)
+        .map(|i| format!("a fairly long string key, number {}", i))
+        .collect();
+    c.bench_function("insert_strings", move |b| {
+        b.iter_batched(
+            || keys.clone(),
+            |keys| {
+                let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+                map.reserve(keys.len());
+                for key in keys {
+                    map.insert(key, ());
+                }
+                map
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+// Growing a map of long String keys, where hashing them all again would be
+// most of the work. Filling each map takes a while, so this takes fewer
+// samples.
+fn grow_benchmark(c: &mut Criterion) {
+    let keys: Vec<String> = (0..100_000)
+        .map(|i| format!("a fairly long string key, number {}", i))
+        .collect();
+    c.bench_function("grow_strings", move |b| {
+        b.iter_batched(
+            || {
+                let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+                for key in &keys {
+                    map.insert(key.clone(), ());
+                }
+                map
+            },
+            |mut map| {
+                map.grow(map.raw_capacity() * 2);
+                map
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    std_benchmark,
+    large_values_benchmark,
+    sparse_iteration_benchmark,
+    string_insert_benchmark
+);
+criterion_group! {
+    name = grow;
+    config = Criterion::default().sample_size(10);
+    targets = grow_benchmark
+}
+criterion_main!(benches, grow);
@@ -0,0 +1,60 @@
+// Runs a sequence of operations on small integer keys against both this map
+// and std's, checking they agree and that the map's table stays consistent.
+// Run with `cargo fuzz run map_ops` from the hashmap directory.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use hashmap::hashmap::{GrowthPolicy, HashMap, ProbeStrategy};
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap as StdHashMap;
+
+#[derive(Arbitrary, Debug)]
+enum Setup {
+    Linear,
+    Prime,
+    Quadratic,
+    RobinHood,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Insert(u8, u16),
+    Get(u8),
+    Remove(u8),
+    Clear,
+    Reserve(u8),
+    Grow(u8),
+}
+
+fuzz_target!(|input: (Setup, Vec<Op>)| {
+    let (setup, ops) = input;
+    let (policy, strategy) = match setup {
+        Setup::Linear => (GrowthPolicy::Doubling, ProbeStrategy::Linear),
+        Setup::Prime => (GrowthPolicy::Prime, ProbeStrategy::Linear),
+        Setup::Quadratic => (GrowthPolicy::PowerOfTwo, ProbeStrategy::Quadratic),
+        Setup::RobinHood => (GrowthPolicy::PowerOfTwo, ProbeStrategy::RobinHood),
+    };
+    let mut map = HashMap::with_growth_policy(0, policy);
+    map.set_probe_strategy(strategy);
+    let mut std_map = StdHashMap::new();
+
+    for op in ops {
+        match op {
+            Op::Insert(key, value) => assert_eq!(map.insert(key, value), std_map.insert(key, value)),
+            Op::Get(key) => assert_eq!(map.get(&key), std_map.get(&key)),
+            Op::Remove(key) => assert_eq!(map.remove(&key), std_map.remove(&key)),
+            Op::Clear => {
+                map.clear();
+                std_map.clear();
+            }
+            Op::Reserve(additional) => map.reserve(additional as usize),
+            Op::Grow(extra) => map.grow(map.len() + extra as usize + 1),
+        }
+        assert_eq!(map.len(), std_map.len());
+        map.assert_invariants();
+    }
+
+    for (key, value) in &std_map {
+        assert_eq!(map.get(key), Some(value));
+    }
+});
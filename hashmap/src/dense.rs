@@ -0,0 +1,462 @@
+use crate::hashmap::{
+    table_size_for, usable_capacity, DefaultHashBuilder, INITIAL_SIZE, MAX_LOAD_FACTOR,
+};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+use std::vec;
+
+// A map laid out for iterating over, rather than HashMap's one table of
+// slots. The keys and values sit side by side in a Vec with no gaps, and the
+// probe table only holds each key's hash and where it is in that Vec. Going
+// through the map then only touches live entries, however empty the probe
+// table has got, and the probe table stays small even when the values are
+// big.
+//
+// The probe table uses linear probing, and removing shifts keys back, so it
+// never has tombstones. Removing swaps the last entry into the gap, which
+// changes the order of the entries and means one index in the probe table
+// has to be patched.
+pub struct DenseMap<K: Hash + Eq, V, S = DefaultHashBuilder> {
+    // Each slot is a key's hash and the index of its entry, or None
+    indices: Vec<Option<(u64, usize)>>,
+    entries: Vec<(K, V)>,
+    hash_builder: S,
+}
+
+impl<K: Hash + Eq, V> DenseMap<K, V> {
+    pub fn new() -> DenseMap<K, V> {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    // Makes a map which can hold `capacity` entries before it has to grow.
+    pub fn with_capacity(capacity: usize) -> DenseMap<K, V> {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> DenseMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> DenseMap<K, V, S> {
+        DenseMap {
+            indices: Vec::new(),
+            entries: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> DenseMap<K, V, S> {
+        let mut map = Self::with_hasher(hash_builder);
+        if capacity > 0 {
+            map.entries.reserve(capacity);
+            map.rebuild(table_size_for(capacity, MAX_LOAD_FACTOR));
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // How many entries the map can hold before it has to grow.
+    pub fn capacity(&self) -> usize {
+        usable_capacity(self.raw_capacity(), MAX_LOAD_FACTOR)
+    }
+
+    // The number of slots in the probe table.
+    pub fn raw_capacity(&self) -> usize {
+        self.indices.len()
+    }
+
+    // The entries, in the order they're kept in, which is the order they
+    // were put in apart from where removals have moved the last one.
+    pub fn as_slice(&self) -> &[(K, V)] {
+        &self.entries
+    }
+
+    // The slot holding `key`, or the empty slot it would go in.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut i = hash as usize % self.raw_capacity();
+        loop {
+            match self.indices[i] {
+                None => return Err(i),
+                Some((h, index)) if h == hash && self.entries[index].0.borrow() == key => {
+                    return Ok(i)
+                }
+                Some(_) => i = (i + 1) % self.raw_capacity(),
+            }
+        }
+    }
+
+    // Finds `key`, giving its slot and the index of its entry.
+    fn search<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let slot = self.find(self.hash_builder.hash_one(key), key).ok()?;
+        let (_, index) = self.indices[slot].unwrap();
+        Some((slot, index))
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.len() >= self.capacity() {
+            self.rebuild((self.raw_capacity() * 2 + 1).max(INITIAL_SIZE));
+        }
+        let hash = self.hash_builder.hash_one(&key);
+        match self.find(hash, &key) {
+            Ok(slot) => {
+                let (_, index) = self.indices[slot].unwrap();
+                Some(mem::replace(&mut self.entries[index].1, value))
+            }
+            Err(slot) => {
+                self.indices[slot] = Some((hash, self.entries.len()));
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, index) = self.search(key)?;
+        Some(&self.entries[index].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, index) = self.search(key)?;
+        Some(&mut self.entries[index].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.search(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    // Like remove, but gives back the key that was stored as well.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (slot, index) = self.search(key)?;
+        self.indices[slot] = None;
+        self.shift_back(slot);
+
+        // The last entry moves into the gap, so its slot has to point there
+        // instead.
+        let last = self.entries.len() - 1;
+        if index != last {
+            let hash = self.hash_builder.hash_one(&self.entries[last].0);
+            let mut i = hash as usize % self.raw_capacity();
+            while self.indices[i] != Some((hash, last)) {
+                i = (i + 1) % self.raw_capacity();
+            }
+            self.indices[i] = Some((hash, index));
+        }
+        Some(self.entries.swap_remove(index))
+    }
+
+    // Fills the empty slot at `hole` from the run of keys after it, as
+    // HashMap does with linear probing.
+    fn shift_back(&mut self, mut hole: usize) {
+        let mut next = (hole + 1) % self.raw_capacity();
+        while let Some((hash, _)) = self.indices[next] {
+            let home = hash as usize % self.raw_capacity();
+            let distance = (next + self.raw_capacity() - home) % self.raw_capacity();
+            let gap = (next + self.raw_capacity() - hole) % self.raw_capacity();
+            if distance >= gap {
+                self.indices.swap(hole, next);
+                hole = next;
+            }
+            next = (next + 1) % self.raw_capacity();
+        }
+    }
+
+    // Makes a new probe table of `size` slots, using the hashes in the old
+    // one, so no key is hashed again and no entry moves.
+    fn rebuild(&mut self, size: usize) {
+        let old = mem::replace(&mut self.indices, vec![None; size]);
+        for (hash, index) in old.into_iter().flatten() {
+            let mut i = hash as usize % size;
+            while self.indices[i].is_some() {
+                i = (i + 1) % size;
+            }
+            self.indices[i] = Some((hash, index));
+        }
+    }
+
+    // Removes everything, keeping the probe table.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for slot in self.indices.iter_mut() {
+            *slot = None;
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: self.entries.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            entries: self.entries.iter_mut(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.entries.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> + '_ {
+        self.entries.iter_mut().map(|(_, value)| value)
+    }
+}
+
+// An iterator over the keys and values of a map. See DenseMap::iter.
+pub struct Iter<'a, K, V> {
+    entries: slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.entries.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+// An iterator over the keys of a map and mutable references to its values.
+// See DenseMap::iter_mut.
+pub struct IterMut<'a, K, V> {
+    entries: slice::IterMut<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.entries.next().map(|(key, value)| (&*key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K: Hash + Eq, V, S> IntoIterator for DenseMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> vec::IntoIter<(K, V)> {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> IntoIterator for &'a DenseMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug, S: BuildHasher> fmt::Debug for DenseMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for DenseMap<K, V, S> {
+    fn default() -> DenseMap<K, V, S> {
+        DenseMap::with_hasher(S::default())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for DenseMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> DenseMap<K, V, S> {
+        let mut map = DenseMap::default();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+// Dense maps serialize straight from their entries, as plain maps.
+#[cfg(feature = "serde")]
+impl<K, V, S> serde::Serialize for DenseMap<K, V, S>
+where
+    K: Hash + Eq + serde::Serialize,
+    V: serde::Serialize,
+    S: BuildHasher,
+{
+    fn serialize<T: serde::Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DenseMap;
+    use crate::hashmap::FixedHashBuilder;
+    use std::hash::BuildHasher;
+
+    // Checks every slot points at an entry with its key, and every entry
+    // has exactly one slot.
+    fn check(map: &DenseMap<u32, u32, FixedHashBuilder>) {
+        let mut seen = vec![false; map.len()];
+        for &(hash, index) in map.indices.iter().flatten() {
+            let key = &map.entries[index].0;
+            assert_eq!(hash, map.hash_builder.hash_one(key), "entry {}", index);
+            assert!(!seen[index], "entry {} has two slots", index);
+            seen[index] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        for (key, _) in &map.entries {
+            assert_eq!(map.get(key), Some(&(key * 10)));
+        }
+    }
+
+    #[test]
+    fn matches_std() {
+        let mut map = DenseMap::with_hasher(FixedHashBuilder::default());
+        let mut std_map = std::collections::HashMap::new();
+        for x in 0..500u32 {
+            let key = x * 7 % 101;
+            if x % 3 == 0 {
+                assert_eq!(map.remove(&key), std_map.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, key * 10), std_map.insert(key, key * 10));
+            }
+            assert_eq!(map.len(), std_map.len());
+        }
+        check(&map);
+
+        let mut items: Vec<(u32, u32)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        let mut expected: Vec<(u32, u32)> = std_map.into_iter().collect();
+        items.sort();
+        expected.sort();
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn removal_patches_indices() {
+        let mut map = DenseMap::with_hasher(FixedHashBuilder::default());
+        for x in 0..10 {
+            map.insert(x, x * 10);
+        }
+
+        // The last entry moves into the removed one's place.
+        assert_eq!(map.remove(&3), Some(30));
+        assert_eq!(map.as_slice()[3], (9, 90));
+        check(&map);
+
+        // Removing the last entry doesn't move any.
+        assert_eq!(map.remove(&8), Some(80));
+        let keys: Vec<u32> = map.keys().copied().collect();
+        assert_eq!(keys, &[0, 1, 2, 9, 4, 5, 6, 7]);
+        check(&map);
+
+        assert_eq!(map.remove_entry(&0), Some((0, 0)));
+        assert_eq!(map.remove(&0), None);
+        assert_eq!(map.as_slice()[0], (7, 70));
+        check(&map);
+
+        // Empty it from the front, so the last entry moves every time.
+        while let Some(&(key, _)) = map.as_slice().first() {
+            map.remove(&key);
+            check(&map);
+        }
+        assert!(map.is_empty());
+        assert!(map.indices.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn iterates_live_entries() {
+        let mut map = DenseMap::with_hasher(FixedHashBuilder::default());
+        for x in 0..1000 {
+            map.insert(x, x * 10);
+        }
+        let capacity = map.capacity();
+        for x in 0..1000 {
+            if x % 10 != 0 {
+                map.remove(&x);
+            }
+        }
+
+        // The probe table is as big as it was, but only the live entries
+        // are left to go through.
+        assert_eq!(map.capacity(), capacity);
+        assert_eq!(map.as_slice().len(), 100);
+        for (_, value) in map.iter_mut() {
+            *value += 1;
+        }
+        let sum: u32 = map.values().sum();
+        assert_eq!(sum, (0..1000).step_by(10).map(|x| x * 10 + 1).sum::<u32>());
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&10), None);
+        map.insert(10, 100);
+        check(&map);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_entries() {
+        let mut map = DenseMap::with_hasher(FixedHashBuilder::default());
+        for x in 0..5 {
+            map.insert(x.to_string(), x);
+        }
+        map.remove("1");
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"{"0":0,"4":4,"2":2,"3":3}"#);
+    }
+}
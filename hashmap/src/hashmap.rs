@@ -1,137 +1,934 @@
-use std::collections::hash_map::DefaultHasher;
+use std::borrow::Borrow;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+use std::iter::FromIterator;
 use std::mem;
+use std::ops::{Index, IndexMut};
+use std::slice;
+use std::vec;
 
 pub const INITIAL_SIZE: usize = 13;
 pub const MAX_LOAD_FACTOR: f32 = 0.67;
 
-// Entry defines the possible states of an index in the backing table:
+// What maps hash keys with, unless they're given something else. Each one
+// is seeded differently, so that keys land in different slots from map to
+// map and run to run, and nobody can pick keys which are sure to collide.
+pub type DefaultHashBuilder = RandomState;
+
+// Hashes keys the same way every time, for when results have to be
+// reproducible, like in benchmarks:
+//
+//     let map: HashMap<u32, u32, FixedHashBuilder> = HashMap::default();
+//
+// Only use it for keys nobody else gets to choose.
+pub type FixedHashBuilder = BuildHasherDefault<DefaultHasher>;
+
+// How the table's size is picked, when it grows and when it's made to hold
+// some number of items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GrowthPolicy {
+    // Any size, growing to 2 * capacity + 1. That keeps sizes odd, but they
+    // can still share factors with the keys' hashes, like 27 does with
+    // multiples of 9, and then those keys only start from a few slots.
+    #[default]
+    Doubling,
+
+    // Only sizes from PRIMES, each one a little over double the last, so
+    // keys whose hashes share a factor still spread over every slot.
+    Prime,
+
+    // Only powers of two, doubling each time. Slots are then picked with a
+    // mask rather than a division, which is quicker, but a mask only keeps
+    // the low bits of the hash, so hashes are mixed up first.
+    PowerOfTwo,
+}
+
+// 2^64 divided by the golden ratio. Multiplying by it spreads every bit of
+// a hash into the top bits of the product, which is Fibonacci hashing.
+const FIBONACCI_MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// The table sizes GrowthPolicy::Prime picks from: the first prime over
+// 2 * p + 1 for each p, starting from INITIAL_SIZE.
+const PRIMES: [u64; 60] = [
+    13,
+    29,
+    59,
+    127,
+    257,
+    521,
+    1049,
+    2099,
+    4201,
+    8419,
+    16843,
+    33703,
+    67409,
+    134837,
+    269683,
+    539389,
+    1078787,
+    2157587,
+    4315183,
+    8630387,
+    17260781,
+    34521589,
+    69043189,
+    138086407,
+    276172823,
+    552345671,
+    1104691373,
+    2209382761,
+    4418765551,
+    8837531111,
+    17675062243,
+    35350124489,
+    70700248981,
+    141400498003,
+    282800996033,
+    565601992079,
+    1131203984191,
+    2262407968397,
+    4524815936827,
+    9049631873719,
+    18099263747489,
+    36198527495003,
+    72397054990021,
+    144794109980101,
+    289588219960211,
+    579176439920423,
+    1158352879840853,
+    2316705759681737,
+    4633411519363499,
+    9266823038727097,
+    18533646077454271,
+    37067292154908571,
+    74134584309817169,
+    148269168619634339,
+    296538337239268717,
+    593076674478537461,
+    1186153348957075027,
+    2372306697914150057,
+    4744613395828300123,
+    9489226791656600279,
+];
+
+impl GrowthPolicy {
+    // The smallest size this policy allows that's at least `size`, or None
+    // if there isn't one that fits in a usize.
+    fn round_up(self, size: usize) -> Option<usize> {
+        match self {
+            GrowthPolicy::Doubling => Some(size),
+            GrowthPolicy::Prime => PRIMES
+                .iter()
+                .find(|&&prime| prime >= size as u64)
+                .and_then(|&prime| usize::try_from(prime).ok()),
+            GrowthPolicy::PowerOfTwo => size.checked_next_power_of_two(),
+        }
+    }
+
+    // The size a table of `capacity` slots grows to once it's too full.
+    fn grown(self, capacity: usize) -> usize {
+        let size = match self {
+            GrowthPolicy::PowerOfTwo => capacity.checked_mul(2),
+            _ => self.round_up(2 * capacity + 1),
+        };
+        size.expect("capacity overflow")
+    }
+}
+
+// The order a key's probe visits slots in, starting from its home slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProbeStrategy {
+    // The next slot along each time. Simple and cache friendly, but keys
+    // that collide build up into long runs which other keys then have to
+    // probe past.
+    #[default]
+    Linear,
+
+    // Steps of 1, 2, 3... so the nth slot is home + n(n + 1)/2, which
+    // scatters colliding keys rather than lining them up. In a power-of-two
+    // table that visits every slot once, so it's only allowed with
+    // GrowthPolicy::PowerOfTwo.
+    Quadratic,
+
+    // Linear, but a new key takes the slot of any key it passes which is
+    // nearer its own home, and that key moves on instead. Keys end up about
+    // as far from home as each other, so the worst lookups are much less
+    // bad, and a lookup can stop as soon as it passes a key nearer home
+    // than it would be. Removing a key shifts the rest of its run back a
+    // slot, so there are never any tombstones.
+    RobinHood,
+}
+
+// The table size needed to hold `len` items without going over
+// `max_load_factor`, and so without growing.
+pub(crate) fn table_size_for(len: usize, max_load_factor: f32) -> usize {
+    checked_table_size_for(len, max_load_factor).expect("capacity overflow")
+}
+
+// Like table_size_for, but None if the size doesn't fit in a usize.
+fn checked_table_size_for(len: usize, max_load_factor: f32) -> Option<usize> {
+    let size = (len as f64 / max_load_factor as f64).ceil();
+    if size >= usize::MAX as f64 {
+        return None;
+    }
+
+    // Rounding can leave that a few slots short of what usable_capacity
+    // says holds `len` items, and the two have to agree.
+    let mut size = size as usize;
+    while usable_capacity(size, max_load_factor) < len {
+        size = size.checked_add(1)?;
+    }
+    Some(size.max(INITIAL_SIZE))
+}
+
+// How many items a table of `size` slots holds without going over
+// `max_load_factor`.
+pub(crate) fn usable_capacity(size: usize, max_load_factor: f32) -> usize {
+    (size as f64 * max_load_factor as f64) as usize
+}
+
+// Why try_reserve couldn't make room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryReserveError {
+    // The table would need more slots, or more bytes, than fit in a usize.
+    CapacityOverflow,
+    // The allocator couldn't give us the memory for the new table.
+    AllocError,
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => write!(f, "capacity overflow"),
+            TryReserveError::AllocError => write!(f, "memory allocation failed"),
+        }
+    }
+}
+
+impl Error for TryReserveError {}
+
+// Slot defines the possible states of an index in the backing table:
 //  - None, if there has never been anything at that index.
 //  - Removed, if there was an item there in the past, which has since been removed.
-//  - Some, if there is currently an item there.
+//    Only quadratic probing leaves these; otherwise removing shifts keys back.
+//  - Some, if there is currently an item there. The key's hash is kept with
+//    it, so growing doesn't have to hash every key again, and probes can
+//    skip keys with a different hash without comparing them.
 #[derive(Debug)]
-enum Entry<K, V> {
+enum Slot<K, V> {
     None,
     Removed,
-    Some(K, V),
+    Some(u64, K, V),
 }
 
-impl<K, V> Entry<K, V> {
+impl<K, V> Slot<K, V> {
+    pub fn value(&self) -> &V {
+        match self {
+            Slot::Some(_, _, value) => value,
+            _ => panic!("unexpected non-value Slot found"),
+        }
+    }
+
     pub fn mut_value(&mut self) -> &mut V {
         match self {
-            Entry::Some(_, value) => value,
-            _ => panic!("unexpected non-value Entry found"),
+            Slot::Some(_, _, value) => value,
+            _ => panic!("unexpected non-value Slot found"),
         }
     }
 
     pub fn into_value(self) -> V {
         match self {
-            Entry::Some(_, value) => value,
-            _ => panic!("unexpected non-value Entry found"),
+            Slot::Some(_, _, value) => value,
+            _ => panic!("unexpected non-value Slot found"),
+        }
+    }
+
+    pub fn into_pair(self) -> (K, V) {
+        match self {
+            Slot::Some(_, key, value) => (key, value),
+            _ => panic!("unexpected non-value Slot found"),
+        }
+    }
+}
+
+// Each slot also has a control byte, kept in a separate, much smaller table,
+// so probes can go along it without loading the slots themselves, which can
+// be big. A control byte is EMPTY for Slot::None, DELETED for Slot::Removed,
+// or the top 7 bits of the key's hash for Slot::Some, so that a probe only
+// has to look at the slots whose keys might match. This checks a byte at a
+// time, but the bytes are laid out so a group of them could be matched at
+// once with SIMD, as in Swiss tables.
+const EMPTY: u8 = 0b1000_0000;
+const DELETED: u8 = 0b1111_1110;
+
+// The control byte for a slot holding a key with this hash.
+fn control_byte(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+impl<K, V> Slot<K, V> {
+    fn control(&self) -> u8 {
+        match self {
+            Slot::None => EMPTY,
+            Slot::Removed => DELETED,
+            Slot::Some(hash, ..) => control_byte(*hash),
         }
     }
 }
 
-pub struct HashMap<K: Hash + Eq + Copy, V> {
+// A table of slots, and their control bytes.
+type Table<K, V> = (Vec<Slot<K, V>>, Vec<u8>);
+
+pub struct HashMap<K: Hash + Eq, V, S = DefaultHashBuilder> {
     // Store the backing table on the heap
-    table: Vec<Entry<K, V>>,
+    table: Vec<Slot<K, V>>,
+
+    // The control byte for each slot in table
+    control: Vec<u8>,
 
     // Store the number of Some{...} elements
     size: usize,
+
+    // Store the number of Some{...} and Removed elements, which is how full
+    // the table is as far as probing is concerned. Only quadratic probing
+    // leaves Removed slots, so otherwise this is the same as size.
+    used: usize,
+
+    // Makes the hashers used to hash keys
+    hash_builder: S,
+
+    // How full the table can get before it grows, MAX_LOAD_FACTOR unless
+    // the map was made with with_load_factor
+    max_load_factor: f32,
+
+    // Picks the table's size
+    growth_policy: GrowthPolicy,
+
+    // Picks the slots a probe goes through
+    probe_strategy: ProbeStrategy,
 }
 
 enum SearchResult {
     Found(usize), // key was found, it's at this index.
     Empty(usize), // key was not found, an empty space suitable for it at this index.
+    Full,         // key was not found, and there's nowhere to put it.
 }
 
-// TODO: Remove the Debug requirement
-impl<K: Hash + Eq + Copy + fmt::Debug, V: fmt::Debug> HashMap<K, V> {
+impl<K: Hash + Eq, V> HashMap<K, V> {
     pub fn new() -> HashMap<K, V> {
-        HashMap {
-            table: Self::allocate_table(INITIAL_SIZE),
-            size: 0,
-        }
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    // Makes a map which can hold `capacity` items before it has to grow.
+    pub fn with_capacity(capacity: usize) -> HashMap<K, V> {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
     }
 
+    // Makes a map with a table of exactly `capacity` slots, which is full
+    // well before they're all used.
+    #[deprecated(note = "use with_capacity, which allows for the load factor")]
     pub fn new_capacity(capacity: usize) -> HashMap<K, V> {
+        Self::with_table_size(capacity, DefaultHashBuilder::default())
+    }
+
+    // Makes a map which can hold `capacity` items, and which grows once
+    // more than `max_load_factor` of its slots are full, rather than
+    // MAX_LOAD_FACTOR. Lower factors make for shorter probe chains but
+    // bigger tables. The factor has to be strictly between 0 and 1.
+    pub fn with_load_factor(capacity: usize, max_load_factor: f32) -> HashMap<K, V> {
+        assert!(
+            max_load_factor > 0.0 && max_load_factor < 1.0,
+            "max load factor must be between 0 and 1, not {}",
+            max_load_factor
+        );
+        let mut map = Self::new();
+        map.max_load_factor = max_load_factor;
+        map.reserve(capacity);
+        map
+    }
+
+    // Makes a map which can hold `capacity` items, whose table only ever
+    // has the sizes `policy` allows.
+    pub fn with_growth_policy(capacity: usize, policy: GrowthPolicy) -> HashMap<K, V> {
+        let mut map = Self::new();
+        map.set_growth_policy(policy);
+        map.reserve(capacity);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashMap<K, V, S> {
+    // Makes a map which hashes its keys with hashers from `hash_builder`.
+    // Like every empty map, it doesn't allocate a table until something is
+    // put in it.
+    pub fn with_hasher(hash_builder: S) -> HashMap<K, V, S> {
+        Self::with_table_size(0, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> HashMap<K, V, S> {
+        if capacity == 0 {
+            return Self::with_hasher(hash_builder);
+        }
+        Self::with_table_size(table_size_for(capacity, MAX_LOAD_FACTOR), hash_builder)
+    }
+
+    fn with_table_size(size: usize, hash_builder: S) -> HashMap<K, V, S> {
+        let (table, control) = Self::allocate_table(size);
         HashMap {
-            table: Self::allocate_table(capacity),
+            table,
+            control,
             size: 0,
+            used: 0,
+            hash_builder,
+            max_load_factor: MAX_LOAD_FACTOR,
+            growth_policy: GrowthPolicy::default(),
+            probe_strategy: ProbeStrategy::default(),
         }
     }
 
     // Allocates a backing table of the given size, on the heap, filling it
-    // by default with Entry::None.
-    fn allocate_table(size: usize) -> Vec<Entry<K, V>> {
-        // New vector, setting each entry to Entry::None by default.
-        let mut vec: Vec<Entry<K, V>> = Vec::with_capacity(size);
+    // by default with Slot::None, and its control bytes with EMPTY.
+    fn allocate_table(size: usize) -> Table<K, V> {
+        // New vector, setting each slot to Slot::None by default.
+        let mut vec: Vec<Slot<K, V>> = Vec::with_capacity(size);
+        for _ in 0..size {
+            vec.push(Slot::None);
+        }
+        (vec, vec![EMPTY; size])
+    }
+
+    // Like allocate_table, but returning an error instead of panicking or
+    // aborting if the memory isn't there.
+    fn try_allocate_table(size: usize) -> Result<Table<K, V>, TryReserveError> {
+        let bytes = size.checked_mul(mem::size_of::<Slot<K, V>>() + 1);
+        if bytes.is_none_or(|bytes| bytes > isize::MAX as usize) {
+            return Err(TryReserveError::CapacityOverflow);
+        }
+
+        let mut vec: Vec<Slot<K, V>> = Vec::new();
+        let mut control: Vec<u8> = Vec::new();
+        vec.try_reserve_exact(size)
+            .map_err(|_| TryReserveError::AllocError)?;
+        control
+            .try_reserve_exact(size)
+            .map_err(|_| TryReserveError::AllocError)?;
         for _ in 0..size {
-            vec.push(Entry::None);
+            vec.push(Slot::None);
         }
-        vec
+        control.resize(size, EMPTY);
+        Ok((vec, control))
     }
 
     pub fn len(&self) -> usize {
         self.size
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // How many items the map can hold before it has to grow.
     pub fn capacity(&self) -> usize {
+        usable_capacity(self.raw_capacity(), self.max_load_factor)
+    }
+
+    // The number of slots in the table, which is more than capacity(),
+    // since the table's never let get fuller than the max load factor.
+    pub fn raw_capacity(&self) -> usize {
         self.table.len()
     }
 
-    fn search(&self, key: &K) -> SearchResult {
-        // Calculate the hash of the key
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        let hash: u64 = hasher.finish();
+    // How full the table is, as the fraction of slots holding an item. It's
+    // 0 for a map with no table yet.
+    pub fn load_factor(&self) -> f32 {
+        if self.raw_capacity() == 0 {
+            return 0.0;
+        }
+        self.len() as f32 / self.raw_capacity() as f32
+    }
+
+    // How many slots hold a tombstone left behind by a removal. Lookups
+    // have to probe past them, though new keys can reuse them. Only
+    // quadratic probing leaves any.
+    pub fn tombstone_count(&self) -> usize {
+        self.used - self.size
+    }
+
+    // How many slots hold an item or a tombstone. It's this, rather than
+    // len(), that decides when the table's rebuilt.
+    pub fn occupied_slots(&self) -> usize {
+        self.used
+    }
+
+    // The hasher keys are hashed with, for working out the hashes to hand
+    // to insert_hashed.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    // How many slots past its home slot a lookup of `key` has to go to find
+    // it, or None if it's not in the map.
+    pub fn probe_length<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.probe(self.hash_builder.hash_one(key), key) {
+            (SearchResult::Found(_), scan) => Some(scan),
+            _ => None,
+        }
+    }
+
+    // The longest probe_length of any key in the map.
+    pub fn max_probe_length(&self) -> usize {
+        self.keys()
+            .map(|key| self.probe_length(key).unwrap())
+            .max()
+            .unwrap_or(0)
+    }
 
-        let mut first_available: Option<usize> = None;
+    // The mean probe_length of the keys in the map, or 0 if it's empty.
+    pub fn average_probe_length(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let total: usize = self.keys().map(|key| self.probe_length(key).unwrap()).sum();
+        total as f32 / self.len() as f32
+    }
 
-        for scan in 0..self.capacity() {
-            let i = (hash as usize + scan) % self.capacity();
-            let entry = &self.table[i];
+    // The table written out a slot to a line, for seeing how keys have been
+    // probed into place. Each key is shown with its home slot, and its
+    // distance, which is how many slots past home a lookup goes to find it.
+    pub fn debug_dump(&self) -> String
+    where
+        K: fmt::Debug,
+    {
+        use std::fmt::Write;
 
-            if let Entry::None | Entry::Removed = entry {
-                first_available = first_available.or(Some(i));
+        let width = self.raw_capacity().saturating_sub(1).to_string().len();
+        let mut out = String::new();
+        for (index, slot) in self.table.iter().enumerate() {
+            match slot {
+                Slot::None => writeln!(out, "{:>width$}: None", index, width = width),
+                Slot::Removed => writeln!(out, "{:>width$}: Removed", index, width = width),
+                Slot::Some(hash, key, _) => writeln!(
+                    out,
+                    "{:>width$}: Some {:?}, home {}, distance {}",
+                    index,
+                    key,
+                    self.home_slot(*hash),
+                    self.probe(*hash, key).1,
+                    width = width
+                ),
             }
+            .unwrap();
+        }
+        out
+    }
+
+    // Checks that the table agrees with itself, panicking if it doesn't:
+    // the counts match the slots, only quadratic probing leaves tombstones,
+    // Robin Hood keys are in order, and every key is found in the slot it's
+    // in. This looks up every key, so it's for tests and fuzzing.
+    pub fn assert_invariants(&self) {
+        let items = self
+            .table
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Some(..)))
+            .count();
+        let removed = self
+            .table
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Removed))
+            .count();
+        assert_eq!(self.size, items, "size doesn't match the slots with items");
+        assert_eq!(
+            self.used,
+            items + removed,
+            "used doesn't match the slots used"
+        );
+        assert!(
+            self.used <= self.capacity(),
+            "more slots used than the load factor allows"
+        );
+        let controls = self.table.iter().map(Slot::control);
+        assert!(
+            controls.eq(self.control.iter().copied()),
+            "control bytes don't match the slots"
+        );
+        if self.probe_strategy != ProbeStrategy::Quadratic {
+            assert_eq!(removed, 0, "only quadratic probing leaves tombstones");
+        }
 
-            match entry {
-                // We've found the item at `key`. Return it.
-                Entry::Some(k, _v) if k == key => return SearchResult::Found(i),
+        for (index, slot) in self.table.iter().enumerate() {
+            let (hash, key) = match slot {
+                Slot::Some(hash, key, _) => (*hash, key),
+                _ => continue,
+            };
+            let rehashed = self.hash_builder.hash_one(key);
+            assert_eq!(hash, rehashed, "key in slot {} has the wrong hash", index);
+            match self.search_hashed(hash, key) {
+                SearchResult::Found(found) => {
+                    assert_eq!(found, index, "key in slot {} found elsewhere", index)
+                }
+                _ => panic!("key in slot {} can't be found", index),
+            }
 
-                // If we find an empty item, break.
-                Entry::None => {
-                    break;
+            let next = (index + 1) % self.raw_capacity();
+            if self.probe_strategy == ProbeStrategy::RobinHood {
+                if let Slot::Some(..) = self.table[next] {
+                    assert!(
+                        self.probe_distance(next) <= self.probe_distance(index) + 1,
+                        "key in slot {} is further from home than Robin Hood allows",
+                        next
+                    );
                 }
+            }
+        }
+    }
+
+    // Looks for `key`, which can be any borrowed form of K whose hash and
+    // equality agree with K's, like &str for String keys.
+    fn search<Q>(&self, key: &Q) -> SearchResult
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.search_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    // Like search, for a key whose hash is already known, so that callers
+    // which go on to insert the key don't have to hash it again.
+    fn search_hashed<Q>(&self, hash: u64, key: &Q) -> SearchResult
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.probe(hash, key).0
+    }
+
+    // Does the work of search, also saying how many slots it went past the
+    // key's home slot before it stopped.
+    fn probe<Q>(&self, hash: u64, key: &Q) -> (SearchResult, usize)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        // There's nothing to find in an empty table, or anywhere to put it.
+        if self.table.is_empty() {
+            return (SearchResult::Full, 0);
+        }
+
+        let home = self.home_slot(hash);
+        let wanted = control_byte(hash);
+
+        // The first tombstone passed, which a new key can go in.
+        let mut first_removed: Option<usize> = None;
+
+        for scan in 0..self.raw_capacity() {
+            let i = self.probe_slot(home, scan);
+            match self.control[i] {
+                // Probes stop at an empty slot, so the key isn't here.
+                EMPTY => return (SearchResult::Empty(first_removed.unwrap_or(i)), scan),
+
+                // Keep going past tombstones.
+                DELETED => first_removed = first_removed.or(Some(i)),
 
-                // Ignore removed entries and other entries, if first_available is set.
-                Entry::Some(..) | Entry::Removed => (),
+                // Only a slot whose control byte matches can hold the key,
+                // so only those get looked at.
+                control => {
+                    if control == wanted {
+                        if let Slot::Some(h, k, _) = &self.table[i] {
+                            if *h == hash && k.borrow() == key {
+                                return (SearchResult::Found(i), scan);
+                            }
+                        }
+                    }
+
+                    // Under Robin Hood, a key that's nearer home than we
+                    // would be here would have been moved on for ours, so
+                    // ours isn't in the table, and this is where it goes.
+                    if self.probe_strategy == ProbeStrategy::RobinHood
+                        && self.probe_distance(i) < scan
+                    {
+                        return (SearchResult::Empty(i), scan);
+                    }
+                }
             };
         }
 
-        // Panic if we can't find an empty space.
-        SearchResult::Empty(first_available.unwrap())
+        let result = match first_removed {
+            Some(i) => SearchResult::Empty(i),
+            None => SearchResult::Full,
+        };
+        (result, self.raw_capacity())
+    }
+
+    // The slot a key hashing to `hash` starts probing from. Power-of-two
+    // tables take the top bits of the mixed hash, where all of it has had a
+    // say, rather than masking off the bottom ones.
+    fn home_slot(&self, hash: u64) -> usize {
+        match self.growth_policy {
+            GrowthPolicy::PowerOfTwo => {
+                let bits = self.raw_capacity().trailing_zeros();
+                let mixed = hash.wrapping_mul(FIBONACCI_MULTIPLIER);
+                mixed.checked_shr(64 - bits).unwrap_or(0) as usize
+            }
+            _ => hash as usize % self.raw_capacity(),
+        }
+    }
+
+    // The slot `scan` steps on from `home`, wrapping around the end of the
+    // table.
+    fn probe_slot(&self, home: usize, scan: usize) -> usize {
+        let step = match self.probe_strategy {
+            ProbeStrategy::Linear | ProbeStrategy::RobinHood => scan,
+            // Halving whichever of scan and scan + 1 is even keeps the
+            // product right mod 2^64, and so mod the table size, even once
+            // it wraps.
+            ProbeStrategy::Quadratic if scan.is_multiple_of(2) => (scan / 2).wrapping_mul(scan + 1),
+            ProbeStrategy::Quadratic => scan.wrapping_mul(scan.div_ceil(2)),
+        };
+        match self.growth_policy {
+            GrowthPolicy::PowerOfTwo => home.wrapping_add(step) & (self.raw_capacity() - 1),
+            _ => (home + step) % self.raw_capacity(),
+        }
+    }
+
+    // How many slots on from its home slot the key in slot `index` is, for
+    // linear and Robin Hood probing.
+    fn probe_distance(&self, index: usize) -> usize {
+        let hash = match self.table[index] {
+            Slot::Some(hash, ..) => hash,
+            _ => panic!("unexpected non-value Slot found"),
+        };
+        let home = self.home_slot(hash);
+        (index + self.raw_capacity() - home) % self.raw_capacity()
+    }
+
+    // Puts a new key in slot `index`, which search picked for it. Under
+    // Robin Hood that slot can be holding a key nearer its home, which then
+    // gets carried on to the next slot, and so on, with each key carried
+    // along taking the place of the first one it finds nearer home than
+    // itself.
+    fn insert_at(&mut self, index: usize, hash: u64, key: K, value: V) {
+        self.size += 1;
+        let mut carried = Slot::Some(hash, key, value);
+        let mut distance = 0;
+        let mut i = index;
+        loop {
+            match &self.table[i] {
+                Slot::None | Slot::Removed => {
+                    if let Slot::None = self.table[i] {
+                        self.used += 1;
+                    }
+                    self.control[i] = carried.control();
+                    self.table[i] = carried;
+                    return;
+                }
+                Slot::Some(..) => {
+                    let resident = self.probe_distance(i);
+                    if i == index || resident < distance {
+                        self.control[i] = carried.control();
+                        carried = mem::replace(&mut self.table[i], carried);
+                        distance = resident;
+                    }
+                }
+            }
+            i = (i + 1) % self.raw_capacity();
+            distance += 1;
+        }
+    }
+
+    // Takes the item out of slot `index`. Quadratic probes can't be
+    // followed backwards, so that leaves a tombstone, but otherwise the keys
+    // after it shift back to fill the gap instead.
+    fn remove_at(&mut self, index: usize) -> (K, V) {
+        let old = if self.probe_strategy == ProbeStrategy::Quadratic {
+            self.control[index] = DELETED;
+            mem::replace(&mut self.table[index], Slot::Removed)
+        } else {
+            self.control[index] = EMPTY;
+            let old = mem::replace(&mut self.table[index], Slot::None);
+            self.shift_back(index);
+            self.used -= 1;
+            old
+        };
+        self.size -= 1;
+        old.into_pair()
+    }
+
+    // Fills the empty slot at `hole` from the run of keys after it, so that
+    // probes which used to go through it still reach them. A key can move
+    // back into the hole as long as that doesn't take it back past its home
+    // slot, and then it leaves a hole of its own. Keys that can't move are
+    // skipped, with the ones after them still moving past, except under
+    // Robin Hood, where keys are in order and so everything after one that
+    // can't move can't either.
+    fn shift_back(&mut self, mut hole: usize) {
+        let mut next = (hole + 1) % self.raw_capacity();
+        while let Slot::Some(..) = self.table[next] {
+            let gap = (next + self.raw_capacity() - hole) % self.raw_capacity();
+            if self.probe_distance(next) >= gap {
+                self.table.swap(hole, next);
+                self.control.swap(hole, next);
+                hole = next;
+            } else if self.probe_strategy == ProbeStrategy::RobinHood {
+                break;
+            }
+            next = (next + 1) % self.raw_capacity();
+        }
+    }
+
+    // Puts `value` in the map under `key`, handing back the value that was
+    // there before, if there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        self.insert_hashed(hash, key, value)
+    }
+
+    // Like insert, for a key that's already been hashed with the map's
+    // hasher. Passing any other hash puts the key where lookups won't find
+    // it.
+    pub fn insert_hashed(&mut self, hash: u64, key: K, value: V) -> Option<V> {
+        self.grow_if_needed();
+        self.insert_without_resize(hash, key, value)
     }
 
+    #[deprecated(note = "renamed to insert, as on std's HashMap")]
     pub fn put(&mut self, key: K, value: V) -> Option<V> {
-        // First, check the load factor, and grow if needed.
-        let load_factor: f32 = self.len() as f32 / self.capacity() as f32;
-        if load_factor > MAX_LOAD_FACTOR {
-            self.grow(2 * self.capacity() + 1);
+        self.insert(key, value)
+    }
+
+    // Grows the table if it's at capacity, so that one more item can go in
+    // without going over the load factor. If it's only the tombstones
+    // quadratic probing leaves that are filling up the table, it's rebuilt
+    // at the same size without them instead, since otherwise probes would
+    // run on through them and never hit an empty slot.
+    fn grow_if_needed(&mut self) {
+        // With a small enough load factor, neither the initial size nor the
+        // next one up might have room, so it's at least what one more needs.
+        let size_for_one_more = |map: &Self| {
+            let size = map.checked_size_for(map.len() + 1);
+            size.expect("capacity overflow")
+        };
+        if self.raw_capacity() == 0 {
+            self.resize(self.initial_size().max(size_for_one_more(self)));
+            return;
+        }
+        if self.len() >= self.capacity() {
+            let grown = self.growth_policy.grown(self.raw_capacity());
+            self.resize(grown.max(size_for_one_more(self)));
+        } else if self.used >= self.capacity() {
+            self.resize(self.raw_capacity());
+        }
+    }
+
+    // Makes sure `additional` more items can be put in without the table
+    // being rebuilt, by rebuilding it now if needed. New items can go in
+    // tombstones as well as empty slots, so it's the used slots that have to
+    // have room, and rebuilding clears the tombstones out too.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(err) = self.try_reserve(additional) {
+            panic!("{}", err);
+        }
+    }
+
+    // Like reserve, but handing back an error if the table would be too big
+    // to size or to allocate, rather than panicking or aborting. The map is
+    // left as it was when that happens.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if additional == 0 {
+            return Ok(());
         }
+        let size_for = |len: usize| {
+            len.checked_add(additional)
+                .and_then(|len| self.checked_size_for(len))
+                .ok_or(TryReserveError::CapacityOverflow)
+        };
+        if size_for(self.used)? > self.raw_capacity() {
+            let size = size_for(self.len())?.max(self.raw_capacity());
+            let table = Self::try_allocate_table(size)?;
+            self.rebuild(table);
+        }
+        Ok(())
+    }
 
-        self.put_without_resize(key, value)
+    // Gets the entry for `key`, to look at or change in place. Growing the
+    // table happens here, before the search, so that a vacant entry can fill
+    // in the slot it was given without searching again.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        self.grow_if_needed();
+        let hash = self.hash_builder.hash_one(&key);
+        self.entry_hashed(hash, key)
     }
 
-    fn put_without_resize(&mut self, key: K, value: V) -> Option<V> {
-        match self.search(&key) {
+    fn entry_hashed(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S> {
+        match self.search_hashed(hash, &key) {
+            SearchResult::Found(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            SearchResult::Empty(index) => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                hash,
+                index,
+            }),
+            SearchResult::Full => {
+                self.resize(self.growth_policy.grown(self.raw_capacity()));
+                self.entry_hashed(hash, key)
+            }
+        }
+    }
+
+    // Like entry, but takes a borrowed key, so that an owned one is only
+    // made from it if the entry is vacant and something is put in.
+    pub fn entry_ref<'b, Q>(&mut self, key: &'b Q) -> EntryRef<'_, 'b, K, Q, V, S>
+    where
+        K: Borrow<Q> + From<&'b Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.grow_if_needed();
+        let hash = self.hash_builder.hash_one(key);
+        self.entry_ref_hashed(hash, key)
+    }
+
+    fn entry_ref_hashed<'b, Q>(&mut self, hash: u64, key: &'b Q) -> EntryRef<'_, 'b, K, Q, V, S>
+    where
+        K: Borrow<Q> + From<&'b Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search_hashed(hash, key) {
+            SearchResult::Found(index) => EntryRef::Occupied(OccupiedEntry { map: self, index }),
+            SearchResult::Empty(index) => EntryRef::Vacant(VacantEntryRef {
+                map: self,
+                key,
+                hash,
+                index,
+            }),
+            SearchResult::Full => {
+                self.resize(self.growth_policy.grown(self.raw_capacity()));
+                self.entry_ref_hashed(hash, key)
+            }
+        }
+    }
+
+    // Gets the value for `key`, calling `f` to make it and putting it in
+    // first if there isn't one. This only searches once, after any growing,
+    // so the reference is always into the table the value ends up in.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> &mut V {
+        self.entry(key).or_insert_with(f)
+    }
+
+    fn insert_without_resize(&mut self, hash: u64, key: K, value: V) -> Option<V> {
+        match self.search_hashed(hash, &key) {
             SearchResult::Found(i) => {
-                let new_entry = Entry::Some(key, value);
+                let new_slot = Slot::Some(hash, key, value);
 
-                // Swap out the entries in the map
-                let old = mem::replace(&mut self.table[i], new_entry);
+                // Swap out the slots in the map
+                let old = mem::replace(&mut self.table[i], new_slot);
 
                 // Return the old value
                 Some(old.into_value())
@@ -139,141 +936,3286 @@ impl<K: Hash + Eq + Copy + fmt::Debug, V: fmt::Debug> HashMap<K, V> {
 
             SearchResult::Empty(i) => {
                 // Add the new value, return None.
-                self.table[i] = Entry::Some(key, value);
-                self.size += 1;
+                self.insert_at(i, hash, key, value);
                 None
             }
+
+            // Every slot is taken, which only happens to tables too small
+            // for the load factor check to leave a gap. Make room and try
+            // again.
+            SearchResult::Full => {
+                self.resize(self.growth_policy.grown(self.raw_capacity()));
+                self.insert_without_resize(hash, key, value)
+            }
         }
     }
 
-    pub fn remove(&mut self, key: K) -> Option<V> {
-        match self.search(&key) {
-            SearchResult::Found(i) => {
-                let old = mem::replace(&mut self.table[i], Entry::Removed);
-                self.size -= 1;
-                Some(old.into_value())
-            }
-            SearchResult::Empty(_) => None,
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
+            SearchResult::Found(i) => Some(self.remove_at(i).1),
+            SearchResult::Empty(_) | SearchResult::Full => None,
         }
     }
 
-    pub fn get(&mut self, key: K) -> Option<&mut V> {
-        match self.search(&key) {
+    // Like remove, but gives back the key that was stored as well, rather
+    // than dropping it.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
+            SearchResult::Found(i) => Some(self.remove_at(i)),
+            SearchResult::Empty(_) | SearchResult::Full => None,
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
+            SearchResult::Found(i) => Some(self.table[i].value()),
+            SearchResult::Empty(_) | SearchResult::Full => None,
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
             SearchResult::Found(i) => Some(self.table[i].mut_value()),
-            SearchResult::Empty(_) => None,
+            SearchResult::Empty(_) | SearchResult::Full => None,
         }
     }
 
-    pub fn contains(&self, key: K) -> bool {
-        match self.search(&key) {
+    // Like get, but also gives back the key as it's stored in the map.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
+            SearchResult::Found(i) => match &self.table[i] {
+                Slot::Some(_, key, value) => Some((key, value)),
+                _ => panic!("unexpected non-value Slot found"),
+            },
+            SearchResult::Empty(_) | SearchResult::Full => None,
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.search(key) {
             SearchResult::Found(_) => true,
-            SearchResult::Empty(_) => false,
+            SearchResult::Empty(_) | SearchResult::Full => false,
         }
     }
 
+    #[deprecated(note = "renamed to contains_key, as on std's HashMap")]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.contains_key(key)
+    }
+
+    // Rebuilds the table with at least `size` slots, or exactly that many
+    // unless the growth policy rounds it up. If that's too few to hold the
+    // items without going over the load factor, it's sized for them instead.
     pub fn grow(&mut self, size: usize) {
+        let mut size = self
+            .growth_policy
+            .round_up(size)
+            .expect("capacity overflow");
+        if usable_capacity(size, self.max_load_factor) < self.len() {
+            size = self
+                .checked_size_for(self.len())
+                .expect("capacity overflow");
+        }
+        self.resize(size);
+    }
+
+    // Switches to picking table sizes with `policy`, rebuilding the table
+    // now if its size isn't one the policy allows, or if keys would start
+    // probing from different slots under it.
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        assert!(
+            self.probe_strategy != ProbeStrategy::Quadratic || policy == GrowthPolicy::PowerOfTwo,
+            "quadratic probing needs GrowthPolicy::PowerOfTwo"
+        );
+        let old_policy = mem::replace(&mut self.growth_policy, policy);
+        if self.raw_capacity() > 0 {
+            let size = policy.round_up(self.raw_capacity());
+            let size = size.expect("capacity overflow");
+            let moves_keys =
+                (old_policy == GrowthPolicy::PowerOfTwo) != (policy == GrowthPolicy::PowerOfTwo);
+            if size != self.raw_capacity() || moves_keys {
+                self.resize(size);
+            }
+        }
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth_policy
+    }
+
+    // Switches to probing with `strategy`, rebuilding the table now so every
+    // key is where the new probes will look for it. Quadratic probing needs
+    // the growth policy set to PowerOfTwo first.
+    pub fn set_probe_strategy(&mut self, strategy: ProbeStrategy) {
+        assert!(
+            strategy != ProbeStrategy::Quadratic || self.growth_policy == GrowthPolicy::PowerOfTwo,
+            "quadratic probing needs GrowthPolicy::PowerOfTwo"
+        );
+        let old_strategy = mem::replace(&mut self.probe_strategy, strategy);
+        if self.raw_capacity() > 0 && strategy != old_strategy {
+            self.resize(self.raw_capacity());
+        }
+    }
+
+    pub fn probe_strategy(&self) -> ProbeStrategy {
+        self.probe_strategy
+    }
+
+    // The first table this map gets, and the one clear_and_shrink goes back
+    // to.
+    fn initial_size(&self) -> usize {
+        let size = self.growth_policy.round_up(INITIAL_SIZE);
+        size.expect("capacity overflow")
+    }
+
+    // The table size needed to hold `len` items, with this map's load factor
+    // and growth policy, or None if it doesn't fit in a usize.
+    fn checked_size_for(&self, len: usize) -> Option<usize> {
+        let size = checked_table_size_for(len, self.max_load_factor)?;
+        self.growth_policy.round_up(size)
+    }
+
+    // Rebuilds the table into one of `size` slots, bigger or smaller, which
+    // also clears out any tombstones. There has to be room for every item,
+    // with a slot to spare.
+    fn resize(&mut self, size: usize) {
         assert!(
             self.len() < size,
             "cannot resize to size smaller than len()"
         );
 
-        // Allocate the new table, swap it into place, keep the old one.
-        let old_table = mem::replace(&mut self.table, Self::allocate_table(size));
+        self.rebuild(Self::allocate_table(size));
+    }
+
+    // Moves everything into `table`, which has to be empty and big enough.
+    fn rebuild(&mut self, (table, control): Table<K, V>) {
+        // Swap the new table into place, keep the old one.
+        let old_table = mem::replace(&mut self.table, table);
+        self.control = control;
         self.size = 0;
+        self.used = 0;
 
-        // Copy over all entries containing values by re-hashing and re-adding.
-        for entry in old_table {
-            if let Entry::Some(key, value) = entry {
-                self.put_without_resize(key, value);
+        // Move over all entries containing values, using the hashes they
+        // already have.
+        for slot in old_table {
+            if let Slot::Some(hash, key, value) = slot {
+                self.reinsert(hash, key, value);
             }
         }
     }
 
-    pub fn clear(&mut self) {
-        self.table = Self::allocate_table(INITIAL_SIZE);
-        self.size = 0;
+    // Puts a key into the first slot a probe for it would stop at, for
+    // moving keys to a new table. Every key is different and the table has
+    // no tombstones, so this doesn't need to compare keys or hash them.
+    fn reinsert(&mut self, hash: u64, key: K, value: V) {
+        let home = self.home_slot(hash);
+        for scan in 0..self.raw_capacity() {
+            let i = self.probe_slot(home, scan);
+            let stop = match self.control[i] {
+                EMPTY => true,
+                DELETED => false,
+                _ => {
+                    self.probe_strategy == ProbeStrategy::RobinHood && self.probe_distance(i) < scan
+                }
+            };
+            if stop {
+                self.insert_at(i, hash, key, value);
+                return;
+            }
+        }
+        panic!("no room to move a key into the new table");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::HashMap;
+    // Shrinks the table as far as it can go while holding everything in it
+    // without growing, but no smaller than INITIAL_SIZE.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
 
-    #[test]
-    fn create_map() {
-        HashMap::<u32, u32>::new();
+    // Like shrink_to_fit, but leaving room for at least `min_capacity`
+    // items, as with with_capacity.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let size = self.checked_size_for(self.len().max(min_capacity));
+        let size = size.expect("capacity overflow");
+        if size < self.raw_capacity() {
+            self.resize(size);
+        }
     }
 
-    #[test]
-    fn add_items() {
-        let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 2);
-        map.put(2, 4);
-        map.put(3, 6);
+    // Iterates over every key and value in the map, in table order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.table.iter(),
+            remaining: self.size,
+        }
     }
 
-    #[test]
-    fn get_items() {
-        let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 2);
-        map.put(2, 4);
-        map.put(3, 6);
-        assert_eq!(map.get(1), Some(&mut 2));
-        assert_eq!(map.get(2), Some(&mut 4));
-        assert_eq!(map.get(3), Some(&mut 6));
+    // Like iter, but with the values borrowed mutably, to change in place.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.table.iter_mut(),
+            remaining: self.size,
+        }
     }
 
-    #[test]
-    fn overwrite() {
-        let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 10);
-        map.put(1, 100);
-        map.put(1, 2);
-        assert_eq!(map.remove(1), Some(2));
-        assert_eq!(map.get(1), None);
+    // Iterates over every key in the map.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
     }
 
-    #[test]
-    fn contains() {
-        let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 2);
-        map.put(2, 4);
-        map.put(3, 6);
-        assert!(map.contains(1));
-        assert!(map.contains(2));
-        assert!(map.contains(3));
-        assert!(!map.contains(4));
-        assert!(!map.contains(6));
-        assert!(!map.contains(9));
+    // Iterates over every value in the map.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
     }
 
-    #[test]
-    fn remove_items() {
-        let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 2);
-        map.put(2, 4);
-        map.put(3, 6);
-        assert_eq!(map.remove(1), Some(2));
-        assert_eq!(map.remove(2), Some(4));
+    // Like values, but borrowed mutably, to change in place.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
 
-        assert_eq!(map.get(3).unwrap(), &6);
-        assert_eq!(map.get(1), None);
-        assert_eq!(map.get(2), None);
-        assert_eq!(map.get(100), None);
+    // Consumes the map, yielding its keys. The values are dropped as it goes.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    // Consumes the map, yielding its values. The keys are dropped as it goes.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    // Takes every key and value out of the map, leaving it empty with the
+    // same table. The table moves into the iterator while it's around, and
+    // the map gets it back, emptied, when the iterator's dropped, along with
+    // anything it hadn't yielded. Until then the map has no table at all, so
+    // leaking the iterator leaves it empty and lazily allocated rather than
+    // holding items it's lost track of.
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        let remaining = mem::replace(&mut self.size, 0);
+        self.used = 0;
+        Drain {
+            table: mem::take(&mut self.table),
+            control: mem::take(&mut self.control),
+            index: 0,
+            remaining,
+            map_table: &mut self.table,
+            map_control: &mut self.control,
+        }
+    }
+
+    // Keeps only the entries `f` returns true for, letting it change the
+    // values as it goes. Entries are removed in place, the same way as with
+    // remove, so the table's never rebuilt.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        let start = self.scan_start();
+        let mut scanned = 0;
+        while scanned < self.raw_capacity() {
+            let index = (start + scanned) % self.raw_capacity();
+            if let Slot::Some(_, key, value) = &mut self.table[index] {
+                if !f(key, value) {
+                    self.remove_at(index);
+                    // Unless that left a tombstone, the slot's been refilled
+                    // from later on, so look at it again.
+                    if self.probe_strategy != ProbeStrategy::Quadratic {
+                        continue;
+                    }
+                }
+            }
+            scanned += 1;
+        }
+    }
+
+    // Takes out the entries `pred` returns true for, handing them back one
+    // at a time as the iterator's advanced. Entries it returns false for
+    // stay, as do any the iterator hasn't got to by the time it's dropped.
+    // Like retain, entries are removed in place.
+    pub fn extract_if<F: FnMut(&K, &mut V) -> bool>(
+        &mut self,
+        pred: F,
+    ) -> ExtractIf<'_, K, V, S, F> {
+        ExtractIf {
+            start: self.scan_start(),
+            scanned: 0,
+            map: self,
+            pred,
+        }
+    }
+
+    // Where a pass over the table that removes keys as it goes, like
+    // retain's, should start: just after an empty slot. Removing shifts the
+    // keys after a hole back into it, but never past an empty slot, so
+    // starting there means keys only ever move back into slots the pass is
+    // on or hasn't got to, and it sees each key once. The load factor
+    // always leaves an empty slot, unless the table hasn't been allocated.
+    fn scan_start(&self) -> usize {
+        match self.control.iter().position(|&control| control == EMPTY) {
+            Some(empty) => (empty + 1) % self.raw_capacity(),
+            None => 0,
+        }
+    }
+
+    // Removes everything, keeping the table so it can be filled back up
+    // without allocating again.
+    pub fn clear(&mut self) {
+        for slot in self.table.iter_mut() {
+            *slot = Slot::None;
+        }
+        self.control.fill(EMPTY);
+        self.size = 0;
+        self.used = 0;
+    }
+
+    // Removes everything and swaps the table for a fresh initial-size one,
+    // giving back the memory a big map was holding on to.
+    pub fn clear_and_shrink(&mut self) {
+        (self.table, self.control) = Self::allocate_table(self.initial_size());
+        self.size = 0;
+        self.used = 0;
+    }
+}
+
+// An iterator over the keys and values of a map. See HashMap::iter.
+pub struct Iter<'a, K, V> {
+    slots: slice::Iter<'a, Slot<K, V>>,
+
+    // The number of Some{...} slots left to go
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        for slot in &mut self.slots {
+            if let Slot::Some(_, key, value) = slot {
+                self.remaining -= 1;
+                return Some((key, value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+// An iterator over the keys and mutable values of a map. See
+// HashMap::iter_mut.
+pub struct IterMut<'a, K, V> {
+    slots: slice::IterMut<'a, Slot<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        for slot in &mut self.slots {
+            if let Slot::Some(_, key, value) = slot {
+                self.remaining -= 1;
+                return Some((key, value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+// An iterator over the keys of a map. See HashMap::keys.
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+// An iterator over the values of a map. See HashMap::values.
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+// An iterator over the mutable values of a map. See HashMap::values_mut.
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+// An iterator taking the keys and values out of a map. Anything left when
+// it's dropped is dropped along with it.
+pub struct IntoIter<K, V> {
+    slots: vec::IntoIter<Slot<K, V>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        for slot in &mut self.slots {
+            if let Slot::Some(_, key, value) = slot {
+                self.remaining -= 1;
+                return Some((key, value));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K: Hash + Eq, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            slots: self.table.into_iter(),
+            remaining: self.size,
+        }
+    }
+}
+
+// An iterator taking the keys out of a map. See HashMap::into_keys.
+pub struct IntoKeys<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+// An iterator taking the values out of a map. See HashMap::into_values.
+pub struct IntoValues<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+// An iterator emptying a map. See HashMap::drain.
+pub struct Drain<'a, K, V> {
+    // The map's table, taken out of it until this is dropped
+    table: Vec<Slot<K, V>>,
+    control: Vec<u8>,
+
+    // The index of the next slot to look at
+    index: usize,
+    remaining: usize,
+
+    // Where the table goes back to
+    map_table: &'a mut Vec<Slot<K, V>>,
+    map_control: &'a mut Vec<u8>,
+}
+
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.index < self.table.len() {
+            let index = self.index;
+            self.index += 1;
+            if let Slot::Some(..) = self.table[index] {
+                self.remaining -= 1;
+                self.control[index] = EMPTY;
+                return Some(mem::replace(&mut self.table[index], Slot::None).into_pair());
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {}
+
+impl<K, V> Drop for Drain<'_, K, V> {
+    fn drop(&mut self) {
+        // Drop whatever wasn't yielded, and clear out any tombstones, before
+        // handing the table back.
+        for slot in self.table.iter_mut() {
+            *slot = Slot::None;
+        }
+        self.control.fill(EMPTY);
+        *self.map_table = mem::take(&mut self.table);
+        *self.map_control = mem::take(&mut self.control);
+    }
+}
+
+// An iterator taking entries out of a map. See HashMap::extract_if.
+pub struct ExtractIf<'a, K, V, S, F>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    map: &'a mut HashMap<K, V, S>,
+
+    // The slot the pass started from, as picked by scan_start, and how many
+    // slots after it have been looked at
+    start: usize,
+    scanned: usize,
+
+    pred: F,
+}
+
+impl<K, V, S, F> Iterator for ExtractIf<'_, K, V, S, F>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.scanned < self.map.raw_capacity() {
+            let index = (self.start + self.scanned) % self.map.raw_capacity();
+            if let Slot::Some(_, key, value) = &mut self.map.table[index] {
+                if (self.pred)(key, value) {
+                    // As in retain, the slot's looked at again if it's
+                    // been refilled.
+                    if self.map.probe_strategy == ProbeStrategy::Quadratic {
+                        self.scanned += 1;
+                    }
+                    return Some(self.map.remove_at(index));
+                }
+            }
+            self.scanned += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len()))
+    }
+}
+
+// Shows the entries in the map, in table order, like {1: 2, 3: 6}.
+impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug, S: BuildHasher> fmt::Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for HashMap<K, V, S> {
+    fn default() -> HashMap<K, V, S> {
+        HashMap::with_hasher(S::default())
+    }
+}
+
+// Collects pairs into a map, sized up front from the iterator's size_hint.
+// Later pairs overwrite earlier ones with the same key, like insert.
+impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> HashMap<K, V, S> {
+        let iter = iter.into_iter();
+        let mut map = HashMap::with_capacity_and_hasher(iter.size_hint().0, S::default());
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+// Adds pairs to a map, like calling insert for each.
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for HashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, &'a V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Copy,
+    V: Copy,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+// Looks up a value which has to be there, panicking if it isn't.
+impl<K: Hash + Eq + fmt::Debug, V, S: BuildHasher> Index<K> for HashMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, key: K) -> &V {
+        match self.search(&key) {
+            SearchResult::Found(i) => self.table[i].value(),
+            SearchResult::Empty(_) | SearchResult::Full => {
+                panic!("key not found in HashMap: {:?}", key)
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V, S: BuildHasher> IndexMut<K> for HashMap<K, V, S> {
+    fn index_mut(&mut self, key: K) -> &mut V {
+        match self.search(&key) {
+            SearchResult::Found(i) => self.table[i].mut_value(),
+            SearchResult::Empty(_) | SearchResult::Full => {
+                panic!("key not found in HashMap: {:?}", key)
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V, S: BuildHasher> Index<&K> for HashMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        match self.search(key) {
+            SearchResult::Found(i) => self.table[i].value(),
+            SearchResult::Empty(_) | SearchResult::Full => {
+                panic!("key not found in HashMap: {:?}", key)
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V, S: BuildHasher> IndexMut<&K> for HashMap<K, V, S> {
+    fn index_mut(&mut self, key: &K) -> &mut V {
+        match self.search(key) {
+            SearchResult::Found(i) => self.table[i].mut_value(),
+            SearchResult::Empty(_) | SearchResult::Full => {
+                panic!("key not found in HashMap: {:?}", key)
+            }
+        }
+    }
+}
+
+// A view into a single key of a map, which may or may not have a value.
+pub enum Entry<'a, K: Hash + Eq, V, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+// An entry for a key that's in the map, at `index` in the table.
+pub struct OccupiedEntry<'a, K: Hash + Eq, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+}
+
+// An entry for a key that isn't in the map, which would go at `index`.
+pub struct VacantEntry<'a, K: Hash + Eq, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    key: K,
+    hash: u64,
+    index: usize,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    // Inserts `default` if the key has no value, then returns the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Like or_insert, but only makes the value if it's needed.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    // Calls `f` on the value, if there is one.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        match &self.map.table[self.index] {
+            Slot::Some(_, key, _) => key,
+            _ => panic!("unexpected non-value Slot found"),
+        }
+    }
+
+    pub fn get(&self) -> &V {
+        self.map.table[self.index].value()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.table[self.index].mut_value()
+    }
+
+    // Turns the entry into a reference to the value, which lives as long as
+    // the map's borrow.
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.table[self.index].mut_value()
+    }
+
+    // Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    // Takes the value out of the map.
+    pub fn remove(self) -> V {
+        self.map.remove_at(self.index).1
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> VacantEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    // Puts `value` into the slot found when the entry was made.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.insert_at(self.index, self.hash, self.key, value);
+        self.map.table[self.index].mut_value()
+    }
+}
+
+// An entry looked up by a borrowed key, from entry_ref. Occupied entries
+// are the same as entry's, since they already have their key in the map.
+pub enum EntryRef<'a, 'b, K: Hash + Eq, Q: ?Sized, V, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntryRef<'a, 'b, K, Q, V, S>),
+}
+
+// An entry for a borrowed key that isn't in the map, which would go at
+// `index`. The owned key is only made when a value is inserted.
+pub struct VacantEntryRef<'a, 'b, K: Hash + Eq, Q: ?Sized, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    key: &'b Q,
+    hash: u64,
+    index: usize,
+}
+
+impl<'a, 'b, K, Q, V, S> EntryRef<'a, 'b, K, Q, V, S>
+where
+    K: Hash + Eq + Borrow<Q> + From<&'b Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &Q {
+        match self {
+            EntryRef::Occupied(entry) => entry.key().borrow(),
+            EntryRef::Vacant(entry) => entry.key(),
+        }
+    }
+
+    // Inserts `default` if the key has no value, then returns the value.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    // Like or_insert, but only makes the value if it's needed.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    // Calls `f` on the value, if there is one.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, 'b, K, Q, V, S> VacantEntryRef<'a, 'b, K, Q, V, S>
+where
+    K: Hash + Eq + Borrow<Q> + From<&'b Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'b Q {
+        self.key
+    }
+
+    // Makes the owned key and puts it and `value` into the slot found when
+    // the entry was made.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map
+            .insert_at(self.index, self.hash, K::from(self.key), value);
+        self.map.table[self.index].mut_value()
+    }
+}
+
+// Maps serialize as plain maps of keys to values, in iteration order, so
+// they can be read back as any other map type, and the other way round.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::HashMap;
+    use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+    use std::fmt;
+    use std::hash::{BuildHasher, Hash};
+    use std::marker::PhantomData;
+
+    impl<K, V, S> Serialize for HashMap<K, V, S>
+    where
+        K: Hash + Eq + Serialize,
+        V: Serialize,
+        S: BuildHasher,
+    {
+        fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+            serializer.collect_map(self.iter())
+        }
+    }
+
+    struct MapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+    impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+    where
+        K: Hash + Eq + Deserialize<'de>,
+        V: Deserialize<'de>,
+        S: BuildHasher + Default,
+    {
+        type Value = HashMap<K, V, S>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a map")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            // Don't trust the input's idea of its size too far before any of
+            // it has actually turned up.
+            let capacity = access.size_hint().unwrap_or(0).min(4096);
+            let mut map = HashMap::with_capacity_and_hasher(capacity, S::default());
+            while let Some((key, value)) = access.next_entry()? {
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+
+    impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
+    where
+        K: Hash + Eq + Deserialize<'de>,
+        V: Deserialize<'de>,
+        S: BuildHasher + Default,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(MapVisitor(PhantomData))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        table_size_for, Entry, EntryRef, FixedHashBuilder, GrowthPolicy, HashMap, ProbeStrategy,
+        Slot, TryReserveError, MAX_LOAD_FACTOR, PRIMES,
+    };
+    use crate::test_support::{dropped, DropCounter, DropLog};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::hash_map::RandomState;
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+    use std::mem;
+    use std::rc::Rc;
+
+    #[test]
+    fn create_map() {
+        HashMap::<u32, u32>::new();
+    }
+
+    #[test]
+    fn add_items() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+    }
+
+    #[test]
+    fn get_items() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+        assert_eq!(map.get(&1), Some(&2));
+        assert_eq!(map.get(&2), Some(&4));
+        assert_eq!(map.get(&3), Some(&6));
+    }
+
+    #[test]
+    fn overwrite() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 10);
+        map.insert(1, 100);
+        map.insert(1, 2);
+        assert_eq!(map.remove(&1), Some(2));
+        assert_eq!(map.get(&1), None);
+    }
+
+    #[test]
+    fn contains_key() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+        assert!(map.contains_key(&1));
+        assert!(map.contains_key(&2));
+        assert!(map.contains_key(&3));
+        assert!(!map.contains_key(&4));
+        assert!(!map.contains_key(&6));
+        assert!(!map.contains_key(&9));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn old_names() {
+        let mut map = HashMap::<String, u32>::new();
+        assert_eq!(map.put("a".to_string(), 1), None);
+        assert_eq!(map.put("a".to_string(), 2), Some(1));
+        assert!(map.contains("a"));
+        assert!(!map.contains("b"));
+        assert_eq!(map.insert("a".to_string(), 3), Some(2));
+    }
+
+    #[test]
+    fn remove_items() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+        assert_eq!(map.remove(&1), Some(2));
+        assert_eq!(map.remove(&2), Some(4));
+        assert!(!map.is_empty());
+        assert_eq!(map.load_factor(), 1.0 / map.raw_capacity() as f32);
+
+        assert_eq!(map.get(&3).unwrap(), &6);
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.get(&100), None);
+
+        assert_eq!(map.remove(&3), Some(6));
+        assert_eq!(map.remove(&3), None);
+        assert!(map.is_empty());
+        assert_eq!(map.load_factor(), 0.0);
+    }
+
+    #[test]
+    fn resize() {
+        let mut map = HashMap::<u32, u32>::new();
+        assert!(map.is_empty());
+        assert_eq!(map.load_factor(), 0.0);
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+        assert_eq!(map.load_factor(), 3.0 / super::INITIAL_SIZE as f32);
+        map.grow(100);
+        assert_eq!(map.load_factor(), 0.03);
+        map.insert(4, 8);
+        map.insert(5, 10);
+        assert_eq!(map.load_factor(), 0.05);
+
+        for x in 1..6 {
+            assert!(!map.is_empty());
+            assert_eq!(map.remove(&x), Some(x * 2));
+        }
+        assert!(map.is_empty());
+        assert_eq!(map.load_factor(), 0.0);
+
+        // The table grows before it would go past its capacity, so filling
+        // it up never takes it over the limit, and clearing it empties it.
+        for x in 0..1000 {
+            map.insert(x, x);
+            assert!(map.len() <= map.capacity());
+            assert!(map.load_factor() <= MAX_LOAD_FACTOR);
+        }
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.load_factor(), 0.0);
+    }
+
+    // Where `key` would go in a table of `capacity` slots, if nothing's in
+    // the way, when it's hashed with a FixedHashBuilder.
+    fn home(key: u32, capacity: usize) -> usize {
+        FixedHashBuilder::default().hash_one(key) as usize % capacity
+    }
+
+    #[test]
+    fn entry_vacant() {
+        let mut map = HashMap::<u32, u32>::new();
+        match map.entry(1) {
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &1);
+                *entry.insert(2) += 1;
+            }
+            Entry::Occupied(_) => panic!("entry should be vacant"),
+        }
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), Some(&3));
+
+        assert_eq!(map.entry(2).or_insert(4), &mut 4);
+        assert_eq!(map.entry(2).or_insert(5), &mut 4);
+        assert_eq!(map.entry(3).or_insert_with(|| 6), &mut 6);
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn entry_occupied() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.entry(1).and_modify(|value| *value *= 10).or_insert(0);
+        map.entry(2).and_modify(|value| *value *= 10).or_insert(7);
+        assert_eq!(map.get(&1), Some(&20));
+        assert_eq!(map.get(&2), Some(&7));
+
+        match map.entry(1) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &1);
+                assert_eq!(entry.insert(30), 20);
+                assert_eq!(entry.get(), &30);
+                assert_eq!(entry.remove(), 30);
+            }
+            Entry::Vacant(_) => panic!("entry should be occupied"),
+        }
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), None);
+        assert!(matches!(map.entry(1), Entry::Vacant(_)));
+    }
+
+    #[test]
+    fn entry_counts() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..1000 {
+            *map.entry(x % 100).or_insert(0) += 1;
+        }
+        assert_eq!(map.len(), 100);
+        assert!(map.raw_capacity() > 100);
+        for x in 0..100 {
+            assert_eq!(map.get(&x), Some(&10));
+        }
+    }
+
+    #[test]
+    fn entry_tombstone() {
+        // Two keys which start probing from the same slot, so the second
+        // goes in the slot after the first. Quadratic probing leaves a
+        // tombstone when the first is removed.
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        map.grow(16);
+        let home = |key: u32| map.home_slot(FixedHashBuilder::default().hash_one(key));
+        let first = 0;
+        let second = (1..).find(|&x| home(x) == home(first)).unwrap();
+        let tombstone = home(first);
+
+        map.insert(first, 1);
+        map.insert(second, 2);
+        assert_eq!(map.probe_length(&second), Some(1));
+        map.remove(&first);
+        assert!(matches!(map.table[tombstone], Slot::Removed));
+
+        // The second key is still found past the tombstone...
+        assert!(matches!(map.entry(second), Entry::Occupied(_)));
+        assert_eq!(map.probe_length(&second), Some(1));
+
+        // ...and the first key goes back in it.
+        match map.entry(first) {
+            Entry::Vacant(entry) => assert_eq!(entry.index, tombstone),
+            Entry::Occupied(_) => panic!("entry should be vacant"),
+        }
+        map.entry(first).or_insert(3);
+        assert!(matches!(map.table[tombstone], Slot::Some(_, 0, 3)));
+        assert_eq!(map.probe_length(&first), Some(0));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&second), Some(&2));
+    }
+
+    #[test]
+    fn iter() {
+        let mut map = HashMap::<u32, u32>::new();
+        assert_eq!(map.iter().next(), None);
+        for x in 0..100 {
+            map.insert(x, x * 2);
+        }
+        // Leave gaps all through the table.
+        for x in (0..100).filter(|x| x % 10 < 3) {
+            map.remove(&x);
+        }
+
+        let mut iter = map.iter();
+        assert_eq!(iter.len(), 70);
+        iter.next();
+        assert_eq!(iter.size_hint(), (69, Some(69)));
+
+        let mut pairs: Vec<(u32, u32)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        pairs.sort();
+        let expected: Vec<(u32, u32)> = (0..100)
+            .filter(|x| x % 10 >= 3)
+            .map(|x| (x, x * 2))
+            .collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..50 {
+            map.insert(x, x);
+        }
+        map.remove(&7);
+        assert_eq!(map.iter_mut().len(), 49);
+
+        for (&k, v) in map.iter_mut() {
+            *v += k + 1;
+        }
+        for x in 0..50 {
+            if x == 7 {
+                assert_eq!(map.get(&x), None);
+            } else {
+                assert_eq!(map.get(&x), Some(&(2 * x + 1)));
+            }
+        }
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..40 {
+            map.insert(x, x + 1);
+        }
+        map.remove(&3);
+
+        let iter = map.into_iter();
+        assert_eq!(iter.len(), 39);
+        let mut pairs: Vec<(u32, u32)> = iter.collect();
+        pairs.sort();
+        let expected: Vec<(u32, u32)> = (0..40).filter(|&x| x != 3).map(|x| (x, x + 1)).collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let drops = Rc::new(Cell::new(0));
+        let mut map = HashMap::<u32, DropCounter>::new();
+        for x in 0..20 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+
+        let mut iter = map.into_iter();
+        for _ in 0..5 {
+            iter.next();
+        }
+        assert_eq!(drops.get(), 5);
+        drop(iter);
+        assert_eq!(drops.get(), 20);
+    }
+
+    #[test]
+    fn keys() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..30 {
+            map.insert(x, 0);
+        }
+        for x in 10..20 {
+            map.remove(&x);
+        }
+        map.insert(15, 1);
+        map.insert(5, 1);
+
+        let mut keys: Vec<u32> = map.keys().copied().collect();
+        keys.sort();
+        let expected: Vec<u32> = (0..10).chain(15..16).chain(20..30).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(map.keys().len(), 21);
+    }
+
+    #[test]
+    fn values() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..100 {
+            map.insert(x, x);
+        }
+        map.remove(&50);
+        assert!(map.raw_capacity() > super::INITIAL_SIZE);
+
+        assert_eq!(map.values().sum::<u32>(), 4950 - 50);
+        assert_eq!(map.values().len(), 99);
+
+        for value in map.values_mut() {
+            *value *= 3;
+        }
+        for x in 0..100 {
+            let expected = if x == 50 { None } else { Some(x * 3) };
+            assert_eq!(map.get(&x).copied(), expected);
+        }
+        assert_eq!(map.values_mut().len(), 99);
+    }
+
+    #[test]
+    fn into_keys_and_values() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..20 {
+            map.insert(x % 10, x);
+        }
+        let mut keys: Vec<u32> = map.into_keys().collect();
+        keys.sort();
+        assert_eq!(keys, (0..10).collect::<Vec<_>>());
+
+        let drops = Rc::new(Cell::new(0));
+        let mut map = HashMap::<u32, DropCounter>::new();
+        for x in 0..10 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+        let values: Vec<DropCounter> = map.into_values().collect();
+        assert_eq!((values.len(), drops.get()), (10, 0));
+        drop(values);
+        assert_eq!(drops.get(), 10);
+
+        // The values go as the keys are taken, and the rest with the
+        // iterator.
+        let mut map = HashMap::<u32, DropCounter>::new();
+        for x in 0..10 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+        let mut keys = map.into_keys();
+        keys.next();
+        keys.next();
+        assert_eq!(drops.get(), 12);
+        drop(keys);
+        assert_eq!(drops.get(), 20);
+    }
+
+    #[test]
+    fn drain() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..50 {
+            map.insert(x, x);
+        }
+        map.remove(&10);
+        let capacity = map.raw_capacity();
+        let buffer = map.table.as_ptr();
+
+        let mut pairs: Vec<(u32, u32)> = map.drain().collect();
+        pairs.sort();
+        let expected: Vec<(u32, u32)> = (0..50).filter(|&x| x != 10).map(|x| (x, x)).collect();
+        assert_eq!(pairs, expected);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.raw_capacity(), capacity);
+        assert!(map.table.iter().all(|slot| matches!(slot, Slot::None)));
+
+        // The map can be used again straight away, in the same table.
+        map.insert(10, 1);
+        assert_eq!(map.get(&10), Some(&1));
+        assert_eq!(map.get(&11), None);
+        for x in 0..40 {
+            map.insert(x, x);
+        }
+        assert_eq!(map.table.as_ptr(), buffer);
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn drain_partway() {
+        let drops = Rc::new(Cell::new(0));
+        let mut map = HashMap::<u32, DropCounter>::new();
+        for x in 0..30 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+        let capacity = map.raw_capacity();
+
+        let mut drain = map.drain();
+        assert_eq!(drain.len(), 30);
+        drain.next();
+        drain.next();
+        drop(drain);
+        assert_eq!(drops.get(), 30);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.raw_capacity(), capacity);
+        assert!(map.iter().next().is_none());
+    }
+
+    #[test]
+    fn drain_leaked() {
+        // Forgetting the iterator leaks its table and what it held, but the
+        // map's already empty, with no table, and carries on working.
+        let mut map: HashMap<u32, u32> = (0..30).map(|x| (x, x)).collect();
+        mem::forget(map.drain());
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.raw_capacity(), 0);
+        map.insert(100, 100);
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(map.get(&0), None);
+        assert_eq!(map[100], 100);
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn retain() {
+        // Enough keys in a small table that plenty collide.
+        let mut map = HashMap::<u32, u32>::with_capacity(120);
+        for x in 0..120 {
+            map.insert(x, x);
+        }
+        for x in (0..120).step_by(7) {
+            map.remove(&x);
+        }
+        let capacity = map.raw_capacity();
+
+        map.retain(|&k, v| {
+            *v += 1;
+            k % 3 != 0
+        });
+        assert_eq!(map.raw_capacity(), capacity);
+        let survivors: Vec<u32> = (0..120).filter(|x| x % 7 != 0 && x % 3 != 0).collect();
+        assert_eq!(map.len(), survivors.len());
+        for x in 0..120 {
+            if survivors.contains(&x) {
+                assert_eq!(map.get(&x), Some(&(x + 1)));
+            } else {
+                assert_eq!(map.get(&x), None);
+            }
+        }
+        assert!(map
+            .table
+            .iter()
+            .all(|slot| !matches!(slot, Slot::Some(_, k, _) if k % 3 == 0)));
+    }
+
+    #[test]
+    fn extract_if() {
+        for &(policy, strategy) in &SETUPS {
+            let mut map = HashMap::<u32, u32>::new();
+            map.set_growth_policy(policy);
+            map.set_probe_strategy(strategy);
+            for x in 0..100 {
+                map.insert(x, x);
+            }
+
+            // Stopping halfway through the odd values leaves the rest of
+            // them in the map with the evens.
+            let mut odds: Vec<(u32, u32)> = map.extract_if(|_, v| *v % 2 == 1).take(25).collect();
+            assert_eq!(odds.len(), 25);
+            assert!(odds.iter().all(|&(k, v)| k == v && v % 2 == 1));
+            assert_eq!(map.len(), 75);
+            map.assert_invariants();
+            for &(k, _) in &odds {
+                assert_eq!(map.get(&k), None);
+            }
+
+            // Going all the way takes out the rest, and leaves the evens,
+            // which `pred` sees once each and can change.
+            let mut seen = 0;
+            odds.extend(map.extract_if(|_, v| {
+                seen += 1;
+                *v += 1000;
+                *v % 2 == 1
+            }));
+            assert_eq!(seen, 75);
+            let mut keys: Vec<u32> = odds.iter().map(|&(k, _)| k).collect();
+            keys.sort();
+            assert_eq!(keys, (1..100).step_by(2).collect::<Vec<u32>>());
+            assert_eq!(map.len(), 50);
+            map.assert_invariants();
+            for x in 0..100 {
+                let value = if x % 2 == 0 { Some(&(x + 1000)) } else { None };
+                assert_eq!(map.get(&x), value);
+            }
+        }
+
+        // Dropping the iterator without calling next takes nothing out.
+        let mut map: HashMap<u32, u32> = (0..10).map(|x| (x, x)).collect();
+        let _ = map.extract_if(|_, _| true);
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.extract_if(|_, _| true).count(), 10);
+        assert!(map.is_empty());
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn collect() {
+        let map: HashMap<u32, u32> = (0..10_000).map(|x| (x, x * 2)).collect();
+        assert_eq!(map.len(), 10_000);
+        // Sized once, for exactly this many items.
+        assert_eq!(map.raw_capacity(), table_size_for(10_000, MAX_LOAD_FACTOR));
+        assert_eq!(map.raw_capacity(), 14926);
+        for (&k, &v) in map.iter() {
+            assert_eq!(v, k * 2);
+        }
+
+        let map: HashMap<u32, &str> = vec![(1, "a"), (2, "b"), (1, "c")].into_iter().collect();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.iter().find(|(&k, _)| k == 1).unwrap().1, &"c");
+        assert_eq!(map.raw_capacity(), super::INITIAL_SIZE);
+    }
+
+    #[test]
+    fn extend() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..8 {
+            map.insert(x, 0);
+        }
+        map.extend((4..100).map(|x| (x, x)));
+        // Grown once, straight to the size for all of them.
+        assert_eq!(map.raw_capacity(), table_size_for(8 + 96, MAX_LOAD_FACTOR));
+        assert_eq!(map.len(), 100);
+        for x in 0..100 {
+            let expected = if x < 4 { 0 } else { x };
+            assert_eq!(map.get(&x).copied(), Some(expected));
+        }
+
+        let other: Vec<(u32, u32)> = (100..200).map(|x| (x, x + 1)).collect();
+        map.extend(other.iter().map(|(k, v)| (k, v)));
+        assert_eq!(map.len(), 200);
+        assert_eq!(map.get(&150), Some(&151));
+        assert_eq!(map.get(&50), Some(&50));
+    }
+
+    #[test]
+    fn index() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(3, 4);
+        assert_eq!(map[1], 2);
+        map[3] += 1;
+        assert_eq!(map[3], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found in HashMap: 7")]
+    fn index_missing() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        let _ = map[7];
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found in HashMap: 1")]
+    fn index_mut_removed() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.remove(&1);
+        map[1] += 1;
+    }
+
+    #[test]
+    fn debug() {
+        let mut map = HashMap::<u32, u32>::new();
+        assert_eq!(format!("{:?}", map), "{}");
+        map.insert(1, 2);
+        map.insert(2, 4);
+        map.insert(3, 6);
+        map.remove(&2);
+        let shown = format!("{:?}", map);
+        assert!(
+            shown == "{1: 2, 3: 6}" || shown == "{3: 6, 1: 2}",
+            "{}",
+            shown
+        );
+    }
+
+    #[test]
+    fn values_without_debug() {
+        struct Opaque(u32);
+
+        let mut map = HashMap::<u32, Opaque>::new();
+        map.insert(1, Opaque(2));
+        assert_eq!(map.get(&1).map(|value| value.0), Some(2));
+        assert_eq!(map.remove(&1).map(|value| value.0), Some(2));
+    }
+
+    #[test]
+    fn default() {
+        #[derive(Default)]
+        struct Counts {
+            map: HashMap<u32, u32>,
+        }
+
+        let mut counts = Counts::default();
+        assert_eq!(counts.map.len(), 0);
+        assert_eq!(counts.map.raw_capacity(), 0);
+        counts.map.insert(1, 1);
+        assert_eq!(counts.map.raw_capacity(), super::INITIAL_SIZE);
+        assert_eq!(counts.map[1], 1);
+    }
+
+    #[test]
+    fn with_capacity() {
+        for n in [0, 1, 8, 9, 13, 100, 1000] {
+            let mut map = HashMap::<usize, usize>::with_capacity(n);
+            let capacity = map.raw_capacity();
+            for x in 0..n {
+                map.insert(x, x);
+                assert_eq!(map.raw_capacity(), capacity, "n = {}", n);
+            }
+            assert_eq!(map.len(), n);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn new_capacity() {
+        let map = HashMap::<u32, u32>::new_capacity(40);
+        assert_eq!(map.raw_capacity(), 40);
+    }
+
+    #[test]
+    fn string_keys() {
+        let mut map = HashMap::<String, u32>::new();
+        for word in "the cat sat on the mat with the other cat".split(' ') {
+            *map.entry(word.to_string()).or_insert(0) += 1;
+        }
+        assert_eq!(map.len(), 7);
+        assert_eq!(map["the".to_string()], 3);
+        assert_eq!(map.get(&"cat".to_string()), Some(&2));
+        assert!(map.contains_key(&"mat".to_string()));
+        assert_eq!(map.remove(&"on".to_string()), Some(1));
+        assert!(!map.contains_key(&"on".to_string()));
+
+        // Growing moves the keys into the new table.
+        for x in 0..100 {
+            map.insert(x.to_string(), x);
+        }
+        assert_eq!(map.len(), 106);
+        assert_eq!(map.get(&"42".to_string()), Some(&42));
+        assert_eq!(map.get(&"sat".to_string()), Some(&1));
+    }
+
+    #[test]
+    fn vec_keys() {
+        let map: HashMap<Vec<u8>, usize> = (0..50u8)
+            .map(|x| (vec![x; x as usize], x as usize))
+            .collect();
+        assert_eq!(map.get(&vec![]), Some(&0));
+        assert_eq!(map.get(&vec![7; 7]), Some(&7));
+        assert_eq!(map.get(&vec![7; 6]), None);
+
+        let mut keys: Vec<Vec<u8>> = map.into_keys().collect();
+        keys.sort();
+        assert_eq!(keys.len(), 50);
+        assert_eq!(keys[49], vec![49; 49]);
+    }
+
+    #[test]
+    fn get_shared() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.insert(1, 2);
+        map.insert(3, 2);
+
+        let shared = &map;
+        let first = shared.get(&1).unwrap();
+        let second = shared.get(&3).unwrap();
+        assert_eq!(first, second);
+        assert!(!std::ptr::eq(first, second));
+
+        *map.get_mut(&1).unwrap() += 5;
+        assert_eq!(map.get(&1), Some(&7));
+        assert_eq!(map.get_mut(&2), None);
+    }
+
+    #[test]
+    fn lookups_by_reference() {
+        let mut map = HashMap::<String, u32>::new();
+        let key = String::from("apple");
+        map.insert(key.clone(), 1);
+        map.insert(String::from("pear"), 2);
+
+        // The key is still ours after looking it up.
+        assert!(map.contains_key(&key));
+        assert_eq!(map[&key], 1);
+        map[&key] += 10;
+        assert_eq!(map.get(&key), Some(&11));
+
+        assert_eq!(map.get(&format!("{}{}", "pe", "ar")), Some(&2));
+        *map.get_mut(&"pear".to_string()).unwrap() *= 2;
+        assert_eq!(map[&String::from("pear")], 4);
+        assert_eq!(map.remove(&"apple".to_owned()), Some(11));
+        assert!(!map.contains_key(&key));
+        assert_eq!(key, "apple");
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut map = HashMap::<String, u32>::new();
+        map.insert("hello".to_string(), 1);
+        map.insert("world".to_string(), 2);
+        assert_eq!(map.get("hello"), Some(&1));
+        assert!(map.contains_key("world"));
+        *map.get_mut("world").unwrap() += 1;
+        assert_eq!(map.remove("world"), Some(3));
+        assert!(!map.contains_key("world"));
+        assert_eq!(map.get("nope"), None);
+
+        let map: HashMap<Vec<u8>, usize> = (0..20u8).map(|x| (vec![x; 3], x as usize)).collect();
+        let bytes = [5u8, 5, 5, 6];
+        assert_eq!(map.get(&bytes[..3]), Some(&5));
+        assert_eq!(map.get(&bytes[1..]), None);
+    }
+
+    #[test]
+    fn get_key_value() {
+        // Equal when the names are, whatever the ids.
+        #[derive(Debug)]
+        struct Tagged {
+            name: &'static str,
+            id: u32,
+        }
+
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Tagged) -> bool {
+                self.name == other.name
+            }
+        }
+
+        impl Eq for Tagged {}
+
+        impl Hash for Tagged {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.name.hash(state);
+            }
+        }
+
+        let tagged = |name, id| Tagged { name, id };
+        let mut map = HashMap::<Tagged, u32>::new();
+        for (i, name) in ["a", "b", "c", "d", "e", "f"].iter().enumerate() {
+            map.insert(tagged(name, i as u32), i as u32);
+        }
+        // Shift some of them back.
+        for name in ["a", "c", "e"] {
+            map.remove(&tagged(name, 0));
+        }
+
+        for (i, name) in [(1, "b"), (3, "d"), (5, "f")] {
+            let (key, &value) = map.get_key_value(&tagged(name, 99)).unwrap();
+            assert_eq!((key.name, key.id, value), (name, i, i));
+            assert_eq!(map.get(&tagged(name, 99)), Some(&value));
+        }
+        assert_eq!(map.get_key_value(&tagged("a", 0)), None);
+    }
+
+    // Hashes a u32 to itself, so it's easy to tell where keys will go.
+    #[derive(Default)]
+    struct IdentityHasher(u64);
+
+    impl Hasher for IdentityHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {
+            unimplemented!("only u32 keys can be hashed");
+        }
+
+        fn write_u32(&mut self, n: u32) {
+            self.0 = n as u64;
+        }
+    }
+
+    type Identity = BuildHasherDefault<IdentityHasher>;
+
+    #[test]
+    fn custom_hasher() {
+        // Every multiple of 13 starts probing from slot 0.
+        let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+        for x in 0..4 {
+            map.insert(x * 13, x);
+        }
+        for x in 0..4 {
+            assert!(matches!(map.table[x as usize], Slot::Some(_, k, _) if k == x * 13));
+            assert_eq!(map.probe_length(&(x * 13)), Some(x as usize));
+        }
+        assert_eq!(map.max_probe_length(), 3);
+        assert_eq!(map.average_probe_length(), 1.5);
+
+        // The rest shift back to fill the gap.
+        assert_eq!(map.remove(&13), Some(1));
+        assert_eq!(map.get(&26), Some(&2));
+        assert_eq!(map.get(&39), Some(&3));
+        assert_eq!(map.get(&13), None);
+        assert!(matches!(map.table[1], Slot::Some(_, 26, 2)));
+        assert_eq!(map.probe_length(&39), Some(2));
+        assert_eq!(map.probe_length(&13), None);
+        map.insert(52, 4);
+        assert!(matches!(map.table[3], Slot::Some(_, 52, 4)));
+
+        // Growing spreads them back out.
+        let mut map: HashMap<u32, u32, Identity> = (0..20).map(|x| (x * 13, x)).collect();
+        assert!(map.raw_capacity() > 20);
+        assert_eq!(map.max_probe_length(), 0);
+        for x in 0..20 {
+            assert_eq!(map[x * 13], x);
+        }
+        assert_eq!(map.entry(7).or_insert(1), &1);
+    }
+
+    // The slot key 0 is in.
+    fn slot_of_zero<S>(map: &HashMap<u32, u32, S>) -> usize {
+        map.table
+            .iter()
+            .position(|slot| matches!(slot, Slot::Some(_, 0, _)))
+            .unwrap()
+    }
+
+    #[test]
+    fn random_layouts() {
+        let mut slots = HashSet::new();
+        for _ in 0..50 {
+            let map: HashMap<u32, u32> = (0..5).map(|x| (x, x)).collect();
+            slots.insert(slot_of_zero(&map));
+        }
+        assert!(slots.len() > 1, "key 0 was always in slot {:?}", slots);
+
+        for _ in 0..10 {
+            let map: HashMap<u32, u32, FixedHashBuilder> = (0..5).map(|x| (x, x)).collect();
+            assert_eq!(slot_of_zero(&map), home(0, super::INITIAL_SIZE));
+        }
+    }
+
+    #[test]
+    fn tombstones_get_cleared() {
+        let mut map = quadratic_map(RandomState::new());
+        for x in 0..100_000 {
+            map.insert(x, x);
+            if x >= 5 {
+                assert_eq!(map.remove(&(x - 5)), Some(x - 5));
+            }
+
+            // There are always empty slots left to stop probes.
+            if x % 1000 == 0 {
+                let empty = map.table.iter().filter(|slot| matches!(slot, Slot::None));
+                assert!(
+                    empty.count() as f32
+                        >= map.raw_capacity() as f32 * (1.0 - MAX_LOAD_FACTOR) - 1.0
+                );
+            }
+        }
+        assert_eq!(map.raw_capacity(), 16);
+        assert_eq!(map.len(), 5);
+        let used = map
+            .table
+            .iter()
+            .filter(|slot| !matches!(slot, Slot::None))
+            .count();
+        assert_eq!(map.used, used);
+        for x in 99_995..100_000 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+        assert_eq!(map.get(&0), None);
+    }
+
+    #[test]
+    fn tombstone_counts() {
+        fn counts<S: BuildHasher>(map: &HashMap<u32, u32, S>) -> (usize, usize, usize) {
+            map.assert_invariants();
+            (map.len(), map.tombstone_count(), map.occupied_slots())
+        }
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        assert_eq!(counts(&map), (0, 0, 0));
+        for x in 0..8 {
+            map.insert(x, x);
+        }
+        assert_eq!(counts(&map), (8, 0, 8));
+
+        // Removing leaves a tombstone, unless there was nothing to remove.
+        for x in 0..6 {
+            map.remove(&x);
+        }
+        assert_eq!(map.remove(&0), None);
+        assert_eq!(counts(&map), (2, 6, 8));
+
+        // A removed key goes back in its old slot, or a tombstone before it,
+        // and overwriting takes up nothing new.
+        map.insert(0, 0);
+        map.insert(6, 60);
+        assert_eq!(counts(&map), (3, 5, 8));
+
+        // Rebuilding, to make room or to grow, leaves no tombstones.
+        map.reserve(6);
+        assert_eq!(counts(&map), (3, 0, 3));
+        let capacity = map.raw_capacity();
+        map.remove(&0);
+        while map.raw_capacity() == capacity {
+            map.insert(map.len() as u32 + 100, 0);
+        }
+        assert_eq!(counts(&map), (map.len(), 0, map.len()));
+
+        // Churning through new keys fills up the table with tombstones, until
+        // it's rebuilt at the same size to get rid of them.
+        let capacity = map.raw_capacity();
+        let mut cleared = 0;
+        for x in 1000..2000 {
+            let tombstones = map.tombstone_count();
+            map.insert(x, x);
+            map.remove(&x);
+            let (len, after, occupied) = counts(&map);
+            assert!(occupied <= map.capacity());
+            assert_eq!(len + after, occupied);
+            if after < tombstones {
+                cleared += 1;
+            }
+        }
+        assert_eq!(map.raw_capacity(), capacity);
+        assert!(cleared > 0);
+
+        map.clear();
+        assert_eq!(counts(&map), (0, 0, 0));
+
+        // Other probe strategies move keys back instead.
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..100 {
+            map.insert(x, x);
+        }
+        for x in 0..50 {
+            map.remove(&x);
+        }
+        assert_eq!(counts(&map), (50, 0, 50));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn full_table() {
+        // With 3 slots and going around the load factor, the third key
+        // leaves no empty slots at all.
+        let mut map = HashMap::<u32, u32>::new_capacity(3);
+        for x in 0..3 {
+            map.insert_without_resize(map.hasher().hash_one(x), x, x);
+        }
+        assert_eq!(map.raw_capacity(), 3);
+        assert_eq!(map.get(&7), None);
+        assert!(!map.contains_key(&7));
+        assert_eq!(map.remove(&7), None);
+        assert_eq!(map.get_key_value(&7), None);
+        for x in 0..3 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+
+        // Fill every slot of a bigger table by going around the load factor.
+        let mut map = HashMap::<u32, u32>::new_capacity(4);
+        for x in 0..4 {
+            map.insert_without_resize(map.hasher().hash_one(x), x, x);
+        }
+        assert!(map.table.iter().all(|slot| matches!(slot, Slot::Some(..))));
+        assert_eq!(map.get(&4), None);
+        assert_eq!(map.remove(&4), None);
+        assert_eq!(
+            map.insert_without_resize(map.hasher().hash_one(4), 4, 4),
+            None
+        );
+        assert_eq!(map.entry(5).or_insert(5), &5);
+        for x in 0..6 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+        assert!(map.raw_capacity() > 4);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn zero_capacity() {
+        let maps = vec![
+            HashMap::<u32, u32>::new_capacity(0),
+            HashMap::with_capacity(0),
+            HashMap::default(),
+            HashMap::new(),
+        ];
+        for mut map in maps {
+            assert_eq!(map.len(), 0);
+            assert_eq!(map.raw_capacity(), 0);
+            assert_eq!(map.get(&1), None);
+            assert_eq!(map.get_mut(&1), None);
+            assert_eq!(map.get_key_value(&1), None);
+            assert!(!map.contains_key(&1));
+            assert_eq!(map.remove(&1), None);
+            assert_eq!(map.iter().next(), None);
+            assert_eq!(map.iter_mut().next(), None);
+            assert_eq!(map.keys().next(), None);
+            assert_eq!(map.values().next(), None);
+            assert_eq!(map.values_mut().next(), None);
+            assert_eq!(map.drain().next(), None);
+            map.retain(|_, _| false);
+            assert_eq!(format!("{:?}", map), "{}");
+            assert_eq!(map.raw_capacity(), 0);
+
+            map.insert(1, 2);
+            assert_eq!(map.raw_capacity(), super::INITIAL_SIZE);
+            assert_eq!(map[1], 2);
+        }
+
+        let mut map = HashMap::<u32, u32>::with_capacity(0);
+        assert_eq!(map.entry(3).or_insert(4), &4);
+        assert_eq!(map.raw_capacity(), super::INITIAL_SIZE);
+        assert_eq!(
+            HashMap::<u32, u32>::with_capacity(0).into_iter().next(),
+            None
+        );
+    }
+
+    #[test]
+    fn shrink() {
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..10_000 {
+            map.insert(x, x);
+        }
+        map.retain(|&k, _| k % 100 == 0);
+        assert_eq!(map.len(), 100);
+        let capacity = map.raw_capacity();
+
+        map.shrink_to(1000);
+        assert_eq!(map.raw_capacity(), table_size_for(1000, MAX_LOAD_FACTOR));
+        map.shrink_to(5000);
+        assert_eq!(map.raw_capacity(), table_size_for(1000, MAX_LOAD_FACTOR));
+
+        map.shrink_to_fit();
+        assert_eq!(map.raw_capacity(), table_size_for(100, MAX_LOAD_FACTOR));
+        assert!(map.raw_capacity() < capacity / 50);
+        for x in 0..10_000 {
+            let expected = if x % 100 == 0 { Some(&x) } else { None };
+            assert_eq!(map.get(&x), expected);
+        }
+
+        // That's exactly full, so one more grows it.
+        assert_eq!(map.capacity(), 100);
+        map.insert(1, 1);
+        assert!(map.raw_capacity() > table_size_for(100, MAX_LOAD_FACTOR));
+
+        let mut map = HashMap::<u32, u32>::with_capacity(1000);
+        map.insert(1, 1);
+        map.shrink_to_fit();
+        assert_eq!(map.raw_capacity(), super::INITIAL_SIZE);
+    }
+
+    #[test]
+    fn reserve() {
+        let mut map = HashMap::<u32, u32>::new();
+        map.reserve(0);
+        assert_eq!(map.raw_capacity(), 0);
+
+        map.reserve(10_000);
+        let capacity = map.raw_capacity();
+        assert_eq!(capacity, table_size_for(10_000, MAX_LOAD_FACTOR));
+        for x in 0..10_000 {
+            map.insert(x, x);
+            assert_eq!(map.raw_capacity(), capacity);
+        }
+        map.reserve(0);
+        assert_eq!(map.raw_capacity(), capacity);
+
+        // Tombstones count against the room left, so reserving clears them.
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        for x in 0..8 {
+            map.insert(x, x);
+        }
+        for x in 0..6 {
+            map.remove(&x);
+        }
+        assert_eq!(map.used, 8);
+        map.reserve(6);
+        assert_eq!(map.raw_capacity(), 16);
+        assert_eq!(map.used, 2);
+        let table = map.table.as_ptr();
+        for x in 10..16 {
+            map.insert(x, x);
+        }
+        assert_eq!(map.table.as_ptr(), table);
+    }
+
+    #[test]
+    fn exact_capacity() {
+        // However the room was made, capacity() entries go in without the
+        // table being reallocated, and the next one grows it.
+        for &n in &[1, 2, 3, 8, 9, 10, 100, 1000, 1234] {
+            let mut with_capacity = HashMap::<u32, u32>::with_capacity(n);
+            let mut reserved = HashMap::<u32, u32>::new();
+            reserved.reserve(n);
+            let mut low_factor = HashMap::<u32, u32>::with_load_factor(n, 0.4);
+            let mut tiny_factor = HashMap::<u32, u32>::with_load_factor(n, 0.05);
+            let maps = [
+                &mut with_capacity,
+                &mut reserved,
+                &mut low_factor,
+                &mut tiny_factor,
+            ];
+            for map in maps {
+                let capacity = map.capacity();
+                assert!(capacity >= n);
+                assert!(capacity <= map.raw_capacity());
+                let table = map.table.as_ptr();
+                for x in 0..capacity as u32 {
+                    map.insert(x, x);
+                }
+                assert_eq!(map.table.as_ptr(), table);
+                map.insert(capacity as u32, 0);
+                assert_ne!(map.table.as_ptr(), table);
+                assert!(map.len() <= map.capacity());
+            }
+        }
+
+        // A factor so small the first table's too small for even one item
+        // still makes room for them as they go in.
+        let mut map = HashMap::<u32, u32>::with_load_factor(0, 0.05);
+        assert_eq!(map.capacity(), 0);
+        for x in 0..100 {
+            map.insert(x, x);
+            assert!(map.len() <= map.capacity());
+        }
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut map = HashMap::<u32, u32>::new();
+        assert_eq!(map.try_reserve(1000), Ok(()));
+        let mut reserved = HashMap::<u32, u32>::new();
+        reserved.reserve(1000);
+        assert_eq!(map.raw_capacity(), reserved.raw_capacity());
+        assert_eq!(map.raw_capacity(), table_size_for(1000, MAX_LOAD_FACTOR));
+
+        // Too many slots to count, or too many bytes to allocate, leaves the
+        // map alone.
+        map.insert(1, 2);
+        let table = map.table.as_ptr();
+        for additional in [usize::MAX, usize::MAX / 2] {
+            assert_eq!(
+                map.try_reserve(additional),
+                Err(TryReserveError::CapacityOverflow)
+            );
+        }
+        assert_eq!(map.table.as_ptr(), table);
+        assert_eq!(map.get(&1), Some(&2));
+    }
+
+    #[test]
+    fn clear() {
+        let drops = Rc::new(Cell::new(0));
+        let mut map = HashMap::<u32, DropCounter>::new();
+        for x in 0..100 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+        map.remove(&0);
+        let capacity = map.raw_capacity();
+
+        map.clear();
+        assert_eq!(drops.get(), 100);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.used, 0);
+        assert_eq!(map.raw_capacity(), capacity);
+        assert!(map.get(&1).is_none());
+
+        let table = map.table.as_ptr();
+        for x in 0..50 {
+            map.insert(x, DropCounter(drops.clone()));
+        }
+        assert_eq!(map.table.as_ptr(), table);
+        assert_eq!(map.len(), 50);
+
+        map.clear_and_shrink();
+        assert_eq!(drops.get(), 150);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.raw_capacity(), super::INITIAL_SIZE);
+    }
+
+    #[test]
+    fn load_factors() {
+        // Puts 0, 1, 2... in a map until it grows, and says how many fit.
+        fn fits(max_load_factor: f32) -> usize {
+            let mut map = HashMap::<u32, u32>::with_load_factor(0, max_load_factor);
+            map.insert(0, 0);
+            let capacity = map.raw_capacity();
+            let mut x = 1;
+            while map.raw_capacity() == capacity {
+                map.insert(x, x);
+                assert!(map.len() <= map.capacity());
+                x += 1;
+            }
+            x as usize - 1
+        }
+        assert!(fits(0.4) < fits(MAX_LOAD_FACTOR));
+        assert!(fits(MAX_LOAD_FACTOR) < fits(0.85));
+
+        // Room for the capacity asked for is made with the map's own factor.
+        let map = HashMap::<u32, u32>::with_load_factor(100, 0.4);
+        assert_eq!(map.raw_capacity(), table_size_for(100, 0.4));
+        assert!(map.raw_capacity() > table_size_for(100, MAX_LOAD_FACTOR));
+        assert_eq!(
+            HashMap::<u32, u32>::with_load_factor(0, 0.4).raw_capacity(),
+            0
+        );
     }
 
     #[test]
-    fn resize() {
+    #[should_panic(expected = "max load factor must be between 0 and 1")]
+    fn bad_load_factor() {
+        HashMap::<u32, u32>::with_load_factor(10, 1.0);
+    }
+
+    // Every key's probe length, in table order.
+    fn probe_lengths<S: BuildHasher>(map: &HashMap<u32, u32, S>) -> Vec<usize> {
+        map.keys()
+            .map(|key| map.probe_length(key).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn prime_sizes() {
+        let build = |policy| {
+            let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+            map.set_growth_policy(policy);
+            for x in 0..15 {
+                map.insert(x * 9, x);
+            }
+            for x in 0..15 {
+                assert_eq!(map.get(&(x * 9)), Some(&x));
+            }
+            map
+        };
+
+        // Multiples of 9 can only start from 3 of the 27 slots Doubling
+        // grows to, so they pile up behind each other.
+        let doubling = build(GrowthPolicy::Doubling);
+        assert_eq!(doubling.raw_capacity(), 27);
+        assert!(
+            doubling.max_probe_length() >= 4,
+            "{}",
+            doubling.max_probe_length()
+        );
+
+        // In 29 slots, every one of them gets its own.
+        let prime = build(GrowthPolicy::Prime);
+        assert_eq!(prime.raw_capacity(), 29);
+        assert_eq!(prime.max_probe_length(), 0);
+
+        // Every size the policy picks is a prime from the table.
+        let mut map = HashMap::<u32, u32>::with_growth_policy(100, GrowthPolicy::Prime);
+        assert_eq!(map.raw_capacity(), 257);
+        map.grow(300);
+        assert_eq!(map.raw_capacity(), 521);
+        for x in 0..1000 {
+            map.insert(x, x);
+            assert!(PRIMES.contains(&(map.raw_capacity() as u64)));
+        }
+        map.shrink_to_fit();
+        assert!(PRIMES.contains(&(map.raw_capacity() as u64)));
+        map.clear_and_shrink();
+        assert_eq!(map.raw_capacity(), 13);
+
+        // Switching policy rebuilds the table at an allowed size.
+        let mut map = HashMap::<u32, u32>::with_capacity(100);
+        map.insert(1, 2);
+        assert_eq!(map.raw_capacity(), 150);
+        map.set_growth_policy(GrowthPolicy::Prime);
+        assert_eq!(map.raw_capacity(), 257);
+        assert_eq!(map.get(&1), Some(&2));
+    }
+
+    #[test]
+    fn power_of_two_sizes() {
+        let mut map = HashMap::<u32, u32>::with_growth_policy(100, GrowthPolicy::PowerOfTwo);
+        assert_eq!(map.raw_capacity(), 256);
+        for x in 0..1000 {
+            map.insert(x, x);
+            assert!(map.raw_capacity().is_power_of_two());
+        }
+        for x in 0..1000 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+        map.grow(3000);
+        assert_eq!(map.raw_capacity(), 4096);
+        map.clear_and_shrink();
+        assert_eq!(map.raw_capacity(), 16);
+
+        // Multiples of 64 all have the same low bits, so masking the hash
+        // alone would start every one of them from slot 0. Mixing it first
+        // spreads them out.
+        let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+        map.set_growth_policy(GrowthPolicy::PowerOfTwo);
+        for x in 0..40 {
+            map.insert(x * 64, x);
+        }
+        assert_eq!(map.raw_capacity(), 64);
+        assert!(map.max_probe_length() < 8, "{}", map.max_probe_length());
+        for x in 0..40 {
+            assert_eq!(map.get(&(x * 64)), Some(&x));
+        }
+
+        // Switching to or from it moves keys even when the size is right.
         let mut map = HashMap::<u32, u32>::new();
-        map.put(1, 2);
-        map.put(2, 4);
-        map.put(3, 6);
-        map.grow(100);
-        map.put(4, 8);
-        map.put(5, 10);
+        map.grow(64);
+        for x in 0..20 {
+            map.insert(x, x);
+        }
+        map.set_growth_policy(GrowthPolicy::PowerOfTwo);
+        assert_eq!(map.raw_capacity(), 64);
+        for x in 0..20 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+        map.set_growth_policy(GrowthPolicy::Doubling);
+        for x in 0..20 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+    }
 
-        for x in 1..6 {
-            assert_eq!(map.remove(x), Some(x * 2));
+    fn quadratic_map<S: BuildHasher>(hash_builder: S) -> HashMap<u32, u32, S> {
+        let mut map = HashMap::with_hasher(hash_builder);
+        map.set_growth_policy(GrowthPolicy::PowerOfTwo);
+        map.set_probe_strategy(ProbeStrategy::Quadratic);
+        map
+    }
+
+    #[test]
+    fn quadratic_probing() {
+        // Every slot gets visited, once, from anywhere.
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        for size in [1, 2, 16, 1024] {
+            map.grow(size);
+            for home in [0, 1, size - 1] {
+                let slots: HashSet<usize> =
+                    (0..size).map(|scan| map.probe_slot(home, scan)).collect();
+                assert_eq!(slots.len(), size);
+            }
+        }
+
+        // Filled right up to the limit at each size, everything's still
+        // there.
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        for x in 0..5000 {
+            let capacity = map.raw_capacity();
+            map.insert(x, x);
+            if map.raw_capacity() != capacity {
+                assert!(map.raw_capacity().is_power_of_two());
+                for y in 0..x {
+                    assert_eq!(map.get(&y), Some(&y), "x {}", x);
+                }
+            }
+        }
+        for x in (0..5000).step_by(3) {
+            assert_eq!(map.remove(&x), Some(x));
+        }
+        for x in 0..5000 {
+            assert_eq!(map.contains_key(&x), x % 3 != 0);
+        }
+
+        // Keys which only differ in their top bits all fit too.
+        let mut map = quadratic_map(Identity::default());
+        map.grow(64);
+        let first = map.raw_capacity();
+        let keys: Vec<u32> = (0..100).map(|x| x << 24).collect();
+        for &key in &keys {
+            map.insert(key, key);
+        }
+        assert!(map.raw_capacity() > first);
+        for &key in &keys {
+            assert_eq!(map.get(&key), Some(&key));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "quadratic probing needs GrowthPolicy::PowerOfTwo")]
+    fn quadratic_needs_power_of_two() {
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        map.set_growth_policy(GrowthPolicy::Prime);
+    }
+
+    // Fills a table of 211 slots with groups of 3 keys that share a home
+    // slot, with the homes 2 apart, so they all run together into one long
+    // cluster, which wraps around the end of the table. The keys go in
+    // backwards, so later ones have to get past earlier ones to find room.
+    fn clustered(strategy: ProbeStrategy) -> (HashMap<u32, u32, Identity>, Vec<u32>) {
+        let mut map = HashMap::with_hasher(Identity::default());
+        map.set_probe_strategy(strategy);
+        map.grow(211);
+        let keys: Vec<u32> = (0..126)
+            .map(|x| (180 + 2 * (x / 3)) % 211 + (x % 3) * 211)
+            .collect();
+        for &key in keys.iter().rev() {
+            map.insert(key, key);
+        }
+        assert_eq!(map.raw_capacity(), 211);
+        (map, keys)
+    }
+
+    fn variance(lengths: &[usize]) -> f64 {
+        let mean = lengths.iter().sum::<usize>() as f64 / lengths.len() as f64;
+        let squares: f64 = lengths.iter().map(|&l| (l as f64 - mean).powi(2)).sum();
+        squares / lengths.len() as f64
+    }
+
+    #[test]
+    fn robin_hood() {
+        let (linear, _) = clustered(ProbeStrategy::Linear);
+        let (robin_hood, keys) = clustered(ProbeStrategy::RobinHood);
+        for &key in &keys {
+            assert_eq!(robin_hood.get(&key), Some(&key));
+        }
+        assert_eq!(robin_hood.get(&(180 + 211 * 3)), None);
+
+        // The keys take up the same slots, so they're as far from home in
+        // total, but it's shared out much more evenly.
+        let (linear, robin_hood) = (probe_lengths(&linear), probe_lengths(&robin_hood));
+        assert_eq!(
+            linear.iter().sum::<usize>(),
+            robin_hood.iter().sum::<usize>()
+        );
+        assert!(variance(&robin_hood) < variance(&linear) / 2.0);
+        assert!(robin_hood.iter().max() < linear.iter().max());
+    }
+
+    #[test]
+    fn robin_hood_removal() {
+        let (mut map, keys) = clustered(ProbeStrategy::RobinHood);
+        for &key in keys.iter().step_by(2) {
+            assert_eq!(map.remove(&key), Some(key));
+        }
+        match map.entry(keys[1]) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), keys[1]),
+            Entry::Vacant(_) => panic!("entry should be occupied"),
+        }
+
+        // Nothing's left behind, and along the run each key is at most one
+        // slot further from home than the one before it.
+        assert!(map.table.iter().all(|slot| !matches!(slot, Slot::Removed)));
+        assert_eq!(map.used, map.len());
+        for i in 0..map.raw_capacity() {
+            let next = (i + 1) % map.raw_capacity();
+            if let (Slot::Some(..), Slot::Some(..)) = (&map.table[i], &map.table[next]) {
+                assert!(map.probe_distance(next) <= map.probe_distance(i) + 1);
+            }
+        }
+        for (i, &key) in keys.iter().enumerate() {
+            let expected = if i % 2 == 0 || i == 1 {
+                None
+            } else {
+                Some(&key)
+            };
+            assert_eq!(map.get(&key), expected);
+        }
+
+        // Putting keys back displaces the others again.
+        for &key in keys.iter().step_by(2) {
+            *map.entry(key).or_insert(0) += key;
+        }
+        map.retain(|&key, _| key % 2 == 0);
+        assert!(map.table.iter().all(|slot| !matches!(slot, Slot::Removed)));
+        for &key in &keys {
+            assert_eq!(map.contains_key(&key), key % 2 == 0);
+        }
+    }
+
+    // Which key is in each slot.
+    fn layout<S>(map: &HashMap<u32, u32, S>) -> Vec<Option<u32>> {
+        let slots = map.table.iter();
+        slots
+            .map(|slot| match slot {
+                Slot::Some(_, key, _) => Some(*key),
+                Slot::None => None,
+                Slot::Removed => panic!("linear probing left a tombstone"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backward_shift() {
+        // In 13 slots with the identity hasher, 11, 24 and 37 start from
+        // slot 11, 25 from 12 and 13 from 0, so they make one run which
+        // wraps around the end of the table, with 13 at home in the middle.
+        let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+        for key in [11, 24, 13, 37, 25] {
+            map.insert(key, key);
+        }
+        let mut expected = vec![None; 13];
+        expected[11..].copy_from_slice(&[Some(11), Some(24)]);
+        expected[..3].copy_from_slice(&[Some(13), Some(37), Some(25)]);
+        assert_eq!(layout(&map), expected);
+
+        // 24 moves back into 11's slot, then 13 can't move back past its
+        // home, so 37 and 25 move past it, round the end of the table.
+        assert_eq!(map.remove(&11), Some(11));
+        expected[11..].copy_from_slice(&[Some(24), Some(37)]);
+        expected[..3].copy_from_slice(&[Some(13), Some(25), None]);
+        assert_eq!(layout(&map), expected);
+
+        // 25 goes back across the end of the table to where 13 was, but no
+        // further.
+        assert_eq!(map.remove(&13), Some(13));
+        expected[..3].copy_from_slice(&[Some(25), None, None]);
+        assert_eq!(layout(&map), expected);
+        assert_eq!(map.used, 3);
+        for key in [24, 37, 25] {
+            assert_eq!(map.get(&key), Some(&key));
+        }
+        for key in [11, 13] {
+            assert_eq!(map.get(&key), None);
+        }
+
+        // Taking out a key at the end of the run moves nothing.
+        assert_eq!(map.remove(&25), Some(25));
+        expected[0] = None;
+        assert_eq!(layout(&map), expected);
+
+        // Lots of keys coming and going never leave anything behind.
+        let mut map = HashMap::<u32, u32>::new();
+        for x in 0..10_000 {
+            map.insert(x, x);
+            if x >= 50 {
+                assert_eq!(map.remove(&(x - 50)), Some(x - 50));
+            }
+        }
+        layout(&map);
+        assert_eq!(map.used, 50);
+        for x in 9950..10_000 {
+            assert_eq!(map.get(&x), Some(&x));
+        }
+        map.retain(|&x, _| x % 2 == 0);
+        layout(&map);
+        assert_eq!(map.used, 25);
+    }
+
+    #[test]
+    fn removing_in_place() {
+        // The same run round the end of the table as in backward_shift.
+        let keys = [11, 24, 13, 37, 25];
+        let wrapped = || {
+            let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+            for key in keys {
+                map.insert(key, key);
+            }
+            map
+        };
+        let mut expected = vec![None; 13];
+        expected[11..].copy_from_slice(&[Some(24), Some(37)]);
+        expected[0] = Some(25);
+
+        // 24, 37 and 25 shift back as 11 and 13 go, without any of them
+        // being seen twice or missed.
+        let mut map = wrapped();
+        let buffer = map.table.as_ptr();
+        let mut seen = Vec::new();
+        map.retain(|&key, _| {
+            seen.push(key);
+            key != 11 && key != 13
+        });
+        seen.sort();
+        assert_eq!(seen, [11, 13, 24, 25, 37]);
+        assert_eq!(layout(&map), expected);
+        assert_eq!(map.table.as_ptr(), buffer);
+        map.assert_invariants();
+
+        let mut map = wrapped();
+        let buffer = map.table.as_ptr();
+        let mut seen = Vec::new();
+        let mut taken: Vec<u32> = map
+            .extract_if(|&key, _| {
+                seen.push(key);
+                key == 11 || key == 13
+            })
+            .map(|(key, _)| key)
+            .collect();
+        seen.sort();
+        taken.sort();
+        assert_eq!(seen, [11, 13, 24, 25, 37]);
+        assert_eq!(taken, [11, 13]);
+        assert_eq!(layout(&map), expected);
+        assert_eq!(map.table.as_ptr(), buffer);
+        map.assert_invariants();
+
+        // Under every strategy, nothing's rebuilt, and only quadratic
+        // probing leaves tombstones.
+        for &(policy, strategy) in &SETUPS {
+            let mut map = HashMap::<u32, u32>::new();
+            map.set_growth_policy(policy);
+            map.set_probe_strategy(strategy);
+            for x in 0..1000 {
+                map.insert(x, x);
+            }
+            let buffer = map.table.as_ptr();
+            let mut seen = 0;
+            map.retain(|&x, _| {
+                seen += 1;
+                x % 3 != 0
+            });
+            assert_eq!(seen, 1000);
+            assert_eq!(map.extract_if(|&x, _| x % 3 == 1).count(), 333);
+            assert_eq!(map.len(), 333);
+            assert_eq!(map.table.as_ptr(), buffer);
+            let tombstones = if strategy == ProbeStrategy::Quadratic {
+                667
+            } else {
+                0
+            };
+            assert_eq!(map.tombstone_count(), tombstones);
+            map.assert_invariants();
+            for x in 0..1000 {
+                assert_eq!(map.contains_key(&x), x % 3 == 2);
+            }
+        }
+    }
+
+    #[test]
+    fn probe_stats() {
+        // Every key's probe length is where search finds it, in every
+        // configuration.
+        let configs = [
+            (GrowthPolicy::Doubling, ProbeStrategy::Linear),
+            (GrowthPolicy::Prime, ProbeStrategy::Linear),
+            (GrowthPolicy::PowerOfTwo, ProbeStrategy::Linear),
+            (GrowthPolicy::PowerOfTwo, ProbeStrategy::Quadratic),
+            (GrowthPolicy::Doubling, ProbeStrategy::RobinHood),
+        ];
+        for &(policy, strategy) in &configs {
+            let mut map = HashMap::<u32, u32, FixedHashBuilder>::default();
+            assert_eq!(
+                (map.max_probe_length(), map.average_probe_length()),
+                (0, 0.0)
+            );
+            map.set_growth_policy(policy);
+            map.set_probe_strategy(strategy);
+            for x in 0..500 {
+                map.insert(x, x);
+            }
+            for x in (0..500).step_by(3) {
+                map.remove(&x);
+            }
+
+            let mut lengths = Vec::new();
+            for x in 0..500 {
+                let length = map.probe_length(&x);
+                if x % 3 == 0 {
+                    assert_eq!(length, None);
+                    continue;
+                }
+                let length = length.unwrap();
+                let home = map.home_slot(FixedHashBuilder::default().hash_one(x));
+                let found = |scan| matches!(map.table[map.probe_slot(home, scan)], Slot::Some(_, k, _) if k == x);
+                assert!(found(length), "{:?} {:?}", policy, strategy);
+                assert!(!(0..length).any(found));
+                lengths.push(length);
+            }
+            assert_eq!(map.max_probe_length(), *lengths.iter().max().unwrap());
+            let average = lengths.iter().sum::<usize>() as f32 / lengths.len() as f32;
+            assert_eq!(map.average_probe_length(), average);
+        }
+
+        // Filling a table of 13 with keys which all start from slot 0 lines
+        // them up, until it grows to 27 slots, where they all get their
+        // own.
+        let mut map = HashMap::<u32, u32, Identity>::with_hasher(Identity::default());
+        for x in 0..8 {
+            map.insert(x * 13, x);
+        }
+        assert_eq!(map.raw_capacity(), 13);
+        assert_eq!(map.max_probe_length(), 7);
+        assert_eq!(map.average_probe_length(), 3.5);
+        map.insert(8 * 13, 8);
+        assert_eq!(map.raw_capacity(), 27);
+        assert_eq!(map.max_probe_length(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        // Tombstones and a few grows don't come through, just the entries.
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        for x in 0..200 {
+            map.insert(x, x * 2);
+        }
+        for x in (0..200).step_by(3) {
+            map.remove(&x);
+        }
+        let json = serde_json::to_string(&map).unwrap();
+        let read: HashMap<u32, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.len(), map.len());
+        for (key, value) in map.iter() {
+            assert_eq!(read.get(key), Some(value));
+        }
+
+        let mut map = HashMap::<String, u64>::new();
+        for x in 0..100 {
+            map.insert(format!("key {}", x), x);
+        }
+        map.remove("key 10");
+        let json = serde_json::to_string(&map).unwrap();
+        let read: HashMap<String, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.len(), 99);
+        assert_eq!(read.get("key 99"), Some(&99));
+        assert_eq!(read.get("key 10"), None);
+
+        let map: HashMap<String, u64> = serde_json::from_str("{}").unwrap();
+        assert!(map.is_empty());
+        assert!(serde_json::from_str::<HashMap<String, u64>>("[1, 2]").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_matches_std() {
+        let std_map: std::collections::HashMap<String, u64> =
+            (0..50).map(|x| (x.to_string(), x)).collect();
+        let json = serde_json::to_string(&std_map).unwrap();
+        let map: HashMap<String, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map.len(), 50);
+        for (key, value) in &std_map {
+            assert_eq!(map.get(key), Some(value));
+        }
+
+        let json = serde_json::to_string(&map).unwrap();
+        let back: std::collections::HashMap<String, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, std_map);
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let calls = Cell::new(0);
+        let square = |x: u32| {
+            calls.set(calls.get() + 1);
+            x * x
+        };
+
+        let mut map = HashMap::<u32, u32>::new();
+        assert_eq!(map.get_or_insert_with(3, || square(3)), &mut 9);
+        assert_eq!(map.get_or_insert_with(3, || square(3)), &mut 9);
+        assert_eq!(calls.get(), 1);
+
+        // The map grows partway through, and each value goes in once and is
+        // written through the reference it comes back as.
+        let capacity = map.raw_capacity();
+        for round in 0..3 {
+            for x in 0..100 {
+                *map.get_or_insert_with(x, || square(x)) += 1;
+            }
+            assert_eq!(calls.get(), 100, "round {}", round);
+        }
+        assert!(map.raw_capacity() > capacity);
+        for x in 0..100 {
+            assert_eq!(map.get(&x), Some(&(x * x + 3)));
+        }
+    }
+
+    #[test]
+    fn remove_entry() {
+        for strategy in [ProbeStrategy::Linear, ProbeStrategy::Quadratic] {
+            let mut map = HashMap::with_growth_policy(0, GrowthPolicy::PowerOfTwo);
+            map.set_probe_strategy(strategy);
+            for i in 0..20 {
+                map.insert(format!("key {}", i), i);
+            }
+
+            // The key that comes back is the one in the map, not the one it
+            // was looked up with.
+            let probe = String::from("key 7");
+            let stored = map.get_key_value("key 7").unwrap().0.as_ptr();
+            let (key, value) = map.remove_entry(&probe).unwrap();
+            assert_eq!((key.as_str(), value), ("key 7", 7));
+            assert_eq!(key.as_ptr(), stored);
+            assert_ne!(key.as_ptr(), probe.as_ptr());
+
+            assert_eq!(map.remove_entry("key 7"), None);
+            assert_eq!(map.len(), 19);
+            for i in (0..20).filter(|&i| i != 7) {
+                assert_eq!(map.get(format!("key {}", i).as_str()), Some(&i));
+            }
+        }
+    }
+
+    thread_local! {
+        static KEYS_MADE: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // A String key that counts how many times it's been made from a &str.
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct CountedKey(String);
+
+    impl From<&str> for CountedKey {
+        fn from(key: &str) -> CountedKey {
+            KEYS_MADE.with(|made| made.set(made.get() + 1));
+            CountedKey(key.to_string())
+        }
+    }
+
+    impl std::borrow::Borrow<str> for CountedKey {
+        fn borrow(&self) -> &str {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn entry_ref() {
+        let words = "the cat sat on the mat and the dog sat on the cat";
+        let mut counts: HashMap<CountedKey, usize> = HashMap::new();
+        for word in words.split(' ') {
+            *counts.entry_ref(word).or_insert(0) += 1;
+        }
+
+        // Only the first of each word made a key, through growing as well.
+        let distinct: HashSet<&str> = words.split(' ').collect();
+        assert_eq!(KEYS_MADE.with(Cell::get), distinct.len());
+        assert_eq!(counts.get("the"), Some(&4));
+        assert_eq!(counts.get("dog"), Some(&1));
+
+        let made = KEYS_MADE.with(Cell::get);
+        let entry = counts.entry_ref("cat").and_modify(|count| *count *= 10);
+        assert_eq!(entry.key(), "cat");
+        assert_eq!(entry.or_insert_with(|| unreachable!()), &mut 20);
+        match counts.entry_ref("bird") {
+            EntryRef::Vacant(entry) => assert_eq!(entry.key(), "bird"),
+            EntryRef::Occupied(_) => panic!("bird isn't in the map"),
+        }
+        assert_eq!(KEYS_MADE.with(Cell::get), made);
+        assert_eq!(counts.get("bird"), None);
+    }
+
+    // An operation to run on both this map and std's, for the differential
+    // tests below. Keys are kept small so that they collide a lot.
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(u32, u32),
+        Get(u32),
+        Remove(u32),
+        ContainsKey(u32),
+        Clear,
+        Reserve(usize),
+        Grow(usize),
+    }
+
+    // The growth policies and probe strategies that work together.
+    const SETUPS: [(GrowthPolicy, ProbeStrategy); 4] = [
+        (GrowthPolicy::Doubling, ProbeStrategy::Linear),
+        (GrowthPolicy::Prime, ProbeStrategy::RobinHood),
+        (GrowthPolicy::PowerOfTwo, ProbeStrategy::Quadratic),
+        (GrowthPolicy::PowerOfTwo, ProbeStrategy::RobinHood),
+    ];
+
+    // Runs `ops` on a map set up with `setup` and on a std HashMap, checking
+    // that every result and length matches along the way, and the contents
+    // at the end.
+    fn check_against_std(setup: (GrowthPolicy, ProbeStrategy), ops: &[Op]) {
+        let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+        map.set_growth_policy(setup.0);
+        map.set_probe_strategy(setup.1);
+        let mut std_map = std::collections::HashMap::new();
+
+        for (step, op) in ops.iter().enumerate() {
+            match *op {
+                Op::Insert(key, value) => {
+                    assert_eq!(
+                        map.insert(key, value),
+                        std_map.insert(key, value),
+                        "{:?}",
+                        op
+                    )
+                }
+                Op::Get(key) => assert_eq!(map.get(&key), std_map.get(&key), "{:?}", op),
+                Op::Remove(key) => assert_eq!(map.remove(&key), std_map.remove(&key), "{:?}", op),
+                Op::ContainsKey(key) => {
+                    assert_eq!(
+                        map.contains_key(&key),
+                        std_map.contains_key(&key),
+                        "{:?}",
+                        op
+                    )
+                }
+                Op::Clear => {
+                    map.clear();
+                    std_map.clear();
+                }
+                Op::Reserve(additional) => map.reserve(additional),
+                Op::Grow(extra) => map.grow(map.len() + extra + 1),
+            }
+            assert_eq!(map.len(), std_map.len(), "after step {}, {:?}", step, op);
+            map.assert_invariants();
+        }
+
+        let mut items: Vec<(u32, u32)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        let mut expected: Vec<(u32, u32)> = std_map.into_iter().collect();
+        items.sort();
+        expected.sort();
+        assert_eq!(items, expected);
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let key = 0..32u32;
+        prop_oneof![
+            4 => (key.clone(), any::<u32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+            2 => key.clone().prop_map(Op::Get),
+            3 => key.clone().prop_map(Op::Remove),
+            1 => key.prop_map(Op::ContainsKey),
+            1 => Just(Op::Clear),
+            1 => (0..64usize).prop_map(Op::Reserve),
+            1 => (0..64usize).prop_map(Op::Grow),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        #[cfg_attr(miri, ignore)]
+        fn matches_std(setup in 0..SETUPS.len(), ops in vec(op(), 0..200)) {
+            check_against_std(SETUPS[setup], &ops);
+        }
+    }
+
+    #[test]
+    fn remove_then_reinsert_colliding() {
+        // Two keys which start probing from the same slot of the first
+        // table, with the second one displaced by the first.
+        let a = 0;
+        let b = (1..).find(|&b| home(b, 13) == home(a, 13)).unwrap();
+        let ops = [
+            Op::Insert(a, 1),
+            Op::Insert(b, 2),
+            Op::Remove(a),
+            Op::Get(b),
+            Op::Insert(a, 3),
+            Op::Remove(b),
+            Op::ContainsKey(b),
+            Op::Get(a),
+            Op::Insert(b, 4),
+            Op::Remove(a),
+            Op::Insert(a, 5),
+            Op::Get(b),
+        ];
+        check_against_std(SETUPS[0], &ops);
+        check_against_std(SETUPS[1], &ops);
+    }
+
+    #[test]
+    fn invariants() {
+        for &setup in &SETUPS {
+            let mut map = HashMap::with_growth_policy(0, setup.0);
+            map.set_probe_strategy(setup.1);
+            map.assert_invariants();
+            for x in 0..50 {
+                map.insert(x, x);
+            }
+            map.retain(|&x, _| x % 3 != 0);
+            map.assert_invariants();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "size doesn't match the slots with items")]
+    fn invariants_wrong_size() {
+        let mut map = HashMap::new();
+        map.insert(1, 1);
+        map.size = 2;
+        map.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "more slots used than the load factor allows")]
+    fn invariants_over_load_factor() {
+        // Growing to a table too small for the items sizes it for them, so
+        // it takes going around the load factor check to overfill one.
+        let mut map: HashMap<u32, u32> = (0..20).map(|x| (x, x)).collect();
+        map.grow(21);
+        assert!(map.len() <= map.capacity());
+        map.assert_invariants();
+        for x in 20..map.capacity() as u32 + 1 {
+            map.insert_without_resize(map.hasher().hash_one(x), x, x);
+        }
+        map.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be found")]
+    fn invariants_lost_key() {
+        // Move a key away from where probing looks for it.
+        let mut map = HashMap::with_hasher(FixedHashBuilder::default());
+        map.insert(0, 0);
+        let slot = slot_of_zero(&map);
+        let moved = (slot + 5) % map.raw_capacity();
+        map.table.swap(slot, moved);
+        map.control.swap(slot, moved);
+        map.assert_invariants();
+    }
+
+    // A map of keys to DropLogs with the same ids, for each setup.
+    fn logged_maps(log: &Rc<RefCell<Vec<u32>>>) -> Vec<HashMap<u32, DropLog>> {
+        let maps = SETUPS.iter().map(|&(policy, strategy)| {
+            let mut map = HashMap::with_growth_policy(0, policy);
+            map.set_probe_strategy(strategy);
+            for x in 0..30 {
+                map.insert(x, DropLog(x, log.clone()));
+            }
+            map
+        });
+        maps.collect()
+    }
+
+    #[test]
+    fn drops_overwritten_values() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for mut map in logged_maps(&log) {
+            log.borrow_mut().clear();
+
+            // The old value goes as soon as it's replaced, and the new one
+            // stays.
+            map.insert(5, DropLog(105, log.clone()));
+            assert_eq!(dropped(&log), &[5]);
+            *map.get_mut(&6).unwrap() = DropLog(106, log.clone());
+            assert_eq!(dropped(&log), &[5, 6]);
+            if let Entry::Occupied(mut entry) = map.entry(7) {
+                entry.insert(DropLog(107, log.clone()));
+            }
+            assert_eq!(dropped(&log), &[5, 6, 7]);
+            assert_eq!(map.get(&5).unwrap().0, 105);
+
+            // Removing gives the value back without dropping it.
+            let removed = map.remove(&8).unwrap();
+            assert_eq!(dropped(&log), &[5, 6, 7]);
+            drop(removed);
+            assert_eq!(dropped(&log), &[5, 6, 7, 8]);
+        }
+    }
+
+    #[test]
+    fn drops_nothing_when_rebuilding() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for mut map in logged_maps(&log) {
+            log.borrow_mut().clear();
+            map.remove(&0);
+            log.borrow_mut().clear();
+
+            map.reserve(500);
+            map.grow(map.raw_capacity() * 2);
+            let strategy = map.probe_strategy();
+            map.set_probe_strategy(ProbeStrategy::Linear);
+            map.set_growth_policy(GrowthPolicy::Prime);
+            map.set_growth_policy(GrowthPolicy::PowerOfTwo);
+            map.set_probe_strategy(strategy);
+            map.shrink_to_fit();
+            for x in 30..100 {
+                map.insert(x, DropLog(x, log.clone()));
+            }
+            assert!(log.borrow().is_empty());
+            assert_eq!(map.len(), 99);
+            map.assert_invariants();
+
+            map.retain(|&key, _| key % 2 == 0);
+            let odd: Vec<u32> = (1..100).step_by(2).collect();
+            assert_eq!(dropped(&log), odd);
+        }
+    }
+
+    #[test]
+    fn drops_everything_once() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for mut map in logged_maps(&log) {
+            log.borrow_mut().clear();
+            map.clear();
+            assert_eq!(dropped(&log), (0..30).collect::<Vec<_>>());
+            drop(map);
+            assert_eq!(log.borrow().len(), 30);
+        }
+
+        // Dropping a map drops what's left in it, after removals have left
+        // gaps, and tombstones under quadratic probing.
+        for mut map in logged_maps(&log) {
+            log.borrow_mut().clear();
+            for x in (0..30).step_by(3) {
+                map.remove(&x);
+            }
+            drop(map);
+            assert_eq!(dropped(&log), (0..30).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn debug_dump() {
+        assert_eq!(HashMap::<u32, u32>::new().debug_dump(), "");
+
+        // Multiples of 13 all start from slot 0, and 1 and 14 from slot 1,
+        // which is taken by then.
+        let mut map = HashMap::with_hasher(Identity::default());
+        for &key in &[0, 13, 26, 1, 14] {
+            map.insert(key, ());
+        }
+        let empty: String = (5..13).map(|i| format!("{:>2}: None\n", i)).collect();
+        let dump = " 0: Some 0, home 0, distance 0
+ 1: Some 13, home 0, distance 1
+ 2: Some 26, home 0, distance 2
+ 3: Some 1, home 1, distance 2
+ 4: Some 14, home 1, distance 3
+";
+        assert_eq!(map.debug_dump(), dump.to_string() + &empty);
+
+        // Removing 13 shifts the rest of the run back a slot.
+        map.remove(&13);
+        let dump = " 0: Some 0, home 0, distance 0
+ 1: Some 26, home 0, distance 1
+ 2: Some 1, home 1, distance 1
+ 3: Some 14, home 1, distance 2
+ 4: None
+";
+        assert_eq!(map.debug_dump(), dump.to_string() + &empty);
+
+        // Quadratic probing jumps further each time, and leaves tombstones.
+        let mut map = quadratic_map(Identity::default());
+        map.grow(8);
+        let keys: Vec<u32> = (0..)
+            .filter(|&key| map.home_slot(key as u64) == 2)
+            .take(3)
+            .collect();
+        for &key in &keys {
+            map.insert(key, key);
+        }
+        map.remove(&keys[1]);
+        let dump = format!(
+            "0: None
+1: None
+2: Some {}, home 2, distance 0
+3: Removed
+4: None
+5: Some {}, home 2, distance 2
+6: None
+7: None
+",
+            keys[0], keys[2]
+        );
+        assert_eq!(map.debug_dump(), dump);
+    }
+
+    thread_local! {
+        static HASHES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Hashes everything to the same value, and counts how many times it's
+    // asked to.
+    #[derive(Default)]
+    struct CollidingHasher;
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            HASHES.with(|hashes| hashes.set(hashes.get() + 1));
+            7
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    #[test]
+    fn cached_hashes() {
+        let colliding = BuildHasherDefault::<CollidingHasher>::default();
+        for &(policy, strategy) in &SETUPS {
+            let mut map = HashMap::with_hasher(colliding.clone());
+            map.set_growth_policy(policy);
+            map.set_probe_strategy(strategy);
+
+            // Every key has the same hash, so only comparing the keys tells
+            // them apart.
+            for x in 0..40 {
+                map.insert(x.to_string(), x);
+            }
+            map.remove("10");
+            map.insert("20".to_string(), 200);
+            assert_eq!(map.get("10"), None);
+            assert_eq!(map.get("20"), Some(&200));
+            assert_eq!(map.get("21"), Some(&21));
+            assert_eq!(map.len(), 39);
+
+            // Growing moves every key without hashing it again.
+            let hashes = HASHES.with(Cell::get);
+            map.grow(map.raw_capacity() * 4);
+            map.set_probe_strategy(strategy);
+            map.shrink_to_fit();
+            assert_eq!(HASHES.with(Cell::get), hashes);
+            map.assert_invariants();
+            for x in (0..40).filter(|&x| x != 10) {
+                let value = if x == 20 { 200 } else { x };
+                assert_eq!(map.get(x.to_string().as_str()), Some(&value));
+            }
+        }
+    }
+
+    // How many times `f` hashes something with a CollidingHasher.
+    fn hashes_in<T>(f: impl FnOnce() -> T) -> usize {
+        let before = HASHES.with(Cell::get);
+        f();
+        HASHES.with(Cell::get) - before
+    }
+
+    #[test]
+    fn hashes_once() {
+        let colliding = BuildHasherDefault::<CollidingHasher>::default();
+        for &(policy, strategy) in &SETUPS {
+            let mut map = HashMap::with_hasher(colliding.clone());
+            map.set_growth_policy(policy);
+            map.set_probe_strategy(strategy);
+
+            // Each insert hashes its key once, whether it's new, already
+            // there, or has to grow the table first.
+            let mut grew = false;
+            for x in 0..40 {
+                let capacity = map.raw_capacity();
+                assert_eq!(hashes_in(|| map.insert(x.to_string(), x)), 1);
+                assert_eq!(hashes_in(|| map.insert(x.to_string(), x)), 1);
+                grew |= map.raw_capacity() != capacity;
+            }
+            assert!(grew);
+
+            assert_eq!(hashes_in(|| map.get("1")), 1);
+            assert_eq!(hashes_in(|| map.remove("1")), 1);
+            assert_eq!(hashes_in(|| map.entry("2".to_string()).or_insert(0)), 1);
+            assert_eq!(hashes_in(|| map.entry("1".to_string()).or_insert(1)), 1);
+            assert_eq!(hashes_in(|| map.remove("1")), 1);
+            assert_eq!(hashes_in(|| map.entry_ref("1").or_insert(1)), 1);
+            assert_eq!(
+                hashes_in(|| map.get_or_insert_with("50".to_string(), || 50)),
+                1
+            );
+
+            // A hash worked out up front isn't worked out again.
+            let hash = map.hasher().hash_one("60");
+            assert_eq!(
+                hashes_in(|| map.insert_hashed(hash, "60".to_string(), 60)),
+                0
+            );
+            assert_eq!(map.get("60"), Some(&60));
+            map.assert_invariants();
+        }
+    }
+
+    #[test]
+    fn control_bytes() {
+        let mut map = quadratic_map(FixedHashBuilder::default());
+        for x in 0..20 {
+            map.insert(x, x);
+        }
+        map.remove(&5);
+        let tombstone = map
+            .table
+            .iter()
+            .position(|slot| matches!(slot, Slot::Removed));
+        assert_eq!(map.control[tombstone.unwrap()], super::DELETED);
+        for (slot, &control) in map.table.iter().zip(&map.control) {
+            match slot {
+                Slot::Some(hash, ..) => assert_eq!(control, (hash >> 57) as u8),
+                Slot::Removed => assert_eq!(control, super::DELETED),
+                Slot::None => assert_eq!(control, super::EMPTY),
+            }
         }
+
+        map.drain().next();
+        assert!(map.control.iter().all(|&control| control == super::EMPTY));
+        map.insert(1, 1);
+        map.clear();
+        assert!(map.control.iter().all(|&control| control == super::EMPTY));
+        map.assert_invariants();
     }
 }
@@ -0,0 +1,242 @@
+use crate::hashmap::{DefaultHashBuilder, Entry, HashMap, Keys};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::{Chain, FromIterator};
+
+// A set of keys, kept in a HashMap with () for every value. () takes up no
+// room at all, so each slot is only as big as it would be for the key on
+// its own.
+pub struct HashSet<K: Hash + Eq, S = DefaultHashBuilder> {
+    map: HashMap<K, (), S>,
+}
+
+impl<K: Hash + Eq> HashSet<K> {
+    pub fn new() -> HashSet<K> {
+        HashSet {
+            map: HashMap::new(),
+        }
+    }
+
+    // Makes a set which can hold `capacity` keys before it has to grow.
+    pub fn with_capacity(capacity: usize) -> HashSet<K> {
+        HashSet {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher> HashSet<K, S> {
+    pub fn with_hasher(hash_builder: S) -> HashSet<K, S> {
+        HashSet {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    // Adds `key`, returning whether it's new. If an equal key is already
+    // there, that one's kept.
+    pub fn insert(&mut self, key: K) -> bool {
+        match self.map.entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(());
+                true
+            }
+        }
+    }
+
+    // Takes out `key`, returning whether it was there.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove(key).is_some()
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // Iterates over every key in the set, in no particular order.
+    pub fn iter(&self) -> Iter<'_, K> {
+        Iter {
+            keys: self.map.keys(),
+        }
+    }
+
+    // The keys in either set, each once: all of this one's, then the ones
+    // only in `other`.
+    pub fn union<'a>(&'a self, other: &'a HashSet<K, S>) -> Union<'a, K, S> {
+        Union {
+            inner: self.iter().chain(other.difference(self)),
+        }
+    }
+
+    // The keys in both sets.
+    pub fn intersection<'a>(&'a self, other: &'a HashSet<K, S>) -> Intersection<'a, K, S> {
+        Intersection {
+            keys: self.iter(),
+            other,
+        }
+    }
+
+    // The keys in this set but not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a HashSet<K, S>) -> Difference<'a, K, S> {
+        Difference {
+            keys: self.iter(),
+            other,
+        }
+    }
+}
+
+// An iterator over the keys in a set. See HashSet::iter.
+pub struct Iter<'a, K> {
+    keys: Keys<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for Iter<'a, K> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.keys.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Iter<'a, K> {}
+
+// See HashSet::union.
+pub struct Union<'a, K: Hash + Eq, S> {
+    inner: Chain<Iter<'a, K>, Difference<'a, K, S>>,
+}
+
+impl<'a, K: Hash + Eq, S: BuildHasher> Iterator for Union<'a, K, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next()
+    }
+}
+
+// See HashSet::intersection.
+pub struct Intersection<'a, K: Hash + Eq, S> {
+    keys: Iter<'a, K>,
+    other: &'a HashSet<K, S>,
+}
+
+impl<'a, K: Hash + Eq, S: BuildHasher> Iterator for Intersection<'a, K, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        let other = self.other;
+        self.keys.find(|key| other.contains(key))
+    }
+}
+
+// See HashSet::difference.
+pub struct Difference<'a, K: Hash + Eq, S> {
+    keys: Iter<'a, K>,
+    other: &'a HashSet<K, S>,
+}
+
+impl<'a, K: Hash + Eq, S: BuildHasher> Iterator for Difference<'a, K, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        let other = self.other;
+        self.keys.find(|key| !other.contains(key))
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, S: BuildHasher> fmt::Debug for HashSet<K, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher + Default> Default for HashSet<K, S> {
+    fn default() -> HashSet<K, S> {
+        HashSet::with_hasher(S::default())
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher + Default> FromIterator<K> for HashSet<K, S> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> HashSet<K, S> {
+        let mut set = HashSet::default();
+        for key in iter {
+            set.insert(key);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashSet;
+
+    fn sorted<'a>(keys: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut keys: Vec<u32> = keys.copied().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut set = HashSet::new();
+        assert!(set.is_empty());
+        assert!(set.insert(1));
+        assert!(set.insert(2));
+        assert!(!set.insert(1));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&1));
+        assert!(!set.contains(&3));
+
+        assert!(set.remove(&1));
+        assert!(!set.remove(&1));
+        assert_eq!(sorted(set.iter()), &[2]);
+        assert_eq!(set.iter().len(), 1);
+
+        let mut words = HashSet::with_capacity(10);
+        words.insert(String::from("a"));
+        assert!(words.contains("a"));
+        assert!(words.remove("a"));
+        assert!(words.is_empty());
+    }
+
+    #[test]
+    fn set_algebra() {
+        let evens: HashSet<u32> = (0..20).step_by(2).collect();
+        let threes: HashSet<u32> = (0..20).step_by(3).collect();
+
+        assert_eq!(
+            sorted(evens.union(&threes)),
+            &[0, 2, 3, 4, 6, 8, 9, 10, 12, 14, 15, 16, 18]
+        );
+        assert_eq!(sorted(evens.intersection(&threes)), &[0, 6, 12, 18]);
+        assert_eq!(sorted(threes.intersection(&evens)), &[0, 6, 12, 18]);
+        assert_eq!(sorted(evens.difference(&threes)), &[2, 4, 8, 10, 14, 16]);
+        assert_eq!(sorted(threes.difference(&evens)), &[3, 9, 15]);
+
+        let empty = HashSet::new();
+        assert_eq!(sorted(evens.union(&empty)), sorted(evens.iter()));
+        assert_eq!(evens.intersection(&empty).count(), 0);
+        assert_eq!(empty.difference(&evens).count(), 0);
+        assert_eq!(format!("{:?}", empty), "{}");
+    }
+}
@@ -1,4 +1,10 @@
+pub mod dense;
 pub mod hashmap;
+pub mod hashset;
+pub mod lru;
+
+#[cfg(test)]
+mod test_support;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,218 @@
+use crate::hashmap::HashMap;
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::mem;
+
+// A map which holds at most a fixed number of entries, throwing out the
+// one that was used longest ago to make room for a new one.
+//
+// The entries live side by side in a Vec, linked together from most to
+// least recently used by their indexes, and a HashMap finds a key's entry.
+// Each key is kept in both, so they have to be Clone.
+pub struct LruMap<K: Hash + Eq, V> {
+    index: HashMap<K, usize>,
+    nodes: Vec<Node<K, V>>,
+
+    // The most and least recently used entries, if there are any
+    head: Option<usize>,
+    tail: Option<usize>,
+
+    max_entries: usize,
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+
+    // The entries used just after and just before this one
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruMap<K, V> {
+    // Makes a map which holds up to `max_entries` entries, which has to be
+    // at least 1.
+    pub fn new(max_entries: usize) -> LruMap<K, V> {
+        assert!(max_entries > 0, "an LruMap has to hold at least one entry");
+        LruMap {
+            index: HashMap::with_capacity(max_entries),
+            nodes: Vec::with_capacity(max_entries),
+            head: None,
+            tail: None,
+            max_entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    // Sets the value for `key`, which becomes the most recently used. If
+    // that needs room making, the least recently used entry is thrown out
+    // and returned.
+    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(&i) = self.index.get(&key) {
+            self.nodes[i].value = value;
+            self.touch(i);
+            return None;
+        }
+
+        let node = Node {
+            key: key.clone(),
+            value,
+            prev: None,
+            next: None,
+        };
+        if self.nodes.len() < self.max_entries {
+            let i = self.nodes.len();
+            self.nodes.push(node);
+            self.index.insert(key, i);
+            self.push_front(i);
+            return None;
+        }
+
+        // Full, so the new entry takes the oldest one's place.
+        let i = self.tail.unwrap();
+        self.unlink(i);
+        let old = mem::replace(&mut self.nodes[i], node);
+        self.index.remove(&old.key);
+        self.index.insert(key, i);
+        self.push_front(i);
+        Some((old.key, old.value))
+    }
+
+    // Gets the value for `key`, making it the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let i = *self.index.get(key)?;
+        self.touch(i);
+        Some(&self.nodes[i].value)
+    }
+
+    // Whether `key` is in the map, without counting as a use of it.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    // Moves entry `i` to the front of the list.
+    fn touch(&mut self, i: usize) {
+        if self.head != Some(i) {
+            self.unlink(i);
+            self.push_front(i);
+        }
+    }
+
+    // Takes entry `i` out of the list, joining up its neighbors.
+    fn unlink(&mut self, i: usize) {
+        let (prev, next) = (self.nodes[i].prev, self.nodes[i].next);
+        match prev {
+            Some(prev) => self.nodes[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.nodes[next].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    // Puts entry `i`, which isn't in the list, at the front of it.
+    fn push_front(&mut self, i: usize) {
+        self.nodes[i].prev = None;
+        self.nodes[i].next = self.head;
+        match self.head {
+            Some(head) => self.nodes[head].prev = Some(i),
+            None => self.tail = Some(i),
+        }
+        self.head = Some(i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruMap;
+
+    #[test]
+    fn eviction_order() {
+        let mut map = LruMap::new(3);
+        assert_eq!(map.put("a", 1), None);
+        assert_eq!(map.put("b", 2), None);
+        assert_eq!(map.put("c", 3), None);
+        assert_eq!(map.len(), 3);
+
+        // Using "a" saves it, so "b" is the oldest.
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.put("d", 4), Some(("b", 2)));
+        assert!(!map.contains("b"));
+
+        // Overwriting counts as a use too, and doesn't evict anything.
+        assert_eq!(map.put("c", 30), None);
+        assert_eq!(map.put("e", 5), Some(("a", 1)));
+        assert_eq!(map.put("f", 6), Some(("d", 4)));
+        assert_eq!(map.get("c"), Some(&30));
+        assert_eq!(map.get("d"), None);
+        assert_eq!(map.put("g", 7), Some(("e", 5)));
+        assert_eq!(map.put("h", 8), Some(("f", 6)));
+        assert_eq!(map.put("i", 9), Some(("c", 30)));
+
+        let mut one = LruMap::new(1);
+        one.put(1, 1);
+        assert_eq!(one.get(&1), Some(&1));
+        assert_eq!(one.put(2, 2), Some((1, 1)));
+        assert_eq!(one.get(&1), None);
+    }
+
+    #[test]
+    fn matches_a_list() {
+        // Checks a long run of mixed gets and puts against a plain list,
+        // most recently used first.
+        let mut map = LruMap::new(10);
+        let mut list: Vec<(u32, u32)> = Vec::new();
+        let mut x: u32 = 1;
+        for step in 0..5000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let key = (x >> 16) % 25;
+            let position = list.iter().position(|&(k, _)| k == key);
+            if x.is_multiple_of(3) {
+                let expected = position.map(|i| list.remove(i));
+                assert_eq!(map.get(&key), expected.as_ref().map(|(_, v)| v));
+                if let Some(entry) = expected {
+                    list.insert(0, entry);
+                }
+            } else {
+                let evicted = match position {
+                    Some(i) => {
+                        list.remove(i);
+                        None
+                    }
+                    None if list.len() == 10 => list.pop(),
+                    None => None,
+                };
+                assert_eq!(map.put(key, step), evicted);
+                list.insert(0, (key, step));
+            }
+            assert_eq!(map.len(), list.len());
+            assert!(map.len() <= map.max_entries());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one entry")]
+    fn empty_lru() {
+        LruMap::<u32, u32>::new(0);
+    }
+}
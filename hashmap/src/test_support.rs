@@ -0,0 +1,32 @@
+// Types for checking that maps drop what they hold, and only once.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// A value that adds one to a shared count when it's dropped.
+#[derive(Debug)]
+pub struct DropCounter(pub Rc<Cell<usize>>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+// A value that writes its id to a shared log when it's dropped, so tests can
+// check which values went, and that none went twice.
+#[derive(Debug)]
+pub struct DropLog(pub u32, pub Rc<RefCell<Vec<u32>>>);
+
+impl Drop for DropLog {
+    fn drop(&mut self) {
+        self.1.borrow_mut().push(self.0);
+    }
+}
+
+// The ids in `log`, sorted.
+pub fn dropped(log: &RefCell<Vec<u32>>) -> Vec<u32> {
+    let mut ids = log.borrow().clone();
+    ids.sort();
+    ids
+}